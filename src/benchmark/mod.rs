@@ -0,0 +1,131 @@
+use crate::cli;
+use crate::dataset::load;
+use crate::recombination;
+use crate::utils::{exit, table::Table};
+use color_eyre::eyre::{eyre, Report, Result};
+use itertools::Itertools;
+use log::{debug, info};
+use std::time::Instant;
+
+/// Benchmark a standard workload.
+///
+/// The workload is the dataset recombinant populations (or all populations,
+/// if the dataset has no phylogeny), in sorted order, so that repeated runs
+/// on the same dataset are comparable. Phases are run on a single thread,
+/// to keep the timings (and flamegraphs) easy to interpret.
+pub fn benchmark(args: &cli::benchmark::Args) -> Result<(), Report> {
+    // use the default run parameters for all phases
+    let run_args = cli::run::Args {
+        dataset_dir: args.dataset_dir.clone(),
+        ..Default::default()
+    };
+
+    let mut phases = Vec::new();
+
+    // ------------------------------------------------------------------------
+    // Load Dataset
+
+    info!("Loading dataset: {:?}", args.dataset_dir);
+    let start = Instant::now();
+    let dataset = load::dataset(&args.dataset_dir, &run_args.mask, None, false)?;
+    phases.push(("load_dataset", 1, start.elapsed().as_secs_f64()));
+
+    // ------------------------------------------------------------------------
+    // Workload
+
+    let mut sequences = dataset
+        .populations
+        .iter()
+        .filter(|(p, _)| dataset.phylogeny.recombinants_all.contains(p))
+        .map(|(_, sequence)| sequence)
+        .collect_vec();
+    if sequences.is_empty() {
+        sequences = dataset.populations.values().collect_vec();
+    }
+    sequences.truncate(args.limit);
+    if sequences.is_empty() {
+        return Err(eyre!(exit::Code::EmptyResult)
+            .wrap_err("The dataset has no populations to benchmark."));
+    }
+    info!("Benchmarking {} sequences.", sequences.len());
+
+    // ------------------------------------------------------------------------
+    // Best Match (Consensus)
+
+    let start = Instant::now();
+    let best_matches = sequences
+        .iter()
+        .map(|sequence| {
+            dataset.search(
+                sequence,
+                None,
+                None,
+                run_args.candidate_margin,
+                run_args.score_deletions,
+                run_args.score_ambiguity,
+            )
+        })
+        .collect_vec();
+    phases.push(("search", sequences.len(), start.elapsed().as_secs_f64()));
+
+    // ------------------------------------------------------------------------
+    // Recombination Search
+
+    let parent_search_populations = dataset.populations.keys().collect_vec();
+    let mut num_searched = 0;
+    let mut num_recombinants = 0;
+
+    let start = Instant::now();
+    for (sequence, best_match) in sequences.iter().zip(best_matches) {
+        let Ok(mut best_match) = best_match else {
+            continue;
+        };
+        let mut trace = Vec::new();
+        num_searched += 1;
+        match recombination::search::all_parents(
+            sequence,
+            &dataset,
+            &mut best_match,
+            &parent_search_populations,
+            &run_args,
+            &mut trace,
+        ) {
+            Ok(recombination) if !recombination.parents.is_empty() => {
+                num_recombinants += 1
+            }
+            Ok(_) => {}
+            Err(e) => debug!("Parent search did not succeed. {e}"),
+        }
+    }
+    phases.push(("parent_search", num_searched, start.elapsed().as_secs_f64()));
+    info!("Detected recombination in {num_recombinants}/{num_searched} sequences.");
+
+    // ------------------------------------------------------------------------
+    // Report
+
+    let mut table = Table::new();
+    table.headers = vec!["phase", "sequences", "seconds", "sequences_per_second"]
+        .into_iter()
+        .map(String::from)
+        .collect_vec();
+    table.rows = phases
+        .into_iter()
+        .map(|(phase, num_sequences, seconds)| {
+            let per_second = if seconds > 0.0 {
+                num_sequences as f64 / seconds
+            } else {
+                0.0
+            };
+            vec![
+                phase.to_string(),
+                num_sequences.to_string(),
+                format!("{seconds:.3}"),
+                format!("{per_second:.1}"),
+            ]
+        })
+        .collect_vec();
+
+    println!("\n{}", table.to_markdown()?);
+
+    Ok(())
+}
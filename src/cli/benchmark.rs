@@ -0,0 +1,19 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+/// Benchmark a standard workload.
+///
+/// Classifies and searches for recombination parents in the dataset
+/// recombinant populations, and reports the timing of each phase.
+/// Suitable for profiling with: cargo flamegraph -- benchmark -d <dir>
+#[derive(Parser, Debug)]
+#[clap(verbatim_doc_comment)]
+pub struct Args {
+    /// Dataset directory.
+    #[clap(short = 'd', long, required = true)]
+    pub dataset_dir: PathBuf,
+
+    /// Number of sequences in the workload.
+    #[clap(short = 'n', long, default_value_t = 100)]
+    pub limit: usize,
+}
@@ -0,0 +1,13 @@
+use clap::Parser;
+use clap_complete::Shell;
+
+/// Generate shell completions.
+///
+/// Ex. rebar completions bash > ~/.local/share/bash-completion/completions/rebar
+#[derive(Clone, Debug, Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Args {
+    /// Shell to generate completions for.
+    #[clap(value_enum)]
+    pub shell: Shell,
+}
@@ -0,0 +1,30 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+/// Build a custom dataset from local files.
+///
+/// Constructs a dataset directory, without downloading, from a reference
+/// fasta, a populations alignment (fasta), and a phylogeny edge list (TSV
+/// with the columns: parent, child). Populations with multiple parents are
+/// recombinants.
+#[derive(Parser, Debug)]
+#[clap(verbatim_doc_comment)]
+pub struct Args {
+    /// Reference sequence (fasta).
+    #[clap(short = 'r', long, required = true)]
+    pub reference: PathBuf,
+
+    /// Populations alignment to the reference (fasta).
+    #[clap(short = 'p', long, required = true)]
+    pub populations: PathBuf,
+
+    /// Phylogeny edge list (TSV), with the columns: parent, child.
+    #[clap(short = 'g', long, required = true)]
+    pub phylogeny: PathBuf,
+
+    /// Output directory.
+    ///
+    /// If the directory does not exist, it will be created.
+    #[clap(short = 'o', long, required = true)]
+    pub output_dir: PathBuf,
+}
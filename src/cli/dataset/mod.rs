@@ -1,9 +1,12 @@
+pub mod build;
 pub mod download;
 pub mod list;
+pub mod plot_tree;
+pub mod stats;
 
 use clap::{Parser, Subcommand};
 
-/// List or download datasets.
+/// List, download, build, or summarize datasets.
 #[derive(Parser, Debug)]
 #[clap(verbatim_doc_comment)]
 pub struct Args {
@@ -11,7 +14,7 @@ pub struct Args {
     pub command: Command,
 }
 
-/// List or download datasets.
+/// List, download, build, or summarize datasets.
 #[derive(Subcommand, Debug)]
 #[clap(verbatim_doc_comment)]
 pub enum Command {
@@ -20,4 +23,13 @@ pub enum Command {
 
     /// Download dataset.
     Download(download::Args),
+
+    /// Build a custom dataset from local files.
+    Build(build::Args),
+
+    /// Summarize dataset diversity.
+    Stats(stats::Args),
+
+    /// Plot the dataset phylogeny.
+    PlotTree(plot_tree::Args),
 }
@@ -0,0 +1,18 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+// -----------------------------------------------------------------------------
+// Dataset Plot Tree
+
+/// Plot the dataset phylogeny.
+#[derive(Parser, Debug)]
+#[clap(verbatim_doc_comment)]
+pub struct Args {
+    /// Dataset directory.
+    #[clap(short = 'd', long, required = true)]
+    pub dataset_dir: PathBuf,
+
+    /// Output image of the phylogeny (PNG).
+    #[clap(short = 'o', long, required = true)]
+    pub output: PathBuf,
+}
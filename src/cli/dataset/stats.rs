@@ -0,0 +1,22 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+// -----------------------------------------------------------------------------
+// Dataset Stats
+
+/// Summarize dataset diversity.
+#[derive(Parser, Debug)]
+#[clap(verbatim_doc_comment)]
+pub struct Args {
+    /// Dataset directory.
+    #[clap(short = 'd', long, required = true)]
+    pub dataset_dir: PathBuf,
+
+    /// Number of most homoplasic sites to report.
+    #[clap(short = 'n', long, default_value_t = 10)]
+    pub top: usize,
+
+    /// Write all sites, with the number of populations they occur in, to this file (TSV).
+    #[clap(short = 'o', long)]
+    pub output: Option<PathBuf>,
+}
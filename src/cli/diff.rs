@@ -0,0 +1,41 @@
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Compare two linelists sample-by-sample.
+///
+/// Samples are joined on 'strain', and differences in the population,
+/// recombinant, parents, and breakpoints are reported.
+#[derive(Clone, Debug, Deserialize, Parser, Serialize)]
+#[clap(verbatim_doc_comment)]
+pub struct Args {
+    /// Original linelist (ex. from a previous dataset or parameters).
+    #[clap(long, required = true)]
+    pub a: PathBuf,
+
+    /// New linelist to compare against the original.
+    #[clap(long, required = true)]
+    pub b: PathBuf,
+
+    /// Write the per-sample differences to this file (TSV).
+    ///
+    /// If not provided, the differences are printed to stdout.
+    #[clap(short = 'o', long)]
+    pub output: Option<PathBuf>,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Args {
+    pub fn new() -> Self {
+        Args {
+            a: PathBuf::new(),
+            b: PathBuf::new(),
+            output: None,
+        }
+    }
+}
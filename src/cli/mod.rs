@@ -1,4 +1,7 @@
+pub mod benchmark;
+pub mod completions;
 pub mod dataset;
+pub mod diff;
 pub mod plot;
 pub mod run;
 pub mod simulate;
@@ -31,7 +34,7 @@ pub struct Cli {
     pub verbosity: Verbosity,
 }
 
-/// Rebar CLI commands (dataset, run, plot).
+/// Rebar CLI commands (dataset, run, plot, simulate, diff-linelist, benchmark, completions).
 #[derive(Subcommand, Debug)]
 #[clap(verbatim_doc_comment)]
 pub enum Command {
@@ -39,6 +42,9 @@ pub enum Command {
     Run(Box<run::Args>),
     Plot(Box<plot::Args>),
     Simulate(Box<simulate::Args>),
+    DiffLinelist(Box<diff::Args>),
+    Benchmark(Box<benchmark::Args>),
+    Completions(Box<completions::Args>),
 }
 
 // -----------------------------------------------------------------------------
@@ -8,17 +8,33 @@ pub struct Args {
     /// Output directory from rebar run.
     ///
     /// Will plot all TSV files under barcodes/
-    #[clap(short = 'r', long, required = true)]
-    pub run_dir: PathBuf,
+    #[clap(short = 'r', long, required_unless_present = "bundle")]
+    pub run_dir: Option<PathBuf>,
+
+    /// Plot bundle from rebar run (--plot-bundle), instead of a --run-dir.
+    ///
+    /// Plots are written to 'plots/' next to the bundle, unless --output-dir is used.
+    #[clap(long, conflicts_with_all = ["run_dir", "dataset_dir", "annotations"])]
+    pub bundle: Option<PathBuf>,
 
     /// A single barcodes TSV file to plot.
     #[clap(short = 'b', long)]
     pub barcodes_file: Option<PathBuf>,
 
     /// Dataset genome annotations.
+    ///
+    /// An optional 'track' column groups annotations (ex. genes, domains) into
+    /// separate bands of the plot.
     #[clap(short = 'a', long)]
     pub annotations: Option<PathBuf>,
 
+    /// Dataset directory used for the run.
+    ///
+    /// Used to check that the run is compatible with the dataset (name, tag,
+    /// and genome length), and to default --annotations.
+    #[clap(short = 'd', long)]
+    pub dataset_dir: Option<PathBuf>,
+
     /// Output directory for plots.
     ///
     /// Otherwise will default to 'plots/' under the --run-dir
@@ -33,6 +49,39 @@ pub struct Args {
     /// the genome size and number of samples, this may cause a crash.
     #[clap(short = 'p', long)]
     pub all_coords: bool,
+
+    /// Omit sites with private mutations from the plot.
+    ///
+    /// Declutters figures focused on parental structure.
+    #[clap(long, conflicts_with = "private_only")]
+    pub hide_private: bool,
+
+    /// Only plot sites with private mutations.
+    ///
+    /// Includes private sites that are not discriminating between parents.
+    #[clap(long)]
+    pub private_only: bool,
+
+    /// Label parental regions with their span and number of substitutions.
+    ///
+    /// Ex. 'BA.2.75 (12 subs, 21k-24k)'
+    #[clap(long)]
+    pub region_details: bool,
+
+    /// Order samples by the similarity of their barcodes.
+    ///
+    /// Samples are sorted by their first breakpoint, then by the parent matched
+    /// at each site, so that similar samples are drawn next to each other.
+    /// Otherwise, samples are drawn in the order of the barcodes file.
+    #[clap(long)]
+    pub cluster_samples: bool,
+
+    /// Maximum number of sites (coordinates) to draw in a single plot.
+    ///
+    /// Wide barcodes are split into multiple plots (<key>.part1.png, <key>.part2.png, ...),
+    /// each with the legend and parental regions.
+    #[clap(long)]
+    pub max_sites_per_plot: Option<usize>,
 }
 
 impl Default for Args {
@@ -45,10 +94,17 @@ impl Args {
     pub fn new() -> Self {
         Args {
             annotations: None,
-            run_dir: PathBuf::new(),
+            run_dir: None,
+            bundle: None,
             barcodes_file: None,
+            dataset_dir: None,
             output_dir: None,
             all_coords: false,
+            hide_private: false,
+            private_only: false,
+            region_details: false,
+            cluster_samples: false,
+            max_sites_per_plot: None,
         }
     }
 }
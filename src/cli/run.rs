@@ -1,4 +1,5 @@
-use clap::{Args as ClapArgs, Parser};
+use crate::recombination::PrivatePolicy;
+use clap::{Args as ClapArgs, Parser, ValueEnum};
 use color_eyre::eyre::{Report, Result, WrapErr};
 use either::*;
 use serde::{Deserialize, Serialize};
@@ -22,14 +23,144 @@ pub struct Args {
     #[serde(skip_serializing_if = "Args::is_default_input", skip_deserializing)]
     pub input: Input,
 
+    /// Reference record id in the dataset reference fasta.
+    ///
+    /// If not provided, the first record is used.
+    #[arg(long)]
+    #[serde(default)]
+    pub reference_id: Option<String>,
+
+    /// Treat problems with the dataset as errors, rather than warnings.
+    ///
+    /// For example, duplicated population names in the populations fasta,
+    /// where only the first record is otherwise retained.
+    #[clap(long, default_value_t = Args::default().strict)]
+    #[serde(default)]
+    pub strict: bool,
+
+    /// Validate the --alignment before the search, reporting all problems at once.
+    ///
+    /// Checks record validity, lengths, and duplicate ids. Fails if any
+    /// problems are found, unless --skip-invalid is used.
+    #[arg(long, default_value_t = Args::default().validate_input)]
+    #[serde(default)]
+    pub validate_input: bool,
+
+    /// Maximum number of warnings to log per category (ex. duplicate IDs).
+    ///
+    /// Additional warnings are counted, and summarized at the end of the run.
+    #[arg(long, default_value_t = Args::default().max_warnings)]
+    #[serde(default)]
+    pub max_warnings: usize,
+
+    /// Skip invalid records in the --alignment, rather than failing the run.
+    ///
+    /// Invalid records (ex. empty, invalid characters, length mismatch with the
+    /// reference) are written with the reason to 'skipped.tsv' under the
+    /// --output-dir.
+    #[arg(long, default_value_t = Args::default().skip_invalid)]
+    #[serde(default)]
+    pub skip_invalid: bool,
+
+    /// Record insertions relative to the reference in the --alignment.
+    ///
+    /// The --alignment must include the dataset reference as a (gapped)
+    /// record, with the same id. Columns with a gap in that record are
+    /// recorded as insertions (in 'recombinations.json'), and all other
+    /// coordinates are in the ungapped reference. The reference record itself
+    /// is not searched.
+    #[arg(long, default_value_t = Args::default().record_insertions)]
+    #[serde(default)]
+    pub record_insertions: bool,
+
+    /// Only process the first N query sequences.
+    ///
+    /// Useful for quickly testing parameters on a large --alignment.
+    #[clap(long)]
+    #[serde(default)]
+    pub limit: Option<usize>,
+
+    /// Process a random sample (fraction between 0 and 1) of query sequences.
+    ///
+    /// Applied after --limit. Use --seed for a reproducible sample.
+    #[clap(long)]
+    #[serde(default)]
+    pub sample: Option<f32>,
+
+    /// Random seed for --sample.
+    #[clap(long, requires = "sample")]
+    #[serde(default)]
+    pub seed: Option<u64>,
+
     // Hidden attribute, will be used for edge cases.
     #[arg(hide = true)]
     pub population: Option<String>,
 
+    /// Only use input populations that have sequence data.
+    ///
+    /// Populations without sequence data (ex. only in the phylogeny) are
+    /// substituted with their nearest ancestor that has sequence data.
+    #[arg(long, default_value_t = Args::default().require_sequence)]
+    #[serde(default)]
+    pub require_sequence: bool,
+
+    /// Keep the original names of input populations, without the 'population_' prefix.
+    ///
+    /// If an --alignment record has the same id as an input population, the
+    /// population is kept and the record is dropped as a duplicate.
+    #[arg(long, default_value_t = Args::default().no_population_prefix)]
+    #[serde(default)]
+    pub no_population_prefix: bool,
+
+    /// Ignore the dataset phylogeny, and run purely on sequence parsimony.
+    ///
+    /// The best match is the top population (rather than the common ancestor of
+    /// all top populations), and known recombinants are not used.
+    #[arg(long, conflicts_with_all = ["knockout_validate", "recombinants_only"])]
+    #[serde(default)]
+    pub no_phylogeny: bool,
+
+    /// Restrict the dataset to just these populations, before searching.
+    ///
+    /// Unlike --parents, this also restricts the best match (consensus)
+    /// search. Use '*' for descendants, ex. 'BA.2*,BA.5*'.
+    #[arg(long, value_delimiter = ',')]
+    #[serde(default)]
+    pub subset: Option<Vec<String>>,
+
+    /// Prefer these populations when breaking ties in the consensus search.
+    ///
+    /// A TSV with columns 'population' and 'prior_weight'. Among populations
+    /// with equal score and support, only those with the highest prior are
+    /// used for the consensus. Populations not in the file have a prior of 1.0.
+    #[clap(long)]
+    #[serde(default)]
+    pub priors: Option<PathBuf>,
+
     /// Restrict parent search to just these candidate parents.
+    ///
+    /// Use '*' for descendants in the phylogeny, or match population names
+    /// directly with 'glob:BA.5.*' or 'regex:^BA\.5'.
     #[arg(long, value_delimiter = ',')]
     pub parents: Option<Vec<String>>,
 
+    /// Exclude populations with fewer substitutions than this from the parent search.
+    ///
+    /// Near-reference populations have few defining substitutions, and can be
+    /// spuriously chosen as parents. They are still used for the best match.
+    #[arg(long, default_value_t = Args::default().parent_min_subs)]
+    #[serde(default)]
+    pub parent_min_subs: usize,
+
+    /// Try the designated parents of known recombinants before the full search.
+    ///
+    /// If the best match is a designated recombinant, and its parents from the
+    /// phylogeny explain the sequence, the exhaustive parent search is skipped.
+    /// Otherwise, falls back to the full search.
+    #[arg(long, conflicts_with = "naive")]
+    #[serde(default)]
+    pub parents_auto: bool,
+
     /// Remove these populations from the dataset.
     ///
     /// Regardless of whether you use '*' or not, all descendants of the
@@ -37,6 +168,16 @@ pub struct Args {
     #[arg(short = 'k', long)]
     pub knockout: Option<Vec<String>>,
 
+    /// Validate the parents of knocked out recombinants.
+    ///
+    /// For each knocked out recombinant in the input, compare the detected
+    /// parents to the designated parents (from the phylogeny), and write a
+    /// pass/fail report to 'knockout_validate.tsv' under the --output-dir.
+    /// Breakpoints are included for review, but are not scored.
+    #[arg(long, requires = "knockout")]
+    #[serde(default)]
+    pub knockout_validate: bool,
+
     /// Number of bases to mask at the 5' and 3' ends.
     ///
     /// Comma separated. Use --mask 0,0 to disable masking.
@@ -44,7 +185,61 @@ pub struct Args {
     #[arg(long, value_delimiter = ',')]
     pub mask: Vec<usize>,
 
+    /// Retain candidate populations within this many matching substitutions
+    /// of the best candidate.
+    ///
+    /// The default (0) keeps only populations tied for the most matches. A
+    /// wider margin can recover the true parent when there is a near-tie, at
+    /// the cost of run time.
+    #[arg(long, default_value_t = Args::default().candidate_margin)]
+    #[serde(default)]
+    pub candidate_margin: usize,
+
+    /// Report the alternative call when it scores within this margin.
+    ///
+    /// For recombinants, the alternative is the non-recombinant best match.
+    /// For non-recombinants, it is the best recombinant hypothesis that was
+    /// rejected. Written to the 'alternative_call' column of the linelist, as
+    /// the population (or parents) and their score.
+    #[arg(long)]
+    #[serde(default)]
+    pub alternative_margin: Option<usize>,
+
+    /// Score deletions, in addition to substitutions, when comparing to populations.
+    ///
+    /// Helps distinguish populations that differ only by deletions. By default,
+    /// coordinates with deletions are excluded from the score.
+    #[arg(long, default_value_t = Args::default().score_deletions)]
+    #[serde(default)]
+    pub score_deletions: bool,
+
+    /// Score IUPAC ambiguity codes when comparing to populations.
+    ///
+    /// An ambiguity code compatible with a population base is support, and
+    /// compatible with neither the population nor reference base is conflict.
+    /// By default, ambiguous coordinates are missing data. 'N' is always missing.
+    #[arg(long, default_value_t = Args::default().score_ambiguity)]
+    #[serde(default)]
+    pub score_ambiguity: bool,
+
+    /// Minimum depth (distance from root in the phylogeny) of the consensus population.
+    ///
+    /// When the top populations are scattered, their common ancestor can be a
+    /// very broad call. Shallower consensus populations are reported as
+    /// ambiguous (no population) instead. The default (0) accepts any depth.
+    #[arg(long, default_value_t = Args::default().min_consensus_depth)]
+    #[serde(default)]
+    pub min_consensus_depth: usize,
+
     /// Maximum number of search iterations to find each parent.
+    ///
+    /// Each iteration searches the dataset for a candidate parent that resolves
+    /// the remaining conflicts, and tests it for recombination. More iterations
+    /// explore more candidates, which can help recursive recombinants (whose
+    /// parents are themselves recombinants), at the cost of run time.
+    ///
+    /// The search also stops early when adding a parent no longer improves
+    /// the combined parsimony score.
     #[arg(short = 'i', long, default_value_t = Args::default().max_iter)]
     pub max_iter: usize,
 
@@ -60,6 +255,15 @@ pub struct Args {
     #[arg(short = 'c', long, default_value_t = Args::default().min_consecutive)]
     pub min_consecutive: usize,
 
+    /// Number of interrupting sites tolerated within a parental region.
+    ///
+    /// A single site from another parent (ex. homoplasy or sequencing error) can
+    /// break an otherwise consecutive region. Up to this many interrupting sites
+    /// are bridged, rather than splitting the region. The default (0) disables this.
+    #[arg(long, default_value_t = Args::default().consecutive_gap_tolerance)]
+    #[serde(default)]
+    pub consecutive_gap_tolerance: usize,
+
     /// Minimum length of a parental region.
     #[arg(short = 'l', long, default_value_t = Args::default().min_length)]
     pub min_length: usize,
@@ -68,10 +272,85 @@ pub struct Args {
     #[arg(short = 's', long, default_value_t = Args::default().min_subs)]
     pub min_subs: usize,
 
+    /// Extra discriminating sites required between parents, otherwise warn.
+    ///
+    /// Parents need at least --min-subs discriminating sites each to pass the
+    /// region filters. Parents with fewer than this margin on top of that are
+    /// nearly identical, and regions can be assigned arbitrarily between them,
+    /// so the recombination call is flagged as possibly unstable.
+    #[arg(long, default_value_t = Args::default().similar_parents_margin)]
+    #[serde(default)]
+    pub similar_parents_margin: usize,
+
+    /// Maximum number of breakpoints, otherwise no recombination is detected.
+    ///
+    /// Real recombinants rarely have more than a few breakpoints, many more can
+    /// indicate a poorly aligned or chimeric assembly. By default, unlimited.
+    #[arg(long)]
+    #[serde(default)]
+    pub max_breakpoints: Option<usize>,
+
     /// Run a naive search, which does not use information about edge cases or known recombinant parents.
     #[arg(short = 'u', long, default_value_t = Args::default().naive)]
     pub naive: bool,
 
+    /// QC: Minimum genome coverage (fraction of non-missing bases), otherwise fail.
+    #[arg(long, default_value_t = Args::default().qc_min_coverage)]
+    #[serde(default)]
+    pub qc_min_coverage: f32,
+
+    /// QC: Maximum fraction of ambiguous (IUPAC) bases, otherwise fail.
+    #[arg(long, default_value_t = Args::default().qc_max_ambiguity)]
+    #[serde(default)]
+    pub qc_max_ambiguity: f32,
+
+    /// QC: Maximum number of private substitutions, otherwise warn.
+    #[arg(long, default_value_t = Args::default().qc_max_private)]
+    #[serde(default)]
+    pub qc_max_private: usize,
+
+    /// QC: Minimum confidence in the population call, otherwise warn.
+    ///
+    /// Confidence is the fraction of the population's informative substitutions
+    /// that support (rather than conflict with) the sequence.
+    #[arg(long, default_value_t = Args::default().qc_min_confidence)]
+    #[serde(default)]
+    pub qc_min_confidence: f32,
+
+    /// Flag consensus calls with a score below this as a 'low_quality_match'.
+    ///
+    /// The score is support minus conflict. A negative score means the
+    /// sequence conflicts with its best match more than it supports it.
+    #[arg(long, default_value_t = Args::default().min_match_score)]
+    #[arg(allow_negative_numbers = true)]
+    #[serde(default)]
+    pub min_match_score: isize,
+
+    /// Minimum number of ambiguous and conflicting sites to flag a possible mixture.
+    ///
+    /// Mixtures (ex. co-infections) are flagged as 'possible_mixture' in the
+    /// linelist, when these sites are scattered across the genome rather than
+    /// clustered into parental regions. Use 0 to disable.
+    #[arg(long, default_value_t = Args::default().mixture_min_sites)]
+    #[serde(default)]
+    pub mixture_min_sites: usize,
+
+    /// How private substitutions of recombinants are assigned an origin.
+    ///
+    /// 'best-match' assigns private subs shared with the consensus best match to the
+    /// best match, 'private' reports all of them as private.
+    #[clap(long, value_enum, default_value_t = PrivatePolicy::default())]
+    #[serde(default)]
+    pub private_policy: PrivatePolicy,
+
+    /// Report reversions in the substitution origins.
+    ///
+    /// Reversions are parental (or best match) substitutions that are the
+    /// reference base in the sequence, reported with the origin 'reversion'.
+    #[clap(long)]
+    #[serde(default)]
+    pub report_reversions: bool,
+
     /// Output directory.
     ///
     /// If the directory does not exist, it will be created.
@@ -82,6 +361,171 @@ pub struct Args {
     )]
     pub output_dir: PathBuf,
 
+    /// Compress all TSV outputs (linelist, barcodes) with gzip (.tsv.gz).
+    #[clap(long, default_value_t = Args::default().compress_output)]
+    #[serde(default)]
+    pub compress_output: bool,
+
+    /// Which output artifacts to write (comma separated).
+    ///
+    /// 'args' is run_args.json, 'provenance' is dataset_provenance.json,
+    /// 'linelist' is linelist.tsv, and 'barcodes' is the barcodes/ directory.
+    /// Barcodes are not built unless requested (or needed for --plot-bundle).
+    /// 'report' is summary.txt and summary.json, and is not written by default
+    /// (--summary is equivalent to adding it).
+    #[arg(long, value_enum, value_delimiter = ',')]
+    #[arg(default_values_t = Args::default().outputs)]
+    #[serde(default = "Output::all")]
+    pub outputs: Vec<Output>,
+
+    /// Genome coordinate base of the outputs (0 or 1).
+    ///
+    /// Coordinates are 1-based internally. With 0, coordinates are shifted
+    /// down by one in: the substitutions, breakpoints, and regions of the
+    /// linelist; the 'coord' column of the barcodes and substitutions tables;
+    /// the breakpoints of the knockout validation, parent pairs, and pangolin
+    /// notes; and the regions of the subthreshold table. The breakpoints BED
+    /// (--emit-bed) always follows the BED convention of 0-based, half-open
+    /// intervals. These remain 1-based: the unique_key (and the barcodes file
+    /// names), the proposed_name, and recombinations.json. The linelist
+    /// 'coordinate_base' column records the base, for plotting.
+    #[arg(long, value_enum, default_value_t = CoordinateBase::default())]
+    #[serde(default)]
+    pub coordinate_base: CoordinateBase,
+
+    /// Only write recombinant samples to the linelist.
+    ///
+    /// All samples are still processed, but non-recombinant samples are
+    /// excluded from the linelist. Barcodes are only written for recombinants.
+    #[clap(long, default_value_t = Args::default().recombinants_only_output)]
+    #[serde(default)]
+    pub recombinants_only_output: bool,
+
+    /// Write barcodes per recombinant (combining samples), or per sample.
+    ///
+    /// 'recombinant' writes one barcodes file per recombinant unique_key, where
+    /// ambiguous origins across samples are collapsed as '?'. 'sample' writes one
+    /// barcodes file per recombinant sample, including its private mutations.
+    #[clap(long, value_enum, default_value_t = BarcodesGranularity::default())]
+    #[serde(default)]
+    pub barcodes_granularity: BarcodesGranularity,
+
+    /// Bundle everything needed for plotting into a single file.
+    ///
+    /// Written as 'plot_bundle.json' under the --output-dir, which includes the
+    /// linelist, barcodes, and annotations. Use with 'rebar plot --bundle' to
+    /// plot without the --run-dir or --dataset-dir.
+    #[clap(long, default_value_t = Args::default().plot_bundle)]
+    #[serde(default)]
+    pub plot_bundle: bool,
+
+    /// Export a pangolin-style lineage report (taxon, lineage, conflict, note).
+    ///
+    /// Written as 'pangolin.csv' under the --output-dir.
+    #[clap(long, default_value_t = Args::default().pangolin)]
+    #[serde(default)]
+    pub pangolin: bool,
+
+    /// Export the substitutions of each sample, annotated by origin, in long format.
+    ///
+    /// Written as 'substitutions.tsv' (strain, coord, ref, alt, origin) under
+    /// the --output-dir.
+    #[clap(long, default_value_t = Args::default().output_substitutions)]
+    #[serde(default)]
+    pub output_substitutions: bool,
+
+    /// Export the search evidence of each sample, in long format for modelling.
+    ///
+    /// Written as 'features.tsv' under the --output-dir, with one row per
+    /// sample and candidate parent: support, conflict, score, regions, and
+    /// private substitution counts.
+    #[clap(long, default_value_t = Args::default().features)]
+    #[serde(default)]
+    pub features: bool,
+
+    /// Export an alignment of each recombinant against its parents.
+    ///
+    /// Written as FASTA to 'parent_alignments/' under the --output-dir,
+    /// restricted to the sites that discriminate between the parents.
+    #[clap(long, default_value_t = Args::default().parent_alignment)]
+    #[serde(default)]
+    pub parent_alignment: bool,
+
+    /// Export recombinant breakpoints and parental regions in BED format.
+    ///
+    /// Written to 'breakpoints.bed' under the --output-dir, for viewing in
+    /// genome browsers (ex. IGV, UCSC).
+    #[clap(long, default_value_t = Args::default().emit_bed)]
+    #[serde(default)]
+    pub emit_bed: bool,
+
+    /// Export the expected sequence of each recombinant, built from its parental regions.
+    ///
+    /// Written as FASTA to 'expected.fasta' under the --output-dir.
+    #[clap(long, default_value_t = Args::default().emit_expected)]
+    #[serde(default)]
+    pub emit_expected: bool,
+
+    /// Export a multiple sequence alignment of all queries to this FASTA file.
+    ///
+    /// Each query is reconstructed in reference coordinates, with deletions
+    /// as '-' and missing data as 'N'.
+    #[clap(long)]
+    #[serde(default)]
+    pub output_alignment: Option<PathBuf>,
+
+    /// Export the resolved query sequences to this FASTA file, before the search.
+    ///
+    /// Queries are written after population expansion, deduplication, and
+    /// knockout, in reference coordinates. The file can be reused as an
+    /// --alignment in a later run.
+    #[clap(long)]
+    #[serde(default)]
+    pub write_query_fasta: Option<PathBuf>,
+
+    /// Export a summary report of the entire run.
+    ///
+    /// Written as 'summary.txt' and 'summary.json' under the --output-dir, with
+    /// the number of samples, classified, recombinant, and failing QC, and
+    /// breakdowns by consensus population and parents. Equivalent to adding
+    /// 'report' to --outputs.
+    #[clap(long, default_value_t = Args::default().summary)]
+    #[serde(default)]
+    pub summary: bool,
+
+    /// Export a summary of recombinants grouped by their parents.
+    ///
+    /// Written as 'parent_pairs.tsv' under the --output-dir, with the count,
+    /// observed breakpoints, and strains of each parent combination.
+    #[clap(long, default_value_t = Args::default().group_by_parents)]
+    #[serde(default)]
+    pub group_by_parents: bool,
+
+    /// Export the parent search trajectory of each sequence.
+    ///
+    /// Written as 'trace.tsv' under the --output-dir, with the candidate
+    /// parents evaluated, their combined score, and the outcome, per hypothesis.
+    #[clap(long, default_value_t = Args::default().trace_search)]
+    #[serde(default)]
+    pub trace_search: bool,
+
+    /// Export the parental regions of near-miss recombinants.
+    ///
+    /// Written as 'subthreshold.tsv' under the --output-dir. For sequences
+    /// where the recombination filters failed, the best rejected parents and
+    /// regions are reported, along with the reason they were rejected.
+    #[clap(long, default_value_t = Args::default().report_subthreshold)]
+    #[serde(default)]
+    pub report_subthreshold: bool,
+
+    /// Maximum number of seconds to spend searching for parents, per sequence.
+    ///
+    /// Sequences that exceed the timeout keep their best match (consensus
+    /// population), and are flagged as 'timed_out' in the linelist.
+    #[clap(long)]
+    #[serde(default)]
+    pub timeout: Option<u64>,
+
     /// Number of CPU threads to use.
     #[clap(short = 't', long, default_value_t = Args::default().threads)]
     #[serde(skip)]
@@ -91,21 +535,73 @@ pub struct Args {
 impl Default for Args {
     fn default() -> Self {
         Args {
+            candidate_margin: 0,
+            alternative_margin: None,
+            barcodes_granularity: BarcodesGranularity::default(),
+            compress_output: false,
+            consecutive_gap_tolerance: 0,
             dataset_dir: PathBuf::new(),
+            emit_bed: false,
+            emit_expected: false,
+            group_by_parents: false,
             input: Input::default(),
             knockout: None,
+            knockout_validate: false,
             mask: vec![100, 200],
             max_iter: 3,
             min_parents: 2,
             max_parents: 2,
+            max_breakpoints: None,
             min_consecutive: 3,
+            min_consensus_depth: 0,
             min_length: 500,
             min_subs: 1,
+            mixture_min_sites: 10,
+            private_policy: PrivatePolicy::default(),
+            report_reversions: false,
+            report_subthreshold: false,
             naive: false,
+            no_phylogeny: false,
+            output_alignment: None,
             output_dir: PathBuf::new(),
+            outputs: Output::all(),
+            coordinate_base: CoordinateBase::default(),
+            output_substitutions: false,
+            features: false,
+            pangolin: false,
+            plot_bundle: false,
+            recombinants_only_output: false,
+            record_insertions: false,
+            score_deletions: false,
+            score_ambiguity: false,
+            parent_alignment: false,
             parents: None,
+            parent_min_subs: 0,
+            parents_auto: false,
             population: None,
+            priors: None,
+            qc_max_ambiguity: 0.05,
+            qc_max_private: 10,
+            qc_min_confidence: 0.9,
+            min_match_score: 0,
+            qc_min_coverage: 0.9,
+            reference_id: None,
+            require_sequence: false,
+            no_population_prefix: false,
+            skip_invalid: false,
+            strict: false,
+            max_warnings: 10,
+            limit: None,
+            sample: None,
+            seed: None,
+            similar_parents_margin: 3,
+            subset: None,
+            summary: false,
             threads: 1,
+            timeout: None,
+            trace_search: false,
+            validate_input: false,
+            write_query_fasta: None,
         }
     }
 }
@@ -113,21 +609,73 @@ impl Default for Args {
 impl Args {
     pub fn new() -> Self {
         Args {
+            candidate_margin: 0,
+            alternative_margin: None,
+            barcodes_granularity: BarcodesGranularity::default(),
+            compress_output: false,
+            consecutive_gap_tolerance: 0,
             dataset_dir: PathBuf::new(),
+            emit_bed: false,
+            emit_expected: false,
+            group_by_parents: false,
             input: Input::default(),
             knockout: None,
+            knockout_validate: false,
             mask: vec![0, 0],
             max_iter: 0,
             min_parents: 0,
             max_parents: 0,
+            max_breakpoints: None,
             min_consecutive: 0,
+            min_consensus_depth: 0,
             min_length: 0,
             min_subs: 0,
+            mixture_min_sites: 0,
+            private_policy: PrivatePolicy::default(),
+            report_reversions: false,
+            report_subthreshold: false,
+            output_alignment: None,
             output_dir: PathBuf::new(),
+            outputs: Output::all(),
+            coordinate_base: CoordinateBase::default(),
+            output_substitutions: false,
+            features: false,
+            pangolin: false,
+            plot_bundle: false,
+            recombinants_only_output: false,
+            record_insertions: false,
+            score_deletions: false,
+            score_ambiguity: false,
+            parent_alignment: false,
             parents: None,
+            parent_min_subs: 0,
+            parents_auto: false,
             population: None,
+            priors: None,
+            qc_max_ambiguity: 0.0,
+            qc_max_private: 0,
+            qc_min_confidence: 0.0,
+            min_match_score: 0,
+            qc_min_coverage: 0.0,
+            reference_id: None,
+            require_sequence: false,
+            no_population_prefix: false,
+            skip_invalid: false,
+            strict: false,
+            max_warnings: 10,
+            limit: None,
+            sample: None,
+            seed: None,
+            similar_parents_margin: 0,
+            subset: None,
+            summary: false,
             threads: 0,
+            timeout: None,
+            trace_search: false,
+            validate_input: false,
+            write_query_fasta: None,
             naive: false,
+            no_phylogeny: false,
         }
     }
 
@@ -149,6 +697,7 @@ impl Args {
     pub fn apply_edge_case(&self, new: &Args) -> Result<Args, Report> {
         let mut output = self.clone();
 
+        output.candidate_margin = new.candidate_margin;
         output.max_iter = new.max_iter;
         output.max_parents = new.max_parents;
         output.min_consecutive = new.min_consecutive;
@@ -199,13 +748,36 @@ impl Args {
 #[derive(ClapArgs, Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[group(required = true, multiple = true)]
 pub struct Input {
-    /// Input fasta alignment.
+    /// Input dataset population.
     #[arg(long, value_delimiter = ',')]
     pub populations: Option<Vec<String>>,
 
-    /// Input dataset population.
+    /// Input fasta alignment, optionally compressed (.gz, .zst).
     #[arg(long)]
     pub alignment: Option<PathBuf>,
+
+    /// Use all recombinant populations in the dataset as input.
+    ///
+    /// Shortcut for '--populations X*', which also restricts the parent
+    /// search to non-recombinant populations (unless --parents is used).
+    #[arg(long)]
+    #[serde(default)]
+    pub recombinants_only: bool,
+
+    /// Input sequence, as a raw genome string aligned to the reference.
+    ///
+    /// For quick checks, without writing a fasta file. The sequence id is
+    /// 'sequence', and the linelist row is also printed to stdout as JSON.
+    #[arg(long, conflicts_with = "sequence_file")]
+    #[serde(default)]
+    pub sequence: Option<String>,
+
+    /// Input sequence, as a file containing a raw genome string (no header).
+    ///
+    /// Same as --sequence, whitespace and line breaks are ignored.
+    #[arg(long)]
+    #[serde(default)]
+    pub sequence_file: Option<PathBuf>,
 }
 
 impl Default for Input {
@@ -219,6 +791,114 @@ impl Input {
         Input {
             populations: None,
             alignment: None,
+            recombinants_only: false,
+            sequence: None,
+            sequence_file: None,
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Barcodes Granularity
+
+/// How barcodes are grouped into files.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize, ValueEnum)]
+pub enum BarcodesGranularity {
+    /// One barcodes file per recombinant unique_key.
+    #[default]
+    Recombinant,
+    /// One barcodes file per sample.
+    Sample,
+}
+
+impl std::fmt::Display for BarcodesGranularity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let granularity = match self {
+            BarcodesGranularity::Recombinant => "recombinant",
+            BarcodesGranularity::Sample => "sample",
+        };
+        write!(f, "{granularity}")
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Output
+
+/// Output artifacts of a run.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize, ValueEnum)]
+pub enum Output {
+    /// CLI arguments of the run (run_args.json).
+    Args,
+    /// Dataset name, tag, and file SHAs (dataset_provenance.json).
+    Provenance,
+    /// Linelist of all samples (linelist.tsv).
+    Linelist,
+    /// Barcodes of each recombinant (barcodes/).
+    Barcodes,
+    /// Full recombination results of all samples (recombinations.json).
+    Recombinations,
+    /// Summary report of the entire run (summary.txt, summary.json).
+    Report,
+}
+
+impl Output {
+    /// Output artifacts written by default, all but the report.
+    pub fn all() -> Vec<Output> {
+        vec![
+            Output::Args,
+            Output::Provenance,
+            Output::Linelist,
+            Output::Barcodes,
+            Output::Recombinations,
+        ]
+    }
+}
+
+impl std::fmt::Display for Output {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let output = match self {
+            Output::Args => "args",
+            Output::Provenance => "provenance",
+            Output::Linelist => "linelist",
+            Output::Barcodes => "barcodes",
+            Output::Recombinations => "recombinations",
+            Output::Report => "report",
+        };
+        write!(f, "{output}")
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Coordinate Base
+
+/// Genome coordinate base of the outputs.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, ValueEnum)]
+pub enum CoordinateBase {
+    /// 0-based coordinates.
+    #[value(name = "0")]
+    Zero,
+    /// 1-based coordinates, as used internally.
+    #[default]
+    #[value(name = "1")]
+    One,
+}
+
+impl CoordinateBase {
+    /// Convert an internal (1-based) coordinate to this base.
+    pub fn shift(&self, coord: usize) -> usize {
+        match self {
+            CoordinateBase::Zero => coord.saturating_sub(1),
+            CoordinateBase::One => coord,
         }
     }
 }
+
+impl std::fmt::Display for CoordinateBase {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let base = match self {
+            CoordinateBase::Zero => "0",
+            CoordinateBase::One => "1",
+        };
+        write!(f, "{base}")
+    }
+}
@@ -2,6 +2,8 @@ use crate::cli;
 use crate::dataset;
 use crate::dataset::attributes::{check_compatibility, Name, Summary};
 use crate::dataset::{sarscov2, toy1};
+use crate::phylogeny::Phylogeny;
+use crate::sequence::read_reference;
 use crate::{utils, utils::remote_file::RemoteFile};
 use color_eyre::eyre::{Report, Result};
 use itertools::Itertools;
@@ -51,6 +53,9 @@ pub async fn dataset(args: &mut cli::dataset::download::Args) -> Result<(), Repo
         );
     }
 
+    // GitHub commits, resolved once per repository and shared between files
+    let mut commits = utils::GithubCommits::new();
+
     // --------------------------------------------------------------------
     // Reference
 
@@ -62,7 +67,8 @@ pub async fn dataset(args: &mut cli::dataset::download::Args) -> Result<(), Repo
     } else {
         match args.name {
             Name::SarsCov2 => {
-                sarscov2::download::reference(&args.tag, &output_path).await?
+                sarscov2::download::reference(&args.tag, &output_path, &mut commits)
+                    .await?
             }
             Name::Toy1 => toy1::download::reference(&args.tag, &output_path)?,
             _ => todo!(),
@@ -80,7 +86,8 @@ pub async fn dataset(args: &mut cli::dataset::download::Args) -> Result<(), Repo
     } else {
         match args.name {
             Name::SarsCov2 => {
-                sarscov2::download::populations(&args.tag, &output_path).await?
+                sarscov2::download::populations(&args.tag, &output_path, &mut commits)
+                    .await?
             }
             Name::Toy1 => toy1::download::populations(&args.tag, &output_path)?,
             _ => todo!(),
@@ -103,49 +110,113 @@ pub async fn dataset(args: &mut cli::dataset::download::Args) -> Result<(), Repo
     // --------------------------------------------------------------------
     // Graph (Phylogeny)
 
-    let output_path = args.output_dir.join("phylogeny.json");
-    info!("Building phylogeny: {output_path:?}");
+    info!("Building phylogeny.");
 
     let phylogeny = match args.name {
         Name::SarsCov2 => {
-            sarscov2::phylogeny::build(&mut summary, &args.output_dir).await?
+            sarscov2::phylogeny::build(&mut summary, &args.output_dir, &mut commits)
+                .await?
         }
         Name::Toy1 => toy1::phylogeny::build()?,
         _ => todo!(),
     };
+
+    // --------------------------------------------------------------------
+    // Create Edge Cases
+    //
+    // Edge cases are simply a vector of the CLI Run Args (cli::run::Args)
+    // customized to particular recombinants.
+
+    let edge_cases = match args.name {
+        Name::SarsCov2 => dataset::sarscov2::edge_cases::default()?,
+        Name::Toy1 => dataset::toy1::edge_cases::default()?,
+        _ => todo!(),
+    };
+
+    // --------------------------------------------------------------------
+    // Export Phylogeny, Mutations, Edge Cases, and Summary
+
+    write_dataset(&args.output_dir, &phylogeny, edge_cases, &summary)?;
+
+    // --------------------------------------------------------------------
+    // Finish
+
+    info!("Done.");
+    Ok(())
+}
+
+/// Write the dataset files shared by downloaded and custom built datasets.
+///
+/// Exports the phylogeny (json and dot), the mutations of the populations,
+/// the edge cases (plus automatic ones for problematic recombinants), and
+/// the summary, which is written last to mark the dataset as complete.
+pub fn write_dataset(
+    output_dir: &Path,
+    phylogeny: &Phylogeny,
+    mut edge_cases: Vec<cli::run::Args>,
+    summary: &Summary,
+) -> Result<(), Report> {
+    // --------------------------------------------------------------------
+    // Graph (Phylogeny)
+
+    let output_path = output_dir.join("phylogeny.json");
+    info!("Exporting phylogeny: {output_path:?}");
     phylogeny.write(&output_path)?;
     // Also write as .dot file for graphviz visualization.
-    let output_path = args.output_dir.join("phylogeny.dot");
+    let output_path = output_dir.join("phylogeny.dot");
     info!("Exporting graphviz phylogeny: {output_path:?}");
     phylogeny.write(&output_path)?;
 
     // --------------------------------------------------------------------
     // Export Mutations
 
-    let output_path = args.output_dir.join("mutations.json");
+    let output_path = output_dir.join("mutations.json");
     info!("Mapping mutations to populations: {output_path:?}");
     let mask = vec![0, 0];
-    let (_populations, mutations) = dataset::load::parse_populations(
+    let reference = read_reference(&summary.reference.local_path, &mask, None)?;
+    let (populations, mutations) = dataset::load::parse_populations(
         &summary.populations.local_path,
-        &summary.reference.local_path,
+        &reference,
         &mask,
+        false,
+    )?;
+    let populations_sha256 = utils::sha256(&summary.populations.local_path)?;
+    dataset::write_mutations(
+        &populations,
+        &mutations,
+        &populations_sha256,
+        &output_path,
     )?;
-    dataset::write_mutations(&mutations, &output_path)?;
 
     // --------------------------------------------------------------------
-    // Create Edge Cases
-    //
-    // Edge cases are simply a vector of the CLI Run Args (cli::run::Args)
-    // customized to particular recombinants.
+    // Export Edge Cases
 
-    let output_path = args.output_dir.join("edge_cases.json");
+    let output_path = output_dir.join("edge_cases.json");
     info!("Creating edge cases: {output_path:?}");
+    auto_edge_cases(phylogeny, &mut edge_cases)?;
 
-    let mut edge_cases = match args.name {
-        Name::SarsCov2 => dataset::sarscov2::edge_cases::default()?,
-        Name::Toy1 => dataset::toy1::edge_cases::default()?,
-        _ => todo!(),
-    };
+    // Reminder, we use the module write  method, not the struct method,
+    // because this is a vector of arguments we need to serialize.
+    cli::run::Args::write(&edge_cases, &output_path)?;
+
+    // --------------------------------------------------------------------
+    // Export Summary
+
+    let output_path = output_dir.join("summary.json");
+    info!("Exporting summary: {output_path:?}");
+    summary.write(&output_path)?;
+
+    Ok(())
+}
+
+/// Add edge cases for problematic recombinants, unless a manual one exists.
+///
+/// Problematic recombinants are those whose parents are not sister taxa, the
+/// edge case searches for the designated parents directly.
+pub fn auto_edge_cases(
+    phylogeny: &Phylogeny,
+    edge_cases: &mut Vec<cli::run::Args>,
+) -> Result<(), Report> {
     let manual_populations =
         edge_cases.iter().filter_map(|e| e.population.clone()).collect_vec();
 
@@ -166,21 +237,6 @@ pub async fn dataset(args: &mut cli::dataset::download::Args) -> Result<(), Repo
         }
     }
 
-    // Reminder, we use the module write  method, not the struct method,
-    // because this is a vector of arguments we need to serialize.
-    cli::run::Args::write(&edge_cases, &output_path)?;
-
-    // --------------------------------------------------------------------
-    // Export Summary
-
-    let output_path = args.output_dir.join("summary.json");
-    info!("Exporting summary: {output_path:?}");
-    summary.write(&output_path)?;
-
-    // --------------------------------------------------------------------
-    // Finish
-
-    info!("Done.");
     Ok(())
 }
 
@@ -0,0 +1,185 @@
+use crate::cli;
+use crate::dataset;
+use crate::dataset::attributes::Summary;
+use crate::phylogeny::Phylogeny;
+use crate::sequence::open_fasta;
+use crate::utils::{remote_file::RemoteFile, table::Table};
+use chrono::Local;
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use color_eyre::Help;
+use itertools::Itertools;
+use log::{info, warn};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{create_dir_all, File};
+use std::io::Write;
+use std::path::Path;
+
+/// Build a custom dataset from local files, without downloading.
+pub fn build(args: &cli::dataset::build::Args) -> Result<(), Report> {
+    info!("Building dataset: {:?}", &args.output_dir);
+
+    let mut summary = Summary::new();
+
+    // Warn if the directory already exists
+    if !args.output_dir.exists() {
+        info!("Creating output directory: {:?}", &args.output_dir);
+        create_dir_all(&args.output_dir)?;
+    } else {
+        warn!(
+            "Proceed with caution! --output-dir {:?} already exists.",
+            args.output_dir
+        );
+    }
+
+    // --------------------------------------------------------------------
+    // Reference
+
+    let output_path = args.output_dir.join("reference.fasta");
+    info!("Copying reference: {output_path:?}");
+    summary.reference = copy_fasta(&args.reference, &output_path)?;
+
+    // --------------------------------------------------------------------
+    // Populations
+
+    let output_path = args.output_dir.join("populations.fasta");
+    info!("Copying populations: {output_path:?}");
+    summary.populations = copy_fasta(&args.populations, &output_path)?;
+
+    // --------------------------------------------------------------------
+    // Graph (Phylogeny)
+
+    info!("Building phylogeny: {:?}", &args.phylogeny);
+
+    let mut graph_data = read_edges(&args.phylogeny)?;
+    summary.misc.insert("phylogeny".to_string(), local_file(&args.phylogeny));
+
+    // cross-reference the populations fasta and phylogeny
+    let populations = open_fasta(&summary.populations.local_path)?
+        .records()
+        .map(|result| result.map(|record| record.id().to_string()))
+        .collect::<Result<Vec<_>, _>>()
+        .wrap_err(eyre!("Failed to parse file: {:?}", &args.populations))?;
+    let nodes = graph_data.iter().map(|(name, _parents)| name.clone()).collect_vec();
+    let missing_nodes =
+        populations.iter().filter(|p| !nodes.contains(p)).unique().collect_vec();
+    if !missing_nodes.is_empty() {
+        warn!("Populations are not in the phylogeny, adding as children of the root: {missing_nodes:?}");
+        missing_nodes.into_iter().for_each(|pop| {
+            graph_data.push((pop.clone(), vec!["root".to_string()]));
+        });
+    }
+    let missing_sequences =
+        nodes.iter().filter(|n| !populations.contains(n)).collect_vec();
+    if !missing_sequences.is_empty() {
+        warn!("Phylogeny populations have no sequence: {missing_sequences:?}");
+    }
+
+    let mut phylogeny = Phylogeny::new();
+    // Add root node
+    phylogeny.graph.add_node("root".to_string());
+    // Add descendants, recombinants have multiple parents
+    phylogeny.update_from(&graph_data)?;
+
+    // --------------------------------------------------------------------
+    // Export Phylogeny, Mutations, Edge Cases, and Summary
+
+    dataset::download::write_dataset(&args.output_dir, &phylogeny, Vec::new(), &summary)?;
+
+    // --------------------------------------------------------------------
+    // Finish
+
+    info!("Done.");
+    Ok(())
+}
+
+/// Read a phylogeny edge list (TSV), with the columns: parent, child.
+///
+/// Returns each population with its parents, ordered so that parents appear
+/// before children. Populations without parents are children of the root.
+pub fn read_edges(path: &Path) -> Result<Vec<(String, Vec<String>)>, Report> {
+    let table = Table::read(path)?;
+    let parent_col_i = table.header_position("parent")?;
+    let child_col_i = table.header_position("child")?;
+
+    // nodes in the order they appear, and their parents
+    let mut nodes = Vec::new();
+    let mut parents: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for row in &table.rows {
+        let (parent, child) = (&row[parent_col_i], &row[child_col_i]);
+        if parent.is_empty() || child.is_empty() {
+            return Err(
+                eyre!("Phylogeny edge is missing a parent or child: {row:?}")
+                    .wrap_err(eyre!("Failed to parse file: {path:?}")),
+            );
+        }
+        for node in [parent, child] {
+            if node != "root" && !nodes.contains(node) {
+                nodes.push(node.clone());
+            }
+        }
+        let child_parents = parents.entry(child.clone()).or_default();
+        if !child_parents.contains(parent) {
+            child_parents.push(parent.clone());
+        }
+    }
+    if parents.contains_key("root") {
+        return Err(eyre!(
+            "The root cannot be a child in the phylogeny: {path:?}"
+        ));
+    }
+
+    // order parents before children, nodes that are never ready are in a cycle
+    let mut graph_data = Vec::new();
+    let mut added = BTreeSet::from(["root".to_string()]);
+    let mut remaining = nodes;
+    while !remaining.is_empty() {
+        let (ready, not_ready): (Vec<_>, Vec<_>) =
+            remaining.into_iter().partition(|node| {
+                parents.get(node).into_iter().flatten().all(|p| added.contains(p))
+            });
+        if ready.is_empty() {
+            return Err(
+                eyre!("Phylogeny contains a cycle: {}", not_ready.join(", "))
+                    .wrap_err(eyre!("Failed to build phylogeny: {path:?}"))
+                    .suggestion("Check the parent-child edges of these populations."),
+            );
+        }
+        for node in ready {
+            let node_parents =
+                parents.remove(&node).unwrap_or_else(|| vec!["root".to_string()]);
+            added.insert(node.clone());
+            graph_data.push((node, node_parents));
+        }
+        remaining = not_ready;
+    }
+
+    Ok(graph_data)
+}
+
+/// Copy a (possibly compressed) fasta file, uncompressed.
+fn copy_fasta(input_path: &Path, output_path: &Path) -> Result<RemoteFile, Report> {
+    let reader = open_fasta(input_path)?;
+    let mut file = File::create(output_path)
+        .wrap_err_with(|| format!("Unable to create file: {output_path:?}"))?;
+    for result in reader.records() {
+        let record = result.wrap_err(eyre!("Failed to parse file: {input_path:?}"))?;
+        let seq = std::str::from_utf8(record.seq())?;
+        writeln!(file, ">{}\n{seq}", record.id())
+            .wrap_err_with(|| format!("Unable to write file: {output_path:?}"))?;
+    }
+
+    Ok(RemoteFile {
+        local_path: output_path.to_owned(),
+        ..local_file(input_path)
+    })
+}
+
+/// Describe a local file, in place of a remote file.
+fn local_file(path: &Path) -> RemoteFile {
+    RemoteFile {
+        url: path.display().to_string(),
+        local_path: path.to_owned(),
+        date_created: Local::now().into(),
+        ..Default::default()
+    }
+}
@@ -1,20 +1,33 @@
 use crate::cli::run;
 use crate::dataset::attributes::{Name, Summary, Tag};
-use crate::dataset::Dataset;
+use crate::dataset::{self, sarscov2, Dataset};
 use crate::phylogeny::Phylogeny;
-use crate::sequence::{read_reference, Sequence, Substitution};
-use bio::io::fasta;
+use crate::sequence::{open_fasta, read_reference, Mutation, Sequence};
+use crate::utils;
 use color_eyre::eyre::{eyre, Report, Result, WrapErr};
-use log::{info, warn};
-use std::collections::BTreeMap;
+use color_eyre::Help;
+use itertools::Itertools;
+use log::{debug, info, warn};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use std::collections::{BTreeMap, BTreeSet};
 use std::path::Path;
+use std::str::FromStr;
+use std::time::Instant;
 
 // ----------------------------------------------------------------------------
 // Dataset
 // ----------------------------------------------------------------------------
 
 /// Load dataset.
-pub fn dataset(dataset_dir: &Path, mask: &Vec<usize>) -> Result<Dataset, Report> {
+///
+/// Optionally, select the reference record by id (otherwise the first). If
+/// `strict`, problems with the dataset (ex. duplicate populations) are errors.
+pub fn dataset(
+    dataset_dir: &Path,
+    mask: &Vec<usize>,
+    reference_id: Option<&str>,
+    strict: bool,
+) -> Result<Dataset, Report> {
     info!("Loading dataset: {:?}", dataset_dir);
 
     let mut dataset = Dataset::new();
@@ -23,13 +36,34 @@ pub fn dataset(dataset_dir: &Path, mask: &Vec<usize>) -> Result<Dataset, Report>
     // Mandatory
 
     // Reference
-    let reference_path = dataset_dir.join("reference.fasta");
-    dataset.reference = read_reference(&reference_path, mask)?;
+    // reference and populations might be compressed (.gz, .zst)
+    let reference_path = utils::find_compressed(&dataset_dir.join("reference.fasta"));
+    dataset.reference = read_reference(&reference_path, mask, reference_id)?;
+    info!("Using reference: {}", dataset.reference.id);
 
     // Populations and Mutations
-    let populations_path = dataset_dir.join("populations.fasta");
-    (dataset.populations, dataset.mutations) =
-        parse_populations(&populations_path, &reference_path, mask)?;
+    let populations_path = utils::find_compressed(&dataset_dir.join("populations.fasta"));
+    // reuse the exported mutations, rather than comparing every population to
+    // the reference again, strict checks (ex. duplicates) need the fasta itself
+    let mutations_path = dataset_dir.join("mutations.json");
+    let cached = if strict {
+        None
+    } else {
+        read_cached_populations(
+            &mutations_path,
+            &populations_path,
+            &dataset.reference,
+            mask,
+        )?
+    };
+    dataset.populations = match cached {
+        Some(populations) => {
+            info!("Using mutations: {mutations_path:?}");
+            populations
+        }
+        None => read_populations(&populations_path, &dataset.reference, mask, strict)?,
+    };
+    dataset.mutations = index_mutations(&dataset.populations);
 
     // ------------------------------------------------------------------------
     // Optional
@@ -65,6 +99,12 @@ pub fn dataset(dataset_dir: &Path, mask: &Vec<usize>) -> Result<Dataset, Report>
         Phylogeny::new()
     };
 
+    // Alias Key, to normalize aliased population names
+    let alias_key_path = dataset_dir.join("alias_key.json");
+    if alias_key_path.exists() {
+        dataset.alias_key = sarscov2::phylogeny::read_alias_key(&alias_key_path)?;
+    }
+
     // --------------------------------------------------------------------
     // Done
 
@@ -78,35 +118,163 @@ pub fn dataset(dataset_dir: &Path, mask: &Vec<usize>) -> Result<Dataset, Report>
 #[allow(clippy::type_complexity)]
 pub fn parse_populations(
     populations_path: &Path,
-    reference_path: &Path,
+    reference: &Sequence,
     mask: &Vec<usize>,
-) -> Result<
-    (
-        BTreeMap<String, Sequence>,
-        BTreeMap<Substitution, Vec<String>>,
-    ),
-    Report,
-> {
+    strict: bool,
+) -> Result<(BTreeMap<String, Sequence>, BTreeMap<Mutation, Vec<String>>), Report> {
+    let populations = read_populations(populations_path, reference, mask, strict)?;
+    let mutations = index_mutations(&populations);
+    Ok((populations, mutations))
+}
+
+/// Read populations from fasta, as sequences compared to the reference.
+///
+/// Records are compared to the reference in parallel, with the current rayon
+/// thread pool. Duplicated population names retain the first record, with a
+/// warning, or are an error if `strict`.
+pub fn read_populations(
+    populations_path: &Path,
+    reference: &Sequence,
+    mask: &Vec<usize>,
+    strict: bool,
+) -> Result<BTreeMap<String, Sequence>, Report> {
     // read in populations from fasta
-    let populations_reader = fasta::Reader::from_file(populations_path)
-        .map_err(|e| eyre!(e))
-        .wrap_err(format!("Failed to read file: {populations_path:?}"))?;
+    let populations_reader = open_fasta(populations_path)?;
+    let records = populations_reader
+        .records()
+        .collect::<Result<Vec<_>, _>>()
+        .wrap_err_with(|| format!("Failed to parse file: {populations_path:?}"))?;
 
-    // read in reference from fasta
-    let reference = read_reference(reference_path, mask)?;
+    // records are independent, the reference is shared (read-only) by all threads
+    let start = Instant::now();
+    let num_records = records.len();
+    let sequences = records
+        .into_par_iter()
+        .map(|record| Sequence::from_record(record, Some(reference), mask))
+        .collect::<Result<Vec<_>, Report>>()?;
+    debug!(
+        "Compared {num_records} populations to the reference in {:.3}s.",
+        start.elapsed().as_secs_f64()
+    );
 
+    // insert in file order, so that the first of any duplicates is retained,
+    // the map itself is sorted by id regardless of the order of the threads
     let mut populations = BTreeMap::new();
+    let mut duplicates = BTreeSet::new();
+    for sequence in sequences {
+        if populations.contains_key(&sequence.id) {
+            duplicates.insert(sequence.id);
+            continue;
+        }
+        populations.insert(sequence.id.clone(), sequence);
+    }
+
+    if !duplicates.is_empty() {
+        let duplicates = duplicates.into_iter().join(", ");
+        if strict {
+            return Err(eyre!(
+                "Populations are duplicated in {populations_path:?}: {duplicates}"
+            )
+            .suggestion("Remove or rename the duplicated records."));
+        }
+        warn!("Populations are duplicated in {populations_path:?}, retaining the first record: {duplicates}");
+    }
+
+    Ok(populations)
+}
+
+/// Map mutations (substitutions and deletions) to the populations they occur in.
+pub fn index_mutations(
+    populations: &BTreeMap<String, Sequence>,
+) -> BTreeMap<Mutation, Vec<String>> {
     let mut mutations = BTreeMap::new();
 
-    for result in populations_reader.records() {
-        let record = result?;
-        let sequence = Sequence::from_record(record, Some(&reference), mask)?;
-        populations.insert(sequence.id.clone(), sequence.clone());
+    for (id, sequence) in populations {
+        for sub in &sequence.substitutions {
+            let mutation = Mutation::Substitution(*sub);
+            mutations.entry(mutation).or_insert(Vec::new()).push(id.clone());
+        }
+        for del in &sequence.deletions {
+            let mutation = Mutation::Deletion(*del);
+            mutations.entry(mutation).or_insert(Vec::new()).push(id.clone());
+        }
+    }
+
+    mutations
+}
+
+/// Read populations from their mutations, in a previously exported mutations.json.
+///
+/// Returns None if the file is missing, unreadable, or was exported from a
+/// different populations fasta (by SHA-256). The file is written without
+/// masking, so the populations are masked here.
+pub fn read_cached_populations(
+    mutations_path: &Path,
+    populations_path: &Path,
+    reference: &Sequence,
+    mask: &[usize],
+) -> Result<Option<BTreeMap<String, Sequence>>, Report> {
+    if !mutations_path.exists() {
+        return Ok(None);
+    }
+    let cached = match dataset::read_mutations(mutations_path) {
+        Ok(cached) => cached,
+        Err(e) => {
+            warn!("Mutations cannot be reused: {e}");
+            return Ok(None);
+        }
+    };
+    if cached.populations_sha256 != utils::sha256(populations_path)? {
+        warn!("Mutations are out of date with populations: {mutations_path:?}");
+        return Ok(None);
+    }
 
-        for sub in sequence.substitutions {
-            mutations.entry(sub).or_insert(Vec::new()).push(sequence.id.clone());
+    // the mutations of each population
+    let mut mutations: BTreeMap<&String, Vec<Mutation>> =
+        cached.populations.iter().map(|p| (p, Vec::new())).collect();
+    for (mutation, pops) in &cached.mutations {
+        let mutation = Mutation::from_str(mutation)
+            .wrap_err_with(|| eyre!("Failed to parse file: {mutations_path:?}"))?;
+        for pop in pops {
+            mutations
+                .get_mut(pop)
+                .ok_or_else(|| eyre!("Population {pop} is not in: {mutations_path:?}"))?
+                .push(mutation);
         }
     }
 
-    Ok((populations, mutations))
+    let start = Instant::now();
+    let populations = mutations
+        .into_iter()
+        .map(|(id, mut pop_mutations)| {
+            pop_mutations.sort();
+            let missing = cached.missing.get(id).cloned().unwrap_or_default();
+            let ambiguous = cached
+                .ambiguous
+                .get(id)
+                .into_iter()
+                .flatten()
+                .map(|a| match Mutation::from_str(a)? {
+                    Mutation::Substitution(sub) => Ok(sub),
+                    Mutation::Deletion(_) => Err(eyre!("Invalid ambiguity code: {a}")),
+                })
+                .collect::<Result<Vec<_>, Report>>()?;
+            let sequence = Sequence::from_mutations(
+                id,
+                reference,
+                &pop_mutations,
+                &missing,
+                &ambiguous,
+                mask,
+            )?;
+            Ok((id.clone(), sequence))
+        })
+        .collect::<Result<BTreeMap<_, _>, Report>>()?;
+    debug!(
+        "Read {} populations from their mutations in {:.3}s.",
+        populations.len(),
+        start.elapsed().as_secs_f64()
+    );
+
+    Ok(Some(populations))
 }
@@ -1,17 +1,21 @@
 pub mod attributes;
 pub mod download;
+pub mod io;
 pub mod list;
 pub mod load;
 pub mod sarscov2;
+pub mod stats;
 pub mod toy1;
 
 use crate::cli::run;
 use crate::phylogeny::Phylogeny;
-use crate::sequence::{parsimony, Sequence, Substitution};
+use crate::sequence::{parsimony, Mutation, Sequence, Substitution};
+use crate::utils::table::Table;
 use color_eyre::eyre::{eyre, Report, Result, WrapErr};
 use indoc::formatdoc;
 use itertools::Itertools;
 use log::debug;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::default::Default;
@@ -29,9 +33,11 @@ pub struct Dataset {
     pub tag: attributes::Tag,
     pub reference: Sequence,
     pub populations: BTreeMap<String, Sequence>,
-    pub mutations: BTreeMap<Substitution, Vec<String>>,
+    pub mutations: BTreeMap<Mutation, Vec<String>>,
     pub phylogeny: Phylogeny,
     pub edge_cases: Vec<run::Args>,
+    pub alias_key: BTreeMap<String, Vec<String>>,
+    pub priors: BTreeMap<String, f32>,
 }
 
 impl fmt::Display for Dataset {
@@ -56,6 +62,8 @@ impl Dataset {
             mutations: BTreeMap::new(),
             phylogeny: Phylogeny::new(),
             edge_cases: Vec::new(),
+            alias_key: BTreeMap::new(),
+            priors: BTreeMap::new(),
         }
     }
 
@@ -105,6 +113,13 @@ impl Dataset {
         let expanded = populations
             .iter()
             .map(|p| {
+                // match population names by pattern, independent of the phylogeny
+                if let Some(pattern) = p.strip_prefix("regex:") {
+                    return self.match_populations(pattern);
+                } else if let Some(pattern) = p.strip_prefix("glob:") {
+                    return self.match_populations(&glob_to_regex(pattern));
+                }
+                let p = &self.normalize_population(p);
                 // if population is '*', use all populations in dataset
                 if p == "*" {
                     Ok(self.populations.keys().cloned().collect_vec())
@@ -136,12 +151,171 @@ impl Dataset {
         Ok(expanded)
     }
 
+    /// Match population names in the dataset against a regular expression.
+    ///
+    /// Unlike the '*' suffix, which expands descendants in the phylogeny,
+    /// this matches by name alone and works without a phylogeny.
+    pub fn match_populations(&self, pattern: &str) -> Result<Vec<String>, Report> {
+        let regex = Regex::new(pattern)
+            .wrap_err_with(|| eyre!("Invalid population pattern: {pattern}"))?;
+        let matches =
+            self.populations.keys().filter(|p| regex.is_match(p)).cloned().collect_vec();
+        if matches.is_empty() {
+            return Err(eyre!("No populations in the dataset match: {pattern}"));
+        }
+        Ok(matches)
+    }
+
+    /// Get the prior weight of a population, populations without a prior are 1.0.
+    pub fn get_prior(&self, population: &str) -> f32 {
+        self.priors.get(population).copied().unwrap_or(1.0)
+    }
+
+    /// Create a new dataset restricted to a subset of populations.
+    ///
+    /// Populations are expanded with wildcarding. The phylogeny is pruned to
+    /// the subset and their ancestors, so that relationships are preserved.
+    pub fn subset(&self, populations: &[String]) -> Result<Dataset, Report> {
+        let populations = self.expand_populations(populations)?;
+
+        let mut dataset = Dataset {
+            name: self.name,
+            tag: self.tag.clone(),
+            reference: self.reference.clone(),
+            populations: self.populations.clone(),
+            mutations: self.mutations.clone(),
+            phylogeny: self.phylogeny.clone(),
+            edge_cases: self.edge_cases.clone(),
+            alias_key: self.alias_key.clone(),
+            priors: self.priors.clone(),
+        };
+
+        // populations and mutations
+        dataset.populations.retain(|p, _seq| populations.contains(p));
+        dataset.mutations.iter_mut().for_each(|(_mutation, pops)| {
+            pops.retain(|p| populations.contains(p));
+        });
+        dataset.mutations.retain(|_mutation, pops| !pops.is_empty());
+
+        // edge cases
+        dataset
+            .edge_cases
+            .retain(|e| e.population.as_ref().is_some_and(|p| populations.contains(p)));
+
+        // phylogeny, keep the subset and their ancestors
+        if !dataset.phylogeny.is_empty() {
+            let mut keep = populations.clone();
+            for p in &populations {
+                let ancestors = self.phylogeny.get_ancestors(p)?;
+                keep.extend(ancestors.into_iter().flatten());
+            }
+            let remove = dataset
+                .phylogeny
+                .get_names()?
+                .into_iter()
+                .filter(|p| *p != "root" && !keep.contains(p))
+                .collect_vec();
+            for p in remove {
+                dataset.phylogeny.remove(&p)?;
+            }
+        }
+
+        Ok(dataset)
+    }
+
+    /// Identify the population that is identical to the reference genome.
+    ///
+    /// This is a population with no substitutions or deletions. If several
+    /// qualify, the one closest to the root of the phylogeny is preferred.
+    pub fn reference_population(&self) -> Option<String> {
+        self.populations
+            .iter()
+            .filter(|(_, p)| p.substitutions.is_empty() && p.deletions.is_empty())
+            .map(|(name, _)| name)
+            .min_by_key(|name| {
+                let depth = self.phylogeny.get_depth(name).unwrap_or(usize::MAX);
+                (depth, name.to_string())
+            })
+            .cloned()
+    }
+
+    /// Normalize a user-supplied population name to its name in the dataset.
+    ///
+    /// Names are matched exactly, then case-insensitively, and then by alias
+    /// compression (ex. B.1.1.529.5 -> BA.5) if the dataset has an alias key.
+    /// The wildcard suffix ('*') is preserved. Unknown names are returned as is.
+    pub fn normalize_population(&self, population: &str) -> String {
+        if population == "*" || population == "X*" {
+            return population.to_string();
+        }
+        let (name, wildcard) = match population.strip_suffix('*') {
+            Some(name) => (name, "*"),
+            None => (population, ""),
+        };
+
+        let mut known = self.populations.keys().cloned().collect_vec();
+        known.extend(self.phylogeny.get_names().unwrap_or_default());
+
+        // exact
+        if known.iter().any(|k| k == name) {
+            return population.to_string();
+        }
+        // case-insensitive
+        if let Some(k) = known.iter().find(|k| k.eq_ignore_ascii_case(name)) {
+            return format!("{k}{wildcard}");
+        }
+        // aliased
+        if !self.alias_key.is_empty() {
+            let name = name.to_ascii_uppercase();
+            if let Ok(compress) =
+                sarscov2::phylogeny::compress_lineage(&name, &self.alias_key)
+            {
+                if known.contains(&compress) {
+                    return format!("{compress}{wildcard}");
+                }
+            }
+        }
+
+        population.to_string()
+    }
+
+    /// Expand list of populations with wildcarding, keeping only populations
+    /// with sequence data.
+    ///
+    /// Populations without sequence data are substituted with their nearest
+    /// ancestor that has sequence data.
+    pub fn expand_populations_with_sequence(
+        &self,
+        populations: &[String],
+    ) -> Result<Vec<String>, Report> {
+        let expanded = self
+            .expand_populations(populations)?
+            .into_iter()
+            .filter_map(|p| self.get_ancestor_with_sequence(&p).ok())
+            .unique()
+            .collect_vec();
+
+        Ok(expanded)
+    }
+
+    /// Whether the mutations index is available, to speed up [Dataset::search].
+    pub fn indexed(&self) -> bool {
+        !self.mutations.is_empty()
+    }
+
     /// Search dataset for a population parsimony match to the sequence.
+    ///
+    /// Candidate populations are those within `candidate_margin` matching
+    /// substitutions of the population(s) with the most matches. Deletions and
+    /// IUPAC ambiguity codes are optionally scored (see [parsimony::Summary]).
     pub fn search(
         &self,
         sequence: &Sequence,
         populations: Option<&Vec<&String>>,
         coordinates: Option<&[usize]>,
+        candidate_margin: usize,
+        score_deletions: bool,
+        score_ambiguity: bool,
     ) -> Result<SearchResult, Report> {
         // initialize an empty result, this will be the final product of this function
         let mut result = SearchResult::new(sequence);
@@ -150,11 +324,11 @@ impl Dataset {
         // Candidate Matches
 
         // Identify preliminary candidate matches, based on populations that have
-        // the greatest number of matching substitutions.
+        // the greatest number of matching substitutions and deletions.
         // NOTE: This is a efficiency shortcut, but the true population is not
         // guaranteed to be in this initial candidate pool.
 
-        // optionally filter subs to the requested coordinates
+        // optionally filter subs and dels to the requested coordinates
         let search_subs = if let Some(coordinates) = &coordinates {
             sequence
                 .substitutions
@@ -164,41 +338,66 @@ impl Dataset {
         } else {
             sequence.substitutions.iter().collect()
         };
+        let search_dels = if let Some(coordinates) = &coordinates {
+            sequence
+                .deletions
+                .iter()
+                .filter(|del| coordinates.contains(&del.coord))
+                .collect_vec()
+        } else {
+            sequence.deletions.iter().collect()
+        };
 
-        // Count up all matching population subs ("support")
-        let mut max_support = 0;
-        let population_support_counts: BTreeMap<&String, usize> = self
-            .populations
-            .iter()
-            .filter(|(pop, _seq)| {
-                if let Some(populations) = populations {
-                    populations.contains(pop)
-                } else {
-                    true
-                }
-            })
-            .filter_map(|(pop, seq)| {
-                let count = seq
-                    .substitutions
-                    .iter()
-                    .filter(|sub| search_subs.contains(sub))
-                    .collect_vec()
-                    .len();
-                if count >= max_support {
-                    max_support = count
-                }
-                (count > 0).then_some((pop, count))
-            })
-            .collect();
+        // Count up all matching population subs and dels ("support")
+        let is_searched = |pop: &String| match populations {
+            Some(populations) => populations.contains(&pop),
+            None => true,
+        };
+        let population_support_counts: BTreeMap<&String, usize> = if !self.indexed() {
+            // scan all populations
+            self.populations
+                .iter()
+                .filter(|(pop, _seq)| is_searched(pop))
+                .filter_map(|(pop, seq)| {
+                    let count = seq
+                        .substitutions
+                        .iter()
+                        .filter(|sub| search_subs.contains(sub))
+                        .count()
+                        + seq
+                            .deletions
+                            .iter()
+                            .filter(|del| search_dels.contains(del))
+                            .count();
+                    (count > 0).then_some((pop, count))
+                })
+                .collect()
+        } else {
+            // look up only the populations with the sequence mutations
+            let mut counts = BTreeMap::new();
+            search_subs
+                .iter()
+                .map(|sub| Mutation::Substitution(**sub))
+                .chain(search_dels.iter().map(|del| Mutation::Deletion(**del)))
+                .for_each(|mutation| {
+                    self.mutations.get(&mutation).into_iter().flatten().for_each(|pop| {
+                        // the index may still list removed populations, skip them
+                        if self.populations.contains_key(pop) && is_searched(pop) {
+                            *counts.entry(pop).or_insert(0) += 1;
+                        }
+                    })
+                });
+            counts
+        };
+        let max_support = population_support_counts.values().max().copied().unwrap_or(0);
 
-        // todo!() decide how much wiggle room we want to give in max support
-        // if we want to do max_support - 10, we might need to alter pretty_print
-        // so that it only displays the first N candidates (ex. 5,10)
-        // this will also cause slow downs
+        // allow some wiggle room below max support, a wider margin means more
+        // candidates to score, which will cause slow downs.
+        // pretty_print only displays the first N candidates.
+        let min_support = max_support.saturating_sub(candidate_margin);
         let population_matches = population_support_counts
             .into_iter()
-            .filter_map(|(pop, count)| (count == max_support).then_some(pop))
-            //.filter(|(_pop, count)| *count >= (max_support - 10))
+            .filter_map(|(pop, count)| (count >= min_support).then_some(pop))
             .collect_vec();
 
         if population_matches.is_empty() {
@@ -212,11 +411,16 @@ impl Dataset {
         population_matches.into_iter().for_each(|pop| {
             // calculate the parsimony score, and store results in map by population
             let pop_seq = &self.populations[pop];
-            let summary =
-                parsimony::Summary::from_sequence(sequence, pop_seq, coordinates)
-                    .unwrap_or_else(|_| {
-                        panic!("Failed to create summary from sequence {}", &sequence.id)
-                    });
+            let summary = parsimony::Summary::from_sequence(
+                sequence,
+                pop_seq,
+                coordinates,
+                score_deletions,
+                score_ambiguity,
+            )
+            .unwrap_or_else(|_| {
+                panic!("Failed to create summary from sequence {}", &sequence.id)
+            });
             result.support.insert(pop.to_owned(), summary.support);
             result.conflict_ref.insert(pop.to_owned(), summary.conflict_ref);
             result.conflict_alt.insert(pop.to_owned(), summary.conflict_alt);
@@ -260,6 +464,17 @@ impl Dataset {
             .cloned()
             .collect_vec();
 
+        // break remaining ties by prior weight, keep only the highest prior(s).
+        // With uniform (default) priors, all top populations are kept.
+        if !self.priors.is_empty() {
+            let max_prior = result
+                .top_populations
+                .iter()
+                .map(|pop| self.get_prior(pop))
+                .fold(f32::MIN, f32::max);
+            result.top_populations.retain(|pop| self.get_prior(pop) >= max_prior);
+        }
+
         // --------------------------------------------------------------------
         // Consensus Population
         // summarize top populations by common ancestor
@@ -288,8 +503,13 @@ impl Dataset {
                 result.top_populations.iter().map(|s| s.as_ref()).collect_vec();
             debug!("Creating {pop} consensus genome from top populations.");
             let pop_seq = self.create_consensus(pop, &top_populations)?;
-            let summary =
-                parsimony::Summary::from_sequence(sequence, &pop_seq, coordinates)?;
+            let summary = parsimony::Summary::from_sequence(
+                sequence,
+                &pop_seq,
+                coordinates,
+                score_deletions,
+                score_ambiguity,
+            )?;
 
             // Add consensus summary to search result
             result.support.insert(pop.to_owned(), summary.support);
@@ -410,6 +630,9 @@ pub struct SearchResult {
     pub conflict_alt: BTreeMap<String, Vec<Substitution>>,
     pub score: BTreeMap<String, isize>,
     pub recombinant: Option<String>,
+    /// A consensus population rejected as too broad (--min-consensus-depth).
+    #[serde(default)]
+    pub consensus_ambiguous: Option<String>,
 }
 
 impl SearchResult {
@@ -425,6 +648,7 @@ impl SearchResult {
             substitutions: Vec::new(),
             score: BTreeMap::new(),
             recombinant: None,
+            consensus_ambiguous: None,
         }
     }
 
@@ -507,22 +731,111 @@ impl SearchResult {
     }
 }
 
+// ----------------------------------------------------------------------------
+// Population Mutations
+
+/// Mutations of the dataset populations, as exported to mutations.json.
+///
+/// The SHA-256 of the populations fasta identifies the populations that the
+/// mutations were parsed from, so they can be reused when loading a dataset.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct PopulationMutations {
+    /// SHA-256 (hex) of the populations fasta, decompressed.
+    pub populations_sha256: String,
+    /// Population names, including those without mutations.
+    pub populations: Vec<String>,
+    /// Mutations (ex. 'A100T' or 'A100-'), mapped to populations.
+    pub mutations: Vec<(String, Vec<String>)>,
+    /// Coordinates of missing data, for populations that have any.
+    #[serde(default)]
+    pub missing: BTreeMap<String, Vec<usize>>,
+    /// IUPAC ambiguity codes (ex. 'A100R'), for populations that have any.
+    #[serde(default)]
+    pub ambiguous: BTreeMap<String, Vec<String>>,
+}
+
 // ----------------------------------------------------------------------------
 // Functions
 // ----------------------------------------------------------------------------
 
+/// Prefix of query ids for dataset populations (ex. population_BA.2).
+pub const POPULATION_PREFIX: &str = "population_";
+
+/// Get the population name of a query id, without the [POPULATION_PREFIX].
+pub fn strip_population_prefix(id: &str) -> &str {
+    id.strip_prefix(POPULATION_PREFIX).unwrap_or(id)
+}
+
+/// Convert a glob pattern ('*' and '?') to an anchored regular expression.
+pub fn glob_to_regex(pattern: &str) -> String {
+    let pattern = pattern
+        .split('*')
+        .map(|part| part.split('?').map(regex::escape).join("."))
+        .join(".*");
+    format!("^{pattern}$")
+}
+
+/// Read population prior weights from a TSV with columns 'population' and 'prior_weight'.
+pub fn read_priors(path: &Path) -> Result<BTreeMap<String, f32>, Report> {
+    let table = Table::read(path)?;
+    let population_i = table.header_position("population")?;
+    let prior_i = table.header_position("prior_weight")?;
+
+    table
+        .rows
+        .iter()
+        .map(|row| {
+            let (population, prior) = (&row[population_i], &row[prior_i]);
+            let prior = prior.parse::<f32>().wrap_err_with(|| {
+                eyre!("Invalid prior_weight for {population}: {prior}")
+            })?;
+            Ok((population.clone(), prior))
+        })
+        .collect()
+}
+
 /// Write mapping of mutations to populations, coordinate sorted.
+///
+/// Populations are described by their mutations and missing data, along with
+/// the SHA-256 of the fasta they were parsed from (see [PopulationMutations]).
 pub fn write_mutations(
-    mutations: &BTreeMap<Substitution, Vec<String>>,
+    populations: &BTreeMap<String, Sequence>,
+    mutations: &BTreeMap<Mutation, Vec<String>>,
+    populations_sha256: &str,
     path: &Path,
 ) -> Result<(), Report> {
     // convert to vector for coordinate sorting
     let mut mutations = mutations.iter().collect_vec();
-    mutations.sort_by(|a, b| a.0.coord.cmp(&b.0.coord));
+    mutations.sort_by(|a, b| a.0.coord().cmp(&b.0.coord()));
+
+    // convert mutation to string for serde pretty
+    let mutations = mutations
+        .iter()
+        .map(|(mutation, pops)| (mutation.to_string(), pops.to_vec()))
+        .collect_vec();
+    let missing = populations
+        .iter()
+        .filter(|(_id, sequence)| !sequence.missing.is_empty())
+        .map(|(id, sequence)| (id.clone(), sequence.missing.clone()))
+        .collect();
+    let ambiguous = populations
+        .iter()
+        .filter(|(_id, sequence)| !sequence.ambiguous.is_empty())
+        .map(|(id, sequence)| {
+            (
+                id.clone(),
+                sequence.ambiguous.iter().map(|a| a.to_string()).collect(),
+            )
+        })
+        .collect();
+    let mutations = PopulationMutations {
+        populations_sha256: populations_sha256.to_string(),
+        populations: populations.keys().cloned().collect(),
+        mutations,
+        missing,
+        ambiguous,
+    };
 
-    // convert substitution to string for serde pretty
-    let mutations =
-        mutations.iter().map(|(sub, pops)| (sub.to_string(), pops)).collect_vec();
     // create output file
     let mut file = File::create(path)
         .wrap_err_with(|| format!("Failed to create file: {path:?}"))?;
@@ -537,3 +850,12 @@ pub fn write_mutations(
 
     Ok(())
 }
+
+/// Read mutations, mapped to populations, from a file exported by [write_mutations].
+pub fn read_mutations(path: &Path) -> Result<PopulationMutations, Report> {
+    let mutations = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("Failed to read file: {path:?}."))?;
+    let mutations = serde_json::from_str(&mutations)
+        .wrap_err_with(|| format!("Failed to parse file: {path:?}"))?;
+    Ok(mutations)
+}
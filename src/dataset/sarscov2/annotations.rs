@@ -6,19 +6,23 @@ use itertools::Itertools;
 pub fn build() -> Result<Table, Report> {
     let mut table = Table::new();
 
-    let headers = vec!["gene", "abbreviation", "start", "end"];
+    let headers = vec!["gene", "abbreviation", "start", "end", "strand", "track"];
     let rows = vec![
-        vec!["ORF1a", "1a", "266", "13468"],
-        vec!["ORF1b", "1b", "13468", "21555"],
-        vec!["S", "S", "21563", "25384"],
-        vec!["ORF3a", "3a", "25393", "26220"],
-        vec!["E", "E", "26245", "26472"],
-        vec!["M", "M", "26523", "27191"],
-        vec!["ORF6", "6", "27202", "27387"],
-        vec!["ORF7a", "7a", "27394", "27759"],
-        vec!["ORF7b", "7b", "27756", "27887"],
-        vec!["ORF8", "8", "27894", "28259"],
-        vec!["ORF9b", "9b", "28284", "28577"],
+        vec!["ORF1a", "1a", "266", "13468", "+", "Genes"],
+        vec!["ORF1b", "1b", "13468", "21555", "+", "Genes"],
+        vec!["S", "S", "21563", "25384", "+", "Genes"],
+        vec!["ORF3a", "3a", "25393", "26220", "+", "Genes"],
+        vec!["E", "E", "26245", "26472", "+", "Genes"],
+        vec!["M", "M", "26523", "27191", "+", "Genes"],
+        vec!["ORF6", "6", "27202", "27387", "+", "Genes"],
+        vec!["ORF7a", "7a", "27394", "27759", "+", "Genes"],
+        vec!["ORF7b", "7b", "27756", "27887", "+", "Genes"],
+        vec!["ORF8", "8", "27894", "28259", "+", "Genes"],
+        vec!["N", "N", "28274", "29533", "+", "Genes"],
+        vec!["ORF9b", "9b", "28284", "28577", "+", "Genes"],
+        // spike domains
+        vec!["S:NTD", "NTD", "21599", "22477", "+", "Domains"],
+        vec!["S:RBD", "RBD", "22517", "23185", "+", "Domains"],
     ];
 
     // Convert values to String
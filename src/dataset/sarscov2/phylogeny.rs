@@ -1,15 +1,16 @@
-use crate::{dataset, phylogeny::Phylogeny, utils::table::Table};
-use bio::io::fasta;
+use crate::sequence::open_fasta;
+use crate::utils::{table::Table, GithubCommits};
+use crate::{dataset, phylogeny::Phylogeny};
 use color_eyre::eyre::{eyre, Report, Result, WrapErr};
-use color_eyre::Help;
 use itertools::Itertools;
-use log::{debug, info, warn};
+use log::{info, warn};
 use std::collections::BTreeMap;
 use std::path::Path;
 
 pub async fn build(
     summary: &mut dataset::attributes::Summary,
     output_dir: &Path,
+    commits: &mut GithubCommits,
 ) -> Result<Phylogeny, Report> {
     // ------------------------------------------------------------------------
     // Download
@@ -23,7 +24,8 @@ pub async fn build(
     let remote_file = if summary.misc.contains_key("lineage_notes") {
         dataset::download::snapshot(&summary.misc["lineage_notes"], &output_path).await?
     } else {
-        dataset::sarscov2::download::lineage_notes(&summary.tag, &output_path).await?
+        dataset::sarscov2::download::lineage_notes(&summary.tag, &output_path, commits)
+            .await?
     };
     summary.misc.insert("lineage_notes".to_string(), remote_file);
 
@@ -35,7 +37,8 @@ pub async fn build(
     let remote_file = if summary.misc.contains_key("alias_key") {
         dataset::download::snapshot(&summary.misc["alias_key"], &output_path).await?
     } else {
-        dataset::sarscov2::download::alias_key(&summary.tag, &output_path).await?
+        dataset::sarscov2::download::alias_key(&summary.tag, &output_path, commits)
+            .await?
     };
     summary.misc.insert("alias_key".to_string(), remote_file);
 
@@ -92,9 +95,7 @@ pub async fn build(
     // read populations fasta, to check if any lineages are missing in notes
     let populations_path = &summary.populations.local_path;
     let populations_file_name = populations_path.file_name().unwrap().to_str().unwrap();
-    let alignment_reader = fasta::Reader::from_file(populations_path)
-        .map_err(|e| eyre!(e))
-        .wrap_err("Failed to read file: {populations_path:?}")?;
+    let alignment_reader = open_fasta(populations_path)?;
 
     // keep track of population names in alignment, cross-reference against
     // lineage notes + alias_key later
@@ -106,72 +107,34 @@ pub async fn build(
         alignment_populations.push(lineage);
     }
 
-    // ------------------------------------------------------------------------
-    // Parent Child Relationships
-    // ------------------------------------------------------------------------
-
-    let mut graph_order = Vec::new();
-    let mut graph_data = BTreeMap::new();
-
-    for row in lineage_table.rows {
-        let lineage = row[lineage_col_i].to_string();
-
-        // Lineages that start with '*' have been withdrawn
-        if lineage.starts_with('*') || lineage == String::new() {
-            continue;
-        }
-
-        let parents = get_lineage_parents(&lineage, &alias_key)?;
-        graph_order.push(lineage.clone());
-        graph_data.insert(lineage, parents);
-    }
-
     // ------------------------------------------------------------------------
     // Graph (Phylogeny)
     // ------------------------------------------------------------------------
 
-    let mut phylogeny = Phylogeny::new();
-
-    // Add root node
-    let name = "root".to_string();
-    phylogeny.graph.add_node(name);
-
-    // todo!() Do this twice? in case lineages are accidentally out of order?
-
-    // Add descendants
-    for name in graph_order {
-        let id = phylogeny.graph.add_node(name.clone());
-        if !graph_data.contains_key(&name) {
-            return Err(
-                eyre!("Parents of {name} are unknown in the phylogeny graph.")
-                    .suggestion(
-                        "Could the lineage_notes be out of order or misformatted?",
-                    )
-                    .suggestion("Parents are required to appear before children."),
-            );
-        }
-        let parents = graph_data.get(&name).unwrap();
+    // if a phylogeny was previously built in this directory, only add the new
+    // lineages, otherwise build it from scratch
+    let phylogeny_path = output_dir.join("phylogeny.json");
+    let phylogeny = if phylogeny_path.exists() {
+        info!("Updating existing phylogeny: {phylogeny_path:?}");
+        let mut phylogeny = Phylogeny::read(&phylogeny_path)?;
+        update(&mut phylogeny, &alias_key, &lineage_table)?;
+        phylogeny
+    } else {
+        // Parent Child Relationships
+        let graph_data = get_graph_data(&lineage_table, &alias_key)?;
 
-        debug!("Population: {name}; Parents: {parents:?}");
+        let mut phylogeny = Phylogeny::new();
 
-        // If multiple parents add this to recombinants list
-        if parents.len() > 1 {
-            phylogeny.recombinants.push(name.clone());
-        }
-        for parent in parents {
-            if phylogeny.get_node(parent).is_err() {
-                return Err(eyre!("Parental lineage {parent} is not in the graph.")
-                    .suggestion(
-                        "Are the alias_key.json and lineage_notes.txt out of sync?",
-                    )
-                    .suggestion("Please check if {parent} is in the alias key."));
-            }
-            let parent_id = phylogeny.get_node(parent)?;
-            phylogeny.graph.add_edge(parent_id, id, 1);
-        }
-    }
+        // Add root node
+        let name = "root".to_string();
+        phylogeny.graph.add_node(name);
 
-    phylogeny.recombinants_all = phylogeny.get_recombinants_all()?;
+        // todo!() Do this twice? in case lineages are accidentally out of order?
+
+        // Add descendants
+        phylogeny.update_from(&graph_data)?;
+        phylogeny
+    };
 
     // ------------------------------------------------------------------------
     // Consistency Check
@@ -246,6 +209,45 @@ pub async fn build(
     Ok(phylogeny)
 }
 
+/// Get lineages and their parents from the lineage notes, in order.
+pub fn get_graph_data(
+    lineage_table: &Table,
+    alias_key: &BTreeMap<String, Vec<String>>,
+) -> Result<Vec<(String, Vec<String>)>, Report> {
+    let lineage_col_i = lineage_table.header_position("Lineage")?;
+    let mut graph_data = Vec::new();
+
+    for row in &lineage_table.rows {
+        let lineage = row[lineage_col_i].to_string();
+
+        // Lineages that start with '*' have been withdrawn
+        if lineage.starts_with('*') || lineage == String::new() {
+            continue;
+        }
+
+        let parents = get_lineage_parents(&lineage, alias_key)?;
+        graph_data.push((lineage, parents));
+    }
+
+    Ok(graph_data)
+}
+
+/// Incrementally update a SARS-CoV-2 phylogeny from a new alias key and lineage notes.
+///
+/// Only new lineages (and their edges) are added, existing nodes are preserved.
+/// Returns the names of the lineages that were added.
+pub fn update(
+    phylogeny: &mut Phylogeny,
+    alias_key: &BTreeMap<String, Vec<String>>,
+    lineage_notes: &Table,
+) -> Result<Vec<String>, Report> {
+    let graph_data = get_graph_data(lineage_notes, alias_key)?;
+    let added = phylogeny.update_from(&graph_data)?;
+    info!("Added {} lineages to the phylogeny.", added.len());
+
+    Ok(added)
+}
+
 /// Get all immediate parents of a SARS-CoV-2 lineage.
 pub fn get_lineage_parents(
     lineage: &str,
@@ -0,0 +1,91 @@
+use crate::cli;
+use crate::dataset::load;
+use crate::utils::table::Table;
+use color_eyre::eyre::{Report, Result};
+use itertools::Itertools;
+use log::info;
+
+/// Summarize dataset diversity.
+///
+/// Reports the number of populations, substitutions per population, singleton
+/// and shared mutations, recombinants, and the most homoplasic sites (mutations
+/// found in the most populations), which are likely to cause spurious breakpoints.
+pub fn stats(args: &cli::dataset::stats::Args) -> Result<(), Report> {
+    let mask = vec![0, 0];
+    let dataset = load::dataset(&args.dataset_dir, &mask, None, false)?;
+
+    // ------------------------------------------------------------------------
+    // Populations
+
+    let num_populations = dataset.populations.len();
+    let num_subs = dataset
+        .populations
+        .values()
+        .map(|sequence| sequence.substitutions.len())
+        .sorted()
+        .collect_vec();
+    let mean_subs = if num_subs.is_empty() {
+        0.0
+    } else {
+        num_subs.iter().sum::<usize>() as f32 / num_subs.len() as f32
+    };
+    let median_subs = match num_subs.len() {
+        0 => 0.0,
+        n if n % 2 == 0 => (num_subs[n / 2 - 1] + num_subs[n / 2]) as f32 / 2.0,
+        n => num_subs[n / 2] as f32,
+    };
+    let num_recombinants = dataset
+        .populations
+        .keys()
+        .filter(|p| dataset.phylogeny.recombinants_all.contains(p))
+        .count();
+
+    // ------------------------------------------------------------------------
+    // Mutations
+
+    let num_mutations = dataset.mutations.len();
+    let num_singleton = dataset.mutations.values().filter(|pops| pops.len() == 1).count();
+    let num_shared = num_mutations - num_singleton;
+
+    let mut summary = Table::new();
+    summary.headers =
+        vec!["statistic", "value"].into_iter().map(String::from).collect_vec();
+    summary.rows = vec![
+        ("populations", num_populations.to_string()),
+        ("recombinants", num_recombinants.to_string()),
+        ("substitutions_mean", format!("{mean_subs:.2}")),
+        ("substitutions_median", format!("{median_subs:.1}")),
+        ("mutations", num_mutations.to_string()),
+        ("mutations_singleton", num_singleton.to_string()),
+        ("mutations_shared", num_shared.to_string()),
+    ]
+    .into_iter()
+    .map(|(statistic, value)| vec![statistic.to_string(), value])
+    .collect_vec();
+
+    println!("\n{}", summary.to_markdown()?);
+
+    // ------------------------------------------------------------------------
+    // Homoplasic Sites
+
+    let mut sites = Table::new();
+    sites.headers =
+        vec!["mutation", "populations"].into_iter().map(String::from).collect_vec();
+    sites.rows = dataset
+        .mutations
+        .iter()
+        .sorted_by(|a, b| b.1.len().cmp(&a.1.len()).then(a.0.cmp(b.0)))
+        .map(|(mutation, pops)| vec![mutation.to_string(), pops.len().to_string()])
+        .collect_vec();
+
+    let mut top_sites = sites.clone();
+    top_sites.rows.truncate(args.top);
+    println!("\n{}", top_sites.to_markdown()?);
+
+    if let Some(output) = &args.output {
+        info!("Exporting sites: {output:?}");
+        sites.write(output)?;
+    }
+
+    Ok(())
+}
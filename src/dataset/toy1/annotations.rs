@@ -6,10 +6,10 @@ use itertools::Itertools;
 pub fn build() -> Result<Table, Report> {
     let mut table = Table::new();
 
-    let headers = vec!["gene", "abbreviation", "start", "end"];
+    let headers = vec!["gene", "abbreviation", "start", "end", "strand"];
     let rows = vec![
-        vec!["Gene1", "g1", "1", "3"],
-        vec!["Gene2", "g2", "12", "20"],
+        vec!["Gene1", "g1", "1", "3", "+"],
+        vec!["Gene2", "g2", "12", "20", "+"],
     ];
 
     // Convert values to String
@@ -1,8 +1,35 @@
 use crate::cli::run;
 use color_eyre::eyre::{Report, Result};
+use log::debug;
 
 /// Create default Toy1 recombinant edge cases.
+///
+/// The Toy1 genome is only 20 bases long, so the default parental region
+/// lengths used for real genomes are relaxed.
 pub fn default() -> Result<Vec<run::Args>, Report> {
-    let edge_cases: Vec<run::Args> = Vec::new();
+    let mut edge_cases: Vec<run::Args> = Vec::new();
+
+    // D: A and B, with adjacent parental regions of 11 and 9 bases
+    let recombinant = "D".to_string();
+    debug!("Creating manual edge case: {recombinant:?}");
+    let edge_case = run::Args {
+        min_length: 3,
+        population: Some(recombinant),
+        ..Default::default()
+    };
+    edge_cases.push(edge_case);
+
+    // E: D and C, with a single sub (G3) from C
+    let recombinant = "E".to_string();
+    debug!("Creating manual edge case: {recombinant:?}");
+    let edge_case = run::Args {
+        min_consecutive: 1,
+        min_length: 1,
+        parents: Some(vec!["D".to_string(), "C".to_string()]),
+        population: Some(recombinant),
+        ..Default::default()
+    };
+    edge_cases.push(edge_case);
+
     Ok(edge_cases)
 }
@@ -0,0 +1,100 @@
+use crate::cli;
+use crate::utils::table::Table;
+use color_eyre::eyre::{Report, Result};
+use itertools::Itertools;
+use log::info;
+use std::collections::BTreeMap;
+
+/// Linelist columns that define a call.
+pub const CALL_COLUMNS: [&str; 4] =
+    ["population", "recombinant", "parents", "breakpoints"];
+
+/// Compare two linelists sample-by-sample.
+pub fn diff_linelist(args: &cli::diff::Args) -> Result<(), Report> {
+    info!("Comparing linelists: {:?} vs. {:?}", args.a, args.b);
+    let a = Table::read(&args.a)?;
+    let b = Table::read(&args.b)?;
+
+    let table = linelists(&a, &b)?;
+
+    // summarize changed calls by category
+    let category_i = table.header_position("category")?;
+    let counts = table.rows.iter().counts_by(|row| row[category_i].clone());
+    let counts: BTreeMap<_, _> = counts.into_iter().collect();
+    let num_changed = table
+        .rows
+        .iter()
+        .filter(|row| CALL_COLUMNS.contains(&row[category_i].as_str()))
+        .map(|row| &row[0])
+        .unique()
+        .count();
+    info!("Samples with a changed call: {num_changed}");
+    for (category, count) in counts {
+        info!("  {category}: {count}");
+    }
+
+    if let Some(output) = &args.output {
+        info!("Exporting differences: {output:?}");
+        table.write(output)?;
+    } else {
+        println!("\n{}", table.to_markdown()?);
+    }
+
+    Ok(())
+}
+
+/// Join two linelists on 'strain', and tabulate the differences.
+///
+/// Returns a table of strain, category, a, and b. The category is a changed
+/// call column (ex. parents), or 'added' or 'removed' for samples that are
+/// only in one linelist.
+pub fn linelists(a: &Table, b: &Table) -> Result<Table, Report> {
+    let mut table = Table::new();
+    table.headers =
+        vec!["strain", "category", "a", "b"].into_iter().map(String::from).collect_vec();
+
+    let a_strain_i = a.header_position("strain")?;
+    let b_strain_i = b.header_position("strain")?;
+    let b_rows: BTreeMap<&String, &Vec<String>> =
+        b.rows.iter().map(|row| (&row[b_strain_i], row)).collect();
+    let a_strains = a.rows.iter().map(|row| &row[a_strain_i]).collect_vec();
+
+    for a_row in &a.rows {
+        let strain = &a_row[a_strain_i];
+        let Some(b_row) = b_rows.get(strain) else {
+            table.rows.push(vec![
+                strain.clone(),
+                "removed".to_string(),
+                strain.clone(),
+                String::new(),
+            ]);
+            continue;
+        };
+        for column in CALL_COLUMNS {
+            let a_value = &a_row[a.header_position(column)?];
+            let b_value = &b_row[b.header_position(column)?];
+            if a_value != b_value {
+                table.rows.push(vec![
+                    strain.clone(),
+                    column.to_string(),
+                    a_value.clone(),
+                    b_value.clone(),
+                ]);
+            }
+        }
+    }
+
+    for b_row in &b.rows {
+        let strain = &b_row[b_strain_i];
+        if !a_strains.contains(&strain) {
+            table.rows.push(vec![
+                strain.clone(),
+                "added".to_string(),
+                String::new(),
+                strain.clone(),
+            ]);
+        }
+    }
+
+    Ok(table)
+}
@@ -1,8 +1,61 @@
-use crate::dataset::{Dataset, SearchResult};
-use crate::recombination::{validate, Recombination};
+use crate::cli::run;
+use crate::dataset::{strip_population_prefix, Dataset, SearchResult};
+use crate::phylogeny::Phylogeny;
+use crate::plot::constants;
+use crate::recombination::{search, validate, Breakpoint, Recombination, Region};
+use crate::sequence::{Sequence, Substitution};
 use crate::utils;
-use color_eyre::eyre::{Report, Result};
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
 use itertools::Itertools;
+use log::warn;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// ----------------------------------------------------------------------------
+// Coordinates
+
+/// Format a substitution in the output coordinate base.
+pub fn format_substitution(sub: &Substitution, base: &run::CoordinateBase) -> String {
+    let sub = Substitution {
+        coord: base.shift(sub.coord),
+        ..*sub
+    };
+    sub.to_string()
+}
+
+/// Format a breakpoint in the output coordinate base.
+pub fn format_breakpoint(breakpoint: &Breakpoint, base: &run::CoordinateBase) -> String {
+    format!(
+        "{}-{}",
+        base.shift(breakpoint.start),
+        base.shift(breakpoint.end)
+    )
+}
+
+/// Format a region in the output coordinate base.
+pub fn format_region(region: &Region, base: &run::CoordinateBase) -> String {
+    let (start, end) = (base.shift(region.start), base.shift(region.end));
+    format!("{start}-{end}|{}", region.origin)
+}
+
+/// Shift the 'coord' column of a table (ex. barcodes) to the output coordinate base.
+pub fn shift_coords(
+    table: &utils::table::Table,
+    base: &run::CoordinateBase,
+) -> Result<utils::table::Table, Report> {
+    let mut table = table.clone();
+    if *base == run::CoordinateBase::One {
+        return Ok(table);
+    }
+    let coord_i = table.header_position("coord")?;
+    for row in table.rows.iter_mut() {
+        let coord = row[coord_i].parse::<usize>().wrap_err_with(|| {
+            format!("Unable to parse coordinate: {:?}", row[coord_i])
+        })?;
+        row[coord_i] = base.shift(coord).to_string();
+    }
+    Ok(table)
+}
 
 // ----------------------------------------------------------------------------
 // LineList
@@ -10,24 +63,43 @@ use itertools::Itertools;
 pub fn linelist(
     results: &Vec<(SearchResult, Recombination)>,
     dataset: &Dataset,
+    args: &run::Args,
 ) -> Result<utils::table::Table, Report> {
     let mut table = utils::table::Table::new();
+    let base = &args.coordinate_base;
 
     table.headers = vec![
         "strain",
         "validate",
         "validate_details",
+        "qc_status",
+        "qc_notes",
+        "possible_mixture",
+        "identical_to_reference",
         "population",
+        "consensus_ambiguous",
+        "score",
+        "low_quality_match",
         "recombinant",
+        "hypothesis",
+        "proposed_name",
         "parents",
+        "parents_plausible",
         "breakpoints",
         "edge_case",
         "unique_key",
         "regions",
+        "parent_fractions",
         "substitutions",
+        "support_details",
+        "unresolved_subs",
+        "timed_out",
+        "alternative_call",
         "genome_length",
+        "coordinate_base",
         "dataset_name",
         "dataset_tag",
+        "dataset_reference",
         "cli_version",
     ]
     .into_iter()
@@ -47,17 +119,58 @@ pub fn linelist(
         let population = best_match.consensus_population.to_string();
         row[table.header_position("population")?] = population.clone();
 
+        // consensus_ambiguous, a population call rejected as too broad
+        if let Some(population) = &best_match.consensus_ambiguous {
+            row[table.header_position("consensus_ambiguous")?] = population.clone();
+        }
+
+        // score of the population, and whether it fits the sequence poorly
+        if let Some(score) = best_match.score.get(&population) {
+            row[table.header_position("score")?] = score.to_string();
+            row[table.header_position("low_quality_match")?] =
+                (*score < args.min_match_score).to_string();
+        }
+
         // recombinant
         if let Some(recombinant) = &recombination.recombinant {
             row[table.header_position("recombinant")?] = recombinant.clone();
         }
 
+        // hypothesis, unassigned if there was no best match
+        if let Some(hypothesis) = &recombination.hypothesis {
+            row[table.header_position("hypothesis")?] = hypothesis.to_string();
+        }
+
+        // proposed name, for novel recombinants
+        if let Some(proposed_name) = proposed_name(recombination, dataset)? {
+            row[table.header_position("proposed_name")?] = proposed_name;
+        }
+
         // parents
         let parents = recombination.parents.join(",").to_string();
         row[table.header_position("parents")?] = parents;
 
+        // parents_plausible, are parents phylogenetically distinct (not nested)?
+        if recombination.recombinant.is_some() && !dataset.phylogeny.is_empty() {
+            let nested = recombination
+                .parents
+                .iter()
+                .tuple_combinations()
+                .map(|(p1, p2)| dataset.phylogeny.is_nested(p1, p2))
+                .collect::<Result<Vec<_>, Report>>();
+            // parents might not be in the phylogeny (ex. custom datasets)
+            if let Ok(nested) = nested {
+                row[table.header_position("parents_plausible")?] =
+                    (!nested.contains(&true)).to_string();
+            }
+        }
+
         // breakpoints
-        let breakpoints = recombination.breakpoints.iter().join(",").to_string();
+        let breakpoints = recombination
+            .breakpoints
+            .iter()
+            .map(|breakpoint| format_breakpoint(breakpoint, base))
+            .join(",");
         row[table.header_position("breakpoints")?] = breakpoints;
 
         // edge_case
@@ -79,19 +192,50 @@ pub fn linelist(
         row[table.header_position("unique_key")?] = unique_key;
 
         // regions
-        let regions = recombination.regions.values().join(",").to_string();
+        let regions = recombination
+            .regions
+            .values()
+            .map(|region| format_region(region, base))
+            .join(",");
         row[table.header_position("regions")?] = regions;
 
+        // parent fractions, genome-length-normalized span of regions per parent
+        let parent_fractions = recombination
+            .parents
+            .iter()
+            .map(|parent| {
+                let span: usize = recombination
+                    .regions
+                    .values()
+                    .filter(|r| r.origin == *parent)
+                    .map(|r| r.end - r.start + 1)
+                    .sum();
+                let fraction = if recombination.genome_length() > 0 {
+                    span as f32 / recombination.genome_length() as f32
+                } else {
+                    0.0
+                };
+                format!("{parent}:{fraction:.2}")
+            })
+            .join(",");
+        row[table.header_position("parent_fractions")?] = parent_fractions;
+
         // genome_length
-        let genome_length = recombination.genome_length.to_string();
+        let genome_length = recombination.genome_length().to_string();
         row[table.header_position("genome_length")?] = genome_length;
 
+        // coordinate base, so that plots can shift back to 1-based
+        row[table.header_position("coordinate_base")?] = base.to_string();
+
         // dataset name
         row[table.header_position("dataset_name")?] = dataset.name.to_string();
 
         // dataset tag
         row[table.header_position("dataset_tag")?] = dataset.tag.to_string();
 
+        // dataset reference
+        row[table.header_position("dataset_reference")?] = dataset.reference.id.clone();
+
         // cli version
         row[table.header_position("cli_version")?] =
             env!("CARGO_PKG_VERSION").to_string();
@@ -99,29 +243,933 @@ pub fn linelist(
         // --------------------------------------------------------------------
         // Substitutions, annotated by parental origin or private
 
-        let subs_by_origin = recombination.get_substitution_origins(best_match)?;
+        let subs_by_origin = recombination.get_substitution_origins(
+            best_match,
+            &args.private_policy,
+            args.report_reversions,
+        )?;
         let mut origins = Vec::new();
-        // origin order: primary parent, secondary parent, recombinant, private
+        // origin order: primary parent, secondary parent, best match, private, reversion
         if recombination.recombinant.is_some() {
             origins.extend(recombination.parents.clone());
-        } else {
+        }
+        if !origins.contains(&best_match.consensus_population) {
             origins.push(best_match.consensus_population.clone());
         }
         origins.push("private".to_string());
+        if args.report_reversions {
+            origins.push("reversion".to_string());
+        }
 
         // string format
         let substitutions = origins
             .iter()
             .filter_map(|o| {
                 let subs = subs_by_origin.get(o).cloned().unwrap_or_default();
-                let subs_format = format!("{}|{o}", subs.iter().join(","));
+                let subs_format = format!(
+                    "{}|{o}",
+                    subs.iter().map(|sub| format_substitution(sub, base)).join(",")
+                );
                 (!subs.is_empty()).then_some(subs_format)
             })
             .join(";");
         row[table.header_position("substitutions")?] = substitutions;
 
+        // support details, subs supporting each parent within its regions
+        let support_details = recombination
+            .parents
+            .iter()
+            .filter_map(|parent| {
+                let subs = recombination
+                    .support
+                    .get(parent)
+                    .into_iter()
+                    .flatten()
+                    .filter(|sub| {
+                        recombination.regions.values().any(|r| {
+                            r.origin == *parent
+                                && r.start <= sub.coord
+                                && sub.coord <= r.end
+                        })
+                    })
+                    .collect_vec();
+                let subs_format =
+                    subs.iter().map(|sub| format_substitution(sub, base)).join(",");
+                (!subs.is_empty()).then_some(format!("{subs_format}|{parent}"))
+            })
+            .join(";");
+        row[table.header_position("support_details")?] = support_details;
+
+        // unresolved substitutions, not explained by any parent
+        let unresolved_subs = recombination
+            .unresolved
+            .iter()
+            .map(|sub| format_substitution(sub, base))
+            .join(",");
+        row[table.header_position("unresolved_subs")?] = unresolved_subs;
+
+        // qc
+        let (qc_status, qc_notes) = qc(best_match, recombination, args);
+        row[table.header_position("qc_status")?] = qc_status;
+        row[table.header_position("qc_notes")?] = qc_notes.join(";");
+
+        // possible_mixture
+        let mixture = possible_mixture(best_match, recombination, args.mixture_min_sites);
+        row[table.header_position("possible_mixture")?] = mixture.to_string();
+
+        // identical_to_reference, a query without mutations is the reference itself
+        let is_reference = identical_to_reference(recombination.sequence, args);
+        row[table.header_position("identical_to_reference")?] = is_reference.to_string();
+
+        // timed_out
+        row[table.header_position("timed_out")?] = recombination.timed_out.to_string();
+
+        // alternative_call, the runner-up call and its score
+        if let Some((call, score)) = &recombination.alternative {
+            row[table.header_position("alternative_call")?] = format!("{call}:{score}");
+        }
+
+        table.rows.push(row);
+    }
+
+    Ok(table)
+}
+
+// ----------------------------------------------------------------------------
+// Knockout Validation
+
+/// Validate the detected parents of knocked out recombinants.
+///
+/// The expected parents come from the phylogeny before the knockout, since
+/// knocked out populations are removed from the dataset phylogeny. Only the
+/// parents are scored ('parents_status'), the breakpoints are informational,
+/// as the phylogeny has no designated breakpoints to compare them to.
+pub fn knockout_validate(
+    results: &Vec<(SearchResult, Recombination)>,
+    dataset: &Dataset,
+    phylogeny: &Phylogeny,
+    knockout: &[String],
+    base: &run::CoordinateBase,
+) -> Result<utils::table::Table, Report> {
+    let mut table = utils::table::Table::new();
+    table.headers = vec![
+        "strain",
+        "parents_status",
+        "expected_recombinant",
+        "expected_parents",
+        "recombinant",
+        "parents",
+        "breakpoints",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect_vec();
+
+    for (_best_match, recombination) in results {
+        let population = strip_population_prefix(&recombination.sequence.id).to_string();
+        if !knockout.contains(&population) {
+            continue;
+        }
+        // only recombinants have designated parents
+        let Some(expected_recombinant) =
+            phylogeny.get_recombinant_ancestor(&population)?
+        else {
+            continue;
+        };
+        let expected_parents = phylogeny.get_parents(&expected_recombinant)?;
+        let parents_match = validate::compare_parents(
+            &recombination.parents,
+            &expected_parents,
+            dataset,
+        )?;
+        let status = if parents_match {
+            validate::Status::Pass
+        } else {
+            validate::Status::Fail
+        };
+
+        let mut row = vec![String::new(); table.headers.len()];
+        row[table.header_position("strain")?] = recombination.sequence.id.clone();
+        row[table.header_position("parents_status")?] = status.to_string();
+        row[table.header_position("expected_recombinant")?] = expected_recombinant;
+        row[table.header_position("expected_parents")?] = expected_parents.join(",");
+        row[table.header_position("recombinant")?] =
+            recombination.recombinant.clone().unwrap_or_default();
+        row[table.header_position("parents")?] = recombination.parents.join(",");
+        row[table.header_position("breakpoints")?] = recombination
+            .breakpoints
+            .iter()
+            .map(|b| format_breakpoint(b, base))
+            .join(",");
+        table.rows.push(row);
+    }
+
+    Ok(table)
+}
+
+// ----------------------------------------------------------------------------
+// Proposed Name
+
+/// Propose a provisional name for a novel recombinant.
+///
+/// The prefix is the common ancestor of the parents (ex. XBB for XBB.1.5 and
+/// XBB.1.16), or the parents themselves if they only share the root, followed
+/// by the breakpoint start coordinates. Ex. Xnovel_BA.2.75_XBB_21000
+pub fn proposed_name(
+    recombination: &Recombination,
+    dataset: &Dataset,
+) -> Result<Option<String>, Report> {
+    if recombination.recombinant.as_deref() != Some("novel") {
+        return Ok(None);
+    }
+
+    let prefix = if dataset.phylogeny.is_empty() {
+        recombination.parents.join("_")
+    } else {
+        match dataset.phylogeny.get_common_ancestor(&recombination.parents)? {
+            ancestor if ancestor == "root" => recombination.parents.join("_"),
+            ancestor => ancestor,
+        }
+    };
+    let breakpoints = recombination.breakpoints.iter().map(|b| b.start).join("_");
+
+    Ok(Some(format!("Xnovel_{prefix}_{breakpoints}")))
+}
+
+// ----------------------------------------------------------------------------
+// QC
+
+/// Summarize sequence and population call quality into a QC verdict.
+///
+/// Returns the status (pass, warn, fail) and notes on the failed criteria.
+/// Low coverage or high ambiguity is a failure, many private substitutions or
+/// low confidence in the population call is a warning.
+pub fn qc(
+    best_match: &SearchResult,
+    recombination: &Recombination,
+    args: &run::Args,
+) -> (String, Vec<String>) {
+    let sequence = recombination.sequence;
+    let genome_length = sequence.genome_length.max(1) as f32;
+    let mut fail = Vec::new();
+    let mut warnings = Vec::new();
+
+    // coverage, missing data includes ambiguous bases
+    let coverage = 1.0 - (sequence.missing.len() as f32 / genome_length);
+    if coverage < args.qc_min_coverage {
+        fail.push(format!("coverage={coverage:.2}<{}", args.qc_min_coverage));
+    }
+
+    // ambiguity, bases that are not nucleotides, missing (N), or gaps (-)
+    let num_ambiguous = sequence
+        .seq
+        .iter()
+        .filter(|b| !"ACGTN-".contains(b.to_ascii_uppercase()))
+        .count();
+    let ambiguity = num_ambiguous as f32 / genome_length;
+    if ambiguity > args.qc_max_ambiguity {
+        fail.push(format!(
+            "ambiguity={ambiguity:.2}>{}",
+            args.qc_max_ambiguity
+        ));
+    }
+
+    // private substitutions
+    let num_private = best_match.private.len();
+    if num_private > args.qc_max_private {
+        warnings.push(format!("private={num_private}>{}", args.qc_max_private));
+    }
+
+    // confidence, support vs conflict of the consensus population
+    let population = &best_match.consensus_population;
+    let support = best_match.support.get(population).map(|s| s.len()).unwrap_or(0);
+    let conflict = best_match.conflict_ref.get(population).map(|s| s.len()).unwrap_or(0)
+        + best_match.conflict_alt.get(population).map(|s| s.len()).unwrap_or(0);
+    if support + conflict > 0 {
+        let confidence = support as f32 / (support + conflict) as f32;
+        if confidence < args.qc_min_confidence {
+            warnings.push(format!(
+                "confidence={confidence:.2}<{}",
+                args.qc_min_confidence
+            ));
+        }
+    }
+
+    let status = if !fail.is_empty() {
+        "fail"
+    } else if !warnings.is_empty() {
+        "warn"
+    } else {
+        "pass"
+    };
+    fail.extend(warnings);
+
+    (status.to_string(), fail)
+}
+
+/// Whether a sequence is identical to the reference.
+///
+/// A sequence without mutations must also pass the coverage QC, otherwise
+/// mostly missing (N) or ambiguous sequences would be called the reference.
+pub fn identical_to_reference(sequence: &Sequence, args: &run::Args) -> bool {
+    let genome_length = sequence.genome_length.max(1) as f32;
+    let coverage = 1.0 - (sequence.missing.len() as f32 / genome_length);
+    sequence.substitutions.is_empty()
+        && sequence.deletions.is_empty()
+        && coverage >= args.qc_min_coverage
+}
+
+// ----------------------------------------------------------------------------
+// Mixture
+
+/// Flag sequences that look like a mixture of lineages (ex. co-infection).
+///
+/// Mixtures have ambiguous bases and conflicts with the consensus population
+/// scattered across the genome, whereas recombinants have clean parental
+/// regions. The sites are binned into genome tenths, and a mixture occupies at
+/// least half of them.
+pub fn possible_mixture(
+    best_match: &SearchResult,
+    recombination: &Recombination,
+    min_sites: usize,
+) -> bool {
+    let sequence = recombination.sequence;
+
+    // ambiguous bases (IUPAC), consensus calling of a mixture produces these
+    let ambiguous = sequence
+        .seq
+        .iter()
+        .enumerate()
+        .filter(|(_i, b)| !"ACGTN-".contains(b.to_ascii_uppercase()))
+        .map(|(i, _b)| i + 1)
+        .collect_vec();
+    if ambiguous.is_empty() {
+        return false;
+    }
+
+    // conflicts with the consensus population
+    let population = &best_match.consensus_population;
+    let conflicts = best_match
+        .conflict_ref
+        .get(population)
+        .into_iter()
+        .chain(best_match.conflict_alt.get(population))
+        .flatten()
+        .map(|sub| sub.coord);
+
+    let sites = ambiguous.into_iter().chain(conflicts).unique().collect_vec();
+    if min_sites == 0 || sites.len() < min_sites {
+        return false;
+    }
+
+    // spatial spread, recombination clusters sites into consecutive regions
+    let num_bins = 10;
+    let bin_size = sequence.genome_length.div_ceil(num_bins).max(1);
+    let bins = sites.iter().map(|coord| (coord - 1) / bin_size).unique().count();
+
+    bins * 2 >= num_bins
+}
+
+// ----------------------------------------------------------------------------
+// Substitutions
+
+/// Long-format table of substitutions per sample (strain, coord, ref, alt, origin).
+///
+/// Origin is the parent, best match, 'private', or 'reversion', from
+/// [Recombination::get_substitution_origins].
+pub fn substitutions(
+    results: &Vec<(SearchResult, Recombination)>,
+    args: &run::Args,
+) -> Result<utils::table::Table, Report> {
+    let mut table = utils::table::Table::new();
+    table.headers = vec!["strain", "coord", "ref", "alt", "origin"]
+        .into_iter()
+        .map(String::from)
+        .collect_vec();
+
+    for (best_match, recombination) in results {
+        let subs_by_origin = recombination.get_substitution_origins(
+            best_match,
+            &args.private_policy,
+            args.report_reversions,
+        )?;
+        let subs = subs_by_origin
+            .iter()
+            .flat_map(|(origin, subs)| subs.iter().map(move |sub| (sub, origin)))
+            .sorted_by(|a, b| a.0.cmp(b.0).then(a.1.cmp(b.1)))
+            .collect_vec();
+
+        for (sub, origin) in subs {
+            let mut row = vec![String::new(); table.headers.len()];
+            row[table.header_position("strain")?] = recombination.sequence.id.clone();
+            row[table.header_position("coord")?] =
+                args.coordinate_base.shift(sub.coord).to_string();
+            row[table.header_position("ref")?] = sub.reference.to_string();
+            row[table.header_position("alt")?] = sub.alt.to_string();
+            row[table.header_position("origin")?] = origin.clone();
+            table.rows.push(row);
+        }
+    }
+
+    Ok(table)
+}
+
+// ----------------------------------------------------------------------------
+// Features
+
+/// Long-format table of search evidence, one row per (sample, candidate parent).
+///
+/// Candidates are the populations scored in the best match search, and the
+/// recombination parents. For parents, counts are restricted to their regions
+/// (from the [Recombination]). For other candidates, counts are genome-wide
+/// (from the [SearchResult]), and private is the number of substitutions in
+/// the sample that the candidate does not support.
+pub fn features(
+    results: &Vec<(SearchResult, Recombination)>,
+) -> Result<utils::table::Table, Report> {
+    let mut table = utils::table::Table::new();
+    table.headers = vec![
+        "strain",
+        "candidate",
+        "is_parent",
+        "support_count",
+        "conflict_ref_count",
+        "conflict_alt_count",
+        "score",
+        "num_regions",
+        "total_region_span",
+        "private_count",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect_vec();
+
+    for (best_match, recombination) in results {
+        let candidates = best_match
+            .score
+            .keys()
+            .chain(&recombination.parents)
+            .unique()
+            .sorted()
+            .collect_vec();
+
+        for candidate in candidates {
+            let is_parent = recombination.parents.contains(candidate);
+            let count = |subs: Option<&Vec<_>>| subs.map(|s| s.len()).unwrap_or_default();
+
+            let (support, conflict_ref, conflict_alt, score, private) = if is_parent {
+                (
+                    count(recombination.support.get(candidate)),
+                    count(recombination.conflict_ref.get(candidate)),
+                    count(recombination.conflict_alt.get(candidate)),
+                    recombination.score.get(candidate).copied().unwrap_or_default(),
+                    count(recombination.private.get(candidate)),
+                )
+            } else {
+                let support = best_match.support.get(candidate);
+                let private = best_match
+                    .substitutions
+                    .iter()
+                    .filter(|sub| !support.is_some_and(|s| s.contains(sub)))
+                    .count();
+                (
+                    count(support),
+                    count(best_match.conflict_ref.get(candidate)),
+                    count(best_match.conflict_alt.get(candidate)),
+                    best_match.score.get(candidate).copied().unwrap_or_default(),
+                    private,
+                )
+            };
+
+            let regions = recombination
+                .regions
+                .values()
+                .filter(|region| region.origin == *candidate)
+                .collect_vec();
+            let span: usize = regions.iter().map(|r| r.end - r.start + 1).sum();
+
+            let mut row = vec![String::new(); table.headers.len()];
+            row[table.header_position("strain")?] = recombination.sequence.id.clone();
+            row[table.header_position("candidate")?] = candidate.clone();
+            row[table.header_position("is_parent")?] = is_parent.to_string();
+            row[table.header_position("support_count")?] = support.to_string();
+            row[table.header_position("conflict_ref_count")?] = conflict_ref.to_string();
+            row[table.header_position("conflict_alt_count")?] = conflict_alt.to_string();
+            row[table.header_position("score")?] = score.to_string();
+            row[table.header_position("num_regions")?] = regions.len().to_string();
+            row[table.header_position("total_region_span")?] = span.to_string();
+            row[table.header_position("private_count")?] = private.to_string();
+            table.rows.push(row);
+        }
+    }
+
+    Ok(table)
+}
+
+// ----------------------------------------------------------------------------
+// Summary
+
+/// Aggregate summary of an entire run.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Summary {
+    pub num_samples: usize,
+    pub num_classified: usize,
+    pub num_recombinant: usize,
+    pub num_qc_fail: usize,
+    pub num_qc_warn: usize,
+    pub populations: BTreeMap<String, usize>,
+    pub parents: BTreeMap<String, usize>,
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "samples: {}", self.num_samples)?;
+        writeln!(f, "classified: {}", self.num_classified)?;
+        writeln!(f, "recombinant: {}", self.num_recombinant)?;
+        writeln!(f, "qc_fail: {}", self.num_qc_fail)?;
+        writeln!(f, "qc_warn: {}", self.num_qc_warn)?;
+        writeln!(f, "\npopulations:")?;
+        for (population, count) in &self.populations {
+            writeln!(f, "  {population}: {count}")?;
+        }
+        writeln!(f, "\nparents:")?;
+        for (parents, count) in &self.parents {
+            writeln!(f, "  {parents}: {count}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Summarize a run: samples, classifications, recombinants, QC, and breakdowns
+/// by consensus population and (sorted) parents.
+pub fn summary(
+    results: &Vec<(SearchResult, Recombination)>,
+    args: &run::Args,
+) -> Summary {
+    let mut summary = Summary {
+        num_samples: results.len(),
+        ..Default::default()
+    };
+
+    for (best_match, recombination) in results {
+        let population = &best_match.consensus_population;
+        if !population.is_empty() {
+            summary.num_classified += 1;
+            *summary.populations.entry(population.clone()).or_default() += 1;
+        }
+        if recombination.recombinant.is_some() {
+            summary.num_recombinant += 1;
+            let parents = recombination.parents.iter().sorted().join("+");
+            *summary.parents.entry(parents).or_default() += 1;
+        }
+        match qc(best_match, recombination, args).0.as_str() {
+            "fail" => summary.num_qc_fail += 1,
+            "warn" => summary.num_qc_warn += 1,
+            _ => (),
+        }
+    }
+
+    summary
+}
+
+// ----------------------------------------------------------------------------
+// Parent Pairs
+
+/// Summarize recombinants grouped by their (sorted) parents, regardless of breakpoints.
+///
+/// Returns a table of parents (ex. BA.2.75+XBB), count, the range of observed
+/// breakpoints, the unique breakpoints, and member strains, sorted by count.
+pub fn parent_pairs(
+    results: &Vec<(SearchResult, Recombination)>,
+    base: &run::CoordinateBase,
+) -> Result<utils::table::Table, Report> {
+    let mut table = utils::table::Table::new();
+    table.headers = vec![
+        "parents",
+        "count",
+        "breakpoints_range",
+        "breakpoints",
+        "strains",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect_vec();
+
+    // group recombinants by sorted parents
+    let mut groups: BTreeMap<String, Vec<&Recombination>> = BTreeMap::new();
+    for (_best_match, recombination) in results {
+        if recombination.recombinant.is_none() {
+            continue;
+        }
+        let parents = recombination.parents.iter().sorted().join("+");
+        groups.entry(parents).or_default().push(recombination);
+    }
+
+    let groups = groups.into_iter().sorted_by(|a, b| b.1.len().cmp(&a.1.len()));
+    for (parents, recombinations) in groups {
+        let mut row = vec![String::new(); table.headers.len()];
+        row[table.header_position("parents")?] = parents;
+        row[table.header_position("count")?] = recombinations.len().to_string();
+
+        let breakpoints =
+            recombinations.iter().flat_map(|r| &r.breakpoints).collect_vec();
+        let start = breakpoints.iter().map(|b| b.start).min();
+        let end = breakpoints.iter().map(|b| b.end).max();
+        if let (Some(start), Some(end)) = (start, end) {
+            let (start, end) = (base.shift(start), base.shift(end));
+            row[table.header_position("breakpoints_range")?] = format!("{start}-{end}");
+        }
+        row[table.header_position("breakpoints")?] = recombinations
+            .iter()
+            .map(|r| r.breakpoints.iter().map(|b| format_breakpoint(b, base)).join(","))
+            .unique()
+            .join(";");
+        row[table.header_position("strains")?] =
+            recombinations.iter().map(|r| &r.sequence.id).join(",");
         table.rows.push(row);
     }
 
     Ok(table)
 }
+
+// ----------------------------------------------------------------------------
+// Trace
+
+/// Parent search trajectory per sequence, one row per candidate parent.
+///
+/// Outcome is one of: non_recombinant, primary, accepted, no_improvement, or
+/// no_recombination.
+pub fn trace(
+    traces: &[(String, Vec<search::Step>)],
+) -> Result<utils::table::Table, Report> {
+    let mut table = utils::table::Table::new();
+    table.headers = vec![
+        "strain",
+        "hypothesis",
+        "parent",
+        "iteration",
+        "candidate",
+        "score",
+        "outcome",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect_vec();
+
+    for (strain, steps) in traces {
+        for step in steps {
+            let mut row = vec![String::new(); table.headers.len()];
+            row[table.header_position("strain")?] = strain.clone();
+            row[table.header_position("hypothesis")?] = step.hypothesis.clone();
+            row[table.header_position("parent")?] = step.parent.to_string();
+            row[table.header_position("iteration")?] = step.iteration.to_string();
+            row[table.header_position("candidate")?] = step.candidate.clone();
+            row[table.header_position("score")?] = step.score.to_string();
+            row[table.header_position("outcome")?] = step.outcome.clone();
+            table.rows.push(row);
+        }
+    }
+
+    Ok(table)
+}
+
+// ----------------------------------------------------------------------------
+// Subthreshold
+
+/// Best rejected parental regions of each non-recombinant sequence.
+///
+/// Among the candidate parents that failed the recombination filters, the
+/// near-miss with the most substitutions in its regions is reported, along
+/// with the reason it was rejected.
+pub fn subthreshold(
+    results: &Vec<(SearchResult, Recombination)>,
+    traces: &[(String, Vec<search::Step>)],
+    base: &run::CoordinateBase,
+) -> Result<utils::table::Table, Report> {
+    let mut table = utils::table::Table::new();
+    table.headers = vec!["strain", "parents", "regions", "reason"]
+        .into_iter()
+        .map(String::from)
+        .collect_vec();
+
+    for ((_best_match, recombination), (strain, steps)) in results.iter().zip(traces) {
+        if !recombination.parents.is_empty() {
+            continue;
+        }
+        let best = steps.iter().filter_map(|step| step.subthreshold.as_ref()).max_by_key(
+            |subthreshold| {
+                subthreshold
+                    .regions
+                    .values()
+                    .map(|region| region.substitutions.len())
+                    .sum::<usize>()
+            },
+        );
+        let Some(subthreshold) = best else {
+            continue;
+        };
+
+        let mut row = vec![String::new(); table.headers.len()];
+        row[table.header_position("strain")?] = strain.clone();
+        row[table.header_position("parents")?] = subthreshold.parents.join(",");
+        row[table.header_position("regions")?] = subthreshold
+            .regions
+            .values()
+            .map(|region| format_region(region, base))
+            .join(",");
+        row[table.header_position("reason")?] = subthreshold.reason.clone();
+        table.rows.push(row);
+    }
+
+    Ok(table)
+}
+
+// ----------------------------------------------------------------------------
+// Pangolin
+
+/// Remap results to pangolin-style lineage columns (taxon, lineage, conflict, note).
+///
+/// Conflict is the fraction of the consensus population's informative
+/// substitutions that conflict with the sequence. Fields avoid commas, so the
+/// table can be written as CSV.
+pub fn pangolin_compat(
+    results: &Vec<(SearchResult, Recombination)>,
+    base: &run::CoordinateBase,
+) -> Result<utils::table::Table, Report> {
+    let mut table = utils::table::Table::new();
+    table.headers = vec!["taxon", "lineage", "conflict", "note"]
+        .into_iter()
+        .map(String::from)
+        .collect_vec();
+
+    for (best_match, recombination) in results {
+        let mut row = vec![String::new(); table.headers.len()];
+        row[table.header_position("taxon")?] = recombination.sequence.id.clone();
+
+        let population = &best_match.consensus_population;
+        if population.is_empty() {
+            row[table.header_position("lineage")?] = "Unassigned".to_string();
+            table.rows.push(row);
+            continue;
+        }
+        row[table.header_position("lineage")?] = population.clone();
+
+        // conflict
+        let support = best_match.support.get(population).map(|s| s.len()).unwrap_or(0);
+        let conflict =
+            best_match.conflict_ref.get(population).map(|s| s.len()).unwrap_or(0)
+                + best_match.conflict_alt.get(population).map(|s| s.len()).unwrap_or(0);
+        let conflict = if support + conflict > 0 {
+            conflict as f32 / (support + conflict) as f32
+        } else {
+            0.0
+        };
+        row[table.header_position("conflict")?] = format!("{conflict:.2}");
+
+        // note
+        if let Some(recombinant) = &recombination.recombinant {
+            row[table.header_position("note")?] = format!(
+                "Recombinant {recombinant}: {} breakpoints {}",
+                recombination.parents.join("/"),
+                recombination
+                    .breakpoints
+                    .iter()
+                    .map(|b| format_breakpoint(b, base))
+                    .join(" "),
+            );
+        }
+
+        table.rows.push(row);
+    }
+
+    Ok(table)
+}
+
+// ----------------------------------------------------------------------------
+// Parent Alignment
+
+/// Align a recombinant sequence against its parents, for manual review.
+///
+/// Returns FASTA records of the Reference, each parent, and the sequence,
+/// restricted to the sites that discriminate between the parents.
+pub fn parent_alignment(recombination: &Recombination) -> Result<String, Report> {
+    let table = &recombination.table;
+    let origin_i = table.header_position("origin")?;
+
+    // discriminating sites, private mutations are excluded
+    let rows = table.rows.iter().filter(|row| row[origin_i] != "private").collect_vec();
+
+    // records order: Reference, parents, sequence
+    let mut ids = vec!["Reference".to_string()];
+    ids.extend(recombination.parents.clone());
+    ids.push(recombination.sequence.id.clone());
+
+    let mut fasta = String::new();
+    for id in ids {
+        let col_i = table.header_position(&id)?;
+        let seq = rows.iter().map(|row| row[col_i].as_str()).join("");
+        fasta.push_str(&format!(">{id}\n{seq}\n"));
+    }
+
+    Ok(fasta)
+}
+
+// ----------------------------------------------------------------------------
+// Expected Recombinant
+
+/// Construct the expected recombinant sequence from its parental regions.
+///
+/// Each region takes the bases of its parent. Bases between regions (the
+/// breakpoint intervals) are taken from the parents where they agree,
+/// otherwise they are missing ('N').
+pub fn expected_recombinant(
+    recombination: &Recombination,
+    dataset: &Dataset,
+) -> Result<Sequence, Report> {
+    let genome_length = dataset.reference.seq.len();
+    let mut seq = vec!['N'; genome_length];
+
+    // the parent (consensus) might not have sequence data itself
+    let mut parent_seqs = Vec::new();
+    for parent in &recombination.parents {
+        let population = dataset.get_ancestor_with_sequence(parent)?;
+        if &population != parent {
+            warn!(
+                "Using {population} sequence for expected recombinant parent {parent}."
+            );
+        }
+        parent_seqs.push((parent, &dataset.populations[&population].seq));
+    }
+
+    let regions = recombination.regions.values().collect_vec();
+    for (region_i, region) in regions.iter().enumerate() {
+        let (_parent, parent_seq) = parent_seqs
+            .iter()
+            .find(|(p, _seq)| **p == region.origin)
+            .ok_or_else(|| eyre!("Region origin {} is not a parent.", region.origin))?;
+
+        // extend the first and last regions to the genome ends
+        let start = if region_i == 0 { 1 } else { region.start };
+        let end = if region_i == regions.len() - 1 {
+            genome_length
+        } else {
+            region.end
+        };
+        // Reminder, -1 to coordinates since they are 1-based
+        seq[start - 1..end].copy_from_slice(&parent_seq[start - 1..end]);
+
+        // breakpoint interval, until the next region starts
+        if let Some(next) = regions.get(region_i + 1) {
+            let (_parent, next_seq) = parent_seqs
+                .iter()
+                .find(|(p, _seq)| **p == next.origin)
+                .ok_or_else(|| eyre!("Region origin {} is not a parent.", next.origin))?;
+            for i in end..next.start - 1 {
+                if parent_seq[i] == next_seq[i] {
+                    seq[i] = parent_seq[i];
+                }
+            }
+        }
+    }
+
+    let id = format!("{}_expected", recombination.sequence.id);
+    let seq = seq.iter().join("");
+    let record = bio::io::fasta::Record::with_attrs(&id, None, seq.as_bytes());
+    // dataset is already masked, no need
+    let mask = Vec::new();
+    Sequence::from_record(record, Some(&dataset.reference), &mask)
+}
+
+// ----------------------------------------------------------------------------
+// Alignment
+
+/// Multiple sequence alignment of sequences in reference coordinates.
+///
+/// Returns FASTA records of each sequence, reconstructed from its
+/// substitutions, deletions, and missing data.
+pub fn alignment(sequences: &[&Sequence], reference: &Sequence) -> String {
+    sequences
+        .iter()
+        .map(|sequence| {
+            let seq = sequence.to_reference_frame(reference).iter().join("");
+            format!(">{}\n{seq}\n", sequence.id)
+        })
+        .join("")
+}
+
+// ----------------------------------------------------------------------------
+// Recombinations
+
+/// Full recombination results (JSON), keyed by sequence id.
+///
+/// The sequence bases are omitted, to keep the file small, but the sequence
+/// substitutions, deletions, and missing data are retained.
+pub fn recombinations(
+    results: &[(SearchResult, Recombination)],
+) -> Result<String, Report> {
+    let recombinations = results
+        .iter()
+        .map(|(_best_match, recombination)| {
+            let mut value = serde_json::to_value(recombination)?;
+            if let Some(sequence) =
+                value.get_mut("sequence").and_then(|s| s.as_object_mut())
+            {
+                sequence.remove("seq");
+            }
+            Ok((recombination.sequence.id.clone(), value))
+        })
+        .collect::<Result<BTreeMap<_, _>, serde_json::Error>>()
+        .wrap_err("Failed to parse recombinations.")?;
+
+    let output = serde_json::to_string_pretty(&recombinations)
+        .wrap_err("Failed to parse recombinations.")?;
+    Ok(format!("{output}\n"))
+}
+
+// ----------------------------------------------------------------------------
+// BED
+
+/// Breakpoints and parental regions of recombinants, in BED format.
+///
+/// Returns two tracks: 'breakpoints' named by sample, and 'regions' named by
+/// sample and origin, colored to match the parents in the plot palette. BED
+/// coordinates are 0-based half-open, whereas rebar coordinates are 1-based
+/// and inclusive. This does not depend on the --coordinate-base.
+pub fn bed(results: &Vec<(SearchResult, Recombination)>, chrom: &str) -> String {
+    let recombinants = results
+        .iter()
+        .filter(|(_b, r)| r.recombinant.is_some())
+        .map(|(_b, r)| r)
+        .collect_vec();
+
+    let mut bed =
+        "track name=breakpoints description=\"rebar breakpoints\"\n".to_string();
+    for recombination in &recombinants {
+        let name = &recombination.sequence.id;
+        for breakpoint in &recombination.breakpoints {
+            let (start, end) = (breakpoint.start - 1, breakpoint.end);
+            bed.push_str(&format!("{chrom}\t{start}\t{end}\t{name}\n"));
+        }
+    }
+
+    bed.push_str("track name=regions description=\"rebar regions\" itemRgb=\"On\"\n");
+    for recombination in &recombinants {
+        for region in recombination.regions.values() {
+            let name = format!("{}|{}", recombination.sequence.id, region.origin);
+            let (start, end) = (region.start - 1, region.end);
+            // same parent colors as the plot, wrap around if the palette runs out
+            let parent_i = recombination
+                .parents
+                .iter()
+                .position(|p| *p == region.origin)
+                .unwrap_or_default();
+            let [r, g, b, _a] =
+                constants::PALETTE_DARK[parent_i % constants::PALETTE_DARK.len()];
+            bed.push_str(&format!(
+                "{chrom}\t{start}\t{end}\t{name}\t0\t.\t{start}\t{end}\t{r},{g},{b}\n"
+            ));
+        }
+    }
+
+    bed
+}
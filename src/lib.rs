@@ -1,5 +1,7 @@
+pub mod benchmark;
 pub mod cli;
 pub mod dataset;
+pub mod diff;
 pub mod export;
 pub mod phylogeny;
 pub mod plot;
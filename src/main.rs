@@ -1,9 +1,18 @@
-use clap::Parser;
+use clap::{CommandFactory, Parser};
 use color_eyre::eyre::{Report, Result};
 use rebar::cli::{dataset, Cli, Command};
+use rebar::utils::exit;
 
 #[tokio::main]
-async fn main() -> Result<(), Report> {
+async fn main() {
+    // Exit with a machine-readable code for the category of error
+    if let Err(report) = rebar().await {
+        eprintln!("Error: {report:?}");
+        std::process::exit(exit::classify(&report) as i32);
+    }
+}
+
+async fn rebar() -> Result<(), Report> {
     // ------------------------------------------------------------------------
     // CLI Setup
 
@@ -20,7 +29,7 @@ async fn main() -> Result<(), Report> {
     // initialize env_logger crate for logging/verbosity level
     env_logger::init();
 
-    // check which CLI command we're running (dataset, run, plot)
+    // check which CLI command we're running (dataset, run, plot, ...)
     match args.command {
         // Dataset
         Command::Dataset(args) => match args.command {
@@ -28,6 +37,9 @@ async fn main() -> Result<(), Report> {
             dataset::Command::Download(mut args) => {
                 rebar::dataset::download::dataset(&mut args).await?
             }
+            dataset::Command::Build(args) => rebar::dataset::io::build(&args)?,
+            dataset::Command::Stats(args) => rebar::dataset::stats::stats(&args)?,
+            dataset::Command::PlotTree(args) => rebar::plot::tree::plot_tree(&args)?,
         },
         // Run
         Command::Run(mut args) => rebar::run::run(&mut args)?,
@@ -35,6 +47,17 @@ async fn main() -> Result<(), Report> {
         Command::Plot(args) => rebar::plot::plot(&args)?,
         // Simulate
         Command::Simulate(args) => rebar::simulate::simulate(&args)?,
+        // Diff Linelist
+        Command::DiffLinelist(args) => rebar::diff::diff_linelist(&args)?,
+        // Benchmark
+        Command::Benchmark(args) => rebar::benchmark::benchmark(&args)?,
+        // Completions
+        Command::Completions(args) => clap_complete::generate(
+            args.shell,
+            &mut Cli::command(),
+            "rebar",
+            &mut std::io::stdout(),
+        ),
     }
 
     Ok(())
@@ -2,10 +2,10 @@ use crate::utils;
 use color_eyre::eyre::{eyre, ContextCompat, Report, Result, WrapErr};
 use color_eyre::Help;
 use itertools::Itertools;
-use log::debug;
+use log::{debug, warn};
 use petgraph::dot::{Config, Dot};
 use petgraph::graph::{Graph, NodeIndex};
-use petgraph::visit::{Dfs, IntoNodeReferences};
+use petgraph::visit::{Dfs, EdgeRef, IntoNodeReferences};
 use petgraph::Direction;
 use serde::{Deserialize, Serialize};
 use serde_json;
@@ -122,6 +122,52 @@ impl Phylogeny {
         Ok(non_recombinants)
     }
 
+    /// Incrementally add populations to the graph, without a full rebuild.
+    ///
+    /// Populations are provided with their parents, in order (parents before
+    /// children). Populations already in the graph are skipped, so existing
+    /// node indices are preserved. Returns the names of the added populations.
+    pub fn update_from(
+        &mut self,
+        populations: &[(String, Vec<String>)],
+    ) -> Result<Vec<String>, Report> {
+        let mut added = Vec::new();
+
+        for (name, parents) in populations {
+            if self.get_node(name).is_ok() {
+                continue;
+            }
+            debug!("Population: {name}; Parents: {parents:?}");
+
+            // check parents before modifying the graph
+            for parent in parents {
+                if self.get_node(parent).is_err() {
+                    return Err(eyre!("Parental lineage {parent} is not in the graph.")
+                        .suggestion(
+                            "Are the alias_key.json and lineage_notes.txt out of sync?",
+                        )
+                        .suggestion("Parents are required to appear before children."));
+                }
+            }
+
+            let id = self.graph.add_node(name.clone());
+            for parent in parents {
+                let parent_id = self.get_node(parent)?;
+                self.graph.add_edge(parent_id, id, 1);
+            }
+
+            // If multiple parents add this to recombinants list
+            if parents.len() > 1 {
+                self.recombinants.push(name.clone());
+            }
+            added.push(name.clone());
+        }
+
+        self.recombinants_all = self.get_recombinants_all()?;
+
+        Ok(added)
+    }
+
     /// Remove a single named node in the graph.
     ///
     /// Connect parents to children to fill the hole.
@@ -173,18 +219,282 @@ impl Phylogeny {
     }
 
     /// Read phylogeny from file.
+    ///
+    /// Auspice v2 JSON files (with a 'tree' key) and Newick files (.nwk, .tree)
+    /// are also accepted.
     pub fn read(path: &Path) -> Result<Phylogeny, Report> {
         let phylogeny = std::fs::read_to_string(path)
             .wrap_err_with(|| format!("Failed to read file: {path:?}."))?;
-        let mut phylogeny: Phylogeny = serde_json::from_str(&phylogeny)
+
+        let ext = utils::path_to_ext(path).unwrap_or_default();
+        if ext == "nwk" || ext == "tree" {
+            debug!("Parsing phylogeny from newick: {path:?}");
+            let mut phylogeny = Phylogeny::from_newick(&phylogeny)
+                .wrap_err_with(|| format!("Failed to parse file: {path:?}."))?;
+            phylogeny.recombinants = phylogeny.get_recombinants()?;
+            phylogeny.recombinants_all = phylogeny.get_recombinants_all()?;
+            return Ok(phylogeny);
+        }
+
+        let phylogeny: serde_json::Value = serde_json::from_str(&phylogeny)
             .wrap_err_with(|| format!("Failed to parse file: {path:?}."))?;
 
+        let mut phylogeny: Phylogeny = if let Some(tree) = phylogeny.get("tree") {
+            debug!("Parsing phylogeny from auspice JSON: {path:?}");
+            Phylogeny::from_auspice_tree(tree)?
+        } else {
+            serde_json::from_value(phylogeny)
+                .wrap_err_with(|| format!("Failed to parse file: {path:?}."))?
+        };
+
         phylogeny.recombinants = phylogeny.get_recombinants()?;
         phylogeny.recombinants_all = phylogeny.get_recombinants_all()?;
 
         Ok(phylogeny)
     }
 
+    /// Read phylogeny from an auspice v2 JSON (ex. Nextstrain builds).
+    ///
+    /// Recombinants cannot be represented in auspice trees, so the phylogeny
+    /// will not contain any.
+    pub fn from_auspice_json(path: &Path) -> Result<Phylogeny, Report> {
+        let auspice = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read file: {path:?}."))?;
+        let auspice: serde_json::Value = serde_json::from_str(&auspice)
+            .wrap_err_with(|| format!("Failed to parse file: {path:?}."))?;
+        let tree = auspice
+            .get("tree")
+            .wrap_err_with(|| format!("Auspice JSON has no tree: {path:?}"))?;
+
+        let mut phylogeny = Phylogeny::from_auspice_tree(tree)?;
+        phylogeny.recombinants = phylogeny.get_recombinants()?;
+        phylogeny.recombinants_all = phylogeny.get_recombinants_all()?;
+
+        Ok(phylogeny)
+    }
+
+    /// Convert the root node of an auspice v2 tree into a phylogeny.
+    ///
+    /// Nodes are labelled by their lineage or clade attribute if available,
+    /// otherwise by their name. Consecutive nodes with the same label are
+    /// collapsed into one.
+    fn from_auspice_tree(tree: &serde_json::Value) -> Result<Phylogeny, Report> {
+        let mut phylogeny = Phylogeny::new();
+        let root = phylogeny.graph.add_node("root".to_string());
+
+        // depth-first traversal, of (auspice node, parent graph node)
+        let mut stack = vec![(tree, root)];
+        while let Some((node, parent)) = stack.pop() {
+            let label = auspice_label(node)
+                .wrap_err_with(|| format!("Auspice node has no name: {node}"))?;
+
+            let node_i = if label == phylogeny.graph[parent] {
+                parent
+            }
+            // a label in multiple places of the tree would otherwise look
+            // like a recombinant (multiple parents), keep the first one
+            else if let Ok(node_i) = phylogeny.get_node(&label) {
+                debug!("Auspice label {label} is not monophyletic, using first node.");
+                node_i
+            } else {
+                let node_i = phylogeny.graph.add_node(label);
+                phylogeny.graph.add_edge(parent, node_i, 1);
+                node_i
+            };
+
+            if let Some(children) = node.get("children").and_then(|c| c.as_array()) {
+                children.iter().for_each(|child| stack.push((child, node_i)));
+            }
+        }
+
+        Ok(phylogeny)
+    }
+
+    /// Parse a phylogeny from a Newick tree.
+    ///
+    /// Branch lengths and comments are ignored. Unlabelled internal nodes are
+    /// named by their order in the tree (ex. node_2), and an unlabelled root is
+    /// named 'root'. Newick trees cannot contain recombinants.
+    pub fn from_newick(newick: &str) -> Result<Phylogeny, Report> {
+        // nodes in the order they appear (parents before children)
+        let mut labels: Vec<Option<String>> = vec![None];
+        let mut parents: Vec<Option<usize>> = vec![None];
+        let mut current = 0;
+        let mut complete = false;
+
+        let delimiters = ['(', ')', ',', ':', ';', '[', '\''];
+        let mut chars = newick.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                // descend to the first child
+                '(' => {
+                    labels.push(None);
+                    parents.push(Some(current));
+                    current = labels.len() - 1;
+                }
+                // move to the next sibling
+                ',' => {
+                    let parent = parents[current]
+                        .wrap_err("Newick has a ',' outside of parentheses.")?;
+                    labels.push(None);
+                    parents.push(Some(parent));
+                    current = labels.len() - 1;
+                }
+                // ascend to the parent, which is labelled next
+                ')' => {
+                    current =
+                        parents[current].wrap_err("Newick has an unmatched ')'.")?;
+                }
+                // branch length
+                ':' => while chars.next_if(|c| !delimiters.contains(c)).is_some() {},
+                // comment
+                '[' => {
+                    chars
+                        .find(|c| *c == ']')
+                        .wrap_err("Newick has an unclosed comment.")?;
+                }
+                ';' => {
+                    complete = true;
+                    break;
+                }
+                c if c.is_whitespace() => continue,
+                // quoted label, with '' as an escaped quote
+                '\'' => {
+                    let mut label = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('\'') if chars.next_if_eq(&'\'').is_some() => {
+                                label.push('\'')
+                            }
+                            Some('\'') => break,
+                            Some(c) => label.push(c),
+                            None => return Err(eyre!("Newick has an unclosed quote.")),
+                        }
+                    }
+                    labels[current] = Some(label);
+                }
+                // unquoted label
+                c => {
+                    let mut label = c.to_string();
+                    while let Some(c) =
+                        chars.next_if(|c| !delimiters.contains(c) && !c.is_whitespace())
+                    {
+                        label.push(c);
+                    }
+                    labels[current] = Some(label);
+                }
+            }
+        }
+
+        if !complete || current != 0 {
+            return Err(eyre!("Newick is incomplete.")
+                .suggestion("Is the tree missing a ')' or the terminating ';'?"));
+        }
+
+        // name unlabelled nodes, and check that labels are unique
+        let names = labels
+            .into_iter()
+            .enumerate()
+            .map(|(i, label)| match label {
+                Some(label) => label,
+                None if i == 0 => "root".to_string(),
+                None => format!("node_{i}"),
+            })
+            .collect_vec();
+        let duplicates = names.iter().duplicates().collect_vec();
+        if !duplicates.is_empty() {
+            return Err(eyre!("Newick labels are duplicated: {duplicates:?}"));
+        }
+
+        // parents precede children, so node indices match the tree order
+        let mut phylogeny = Phylogeny::new();
+        for (name, parent) in names.into_iter().zip(parents) {
+            let node = phylogeny.graph.add_node(name);
+            if let Some(parent) = parent {
+                phylogeny.graph.add_edge(NodeIndex::new(parent), node, 1);
+            }
+        }
+
+        Ok(phylogeny)
+    }
+
+    /// Convert the phylogeny to a Newick tree.
+    ///
+    /// Recombinants cannot be represented in Newick, so they are written once
+    /// under their first parent, and the edges to other parents are lost.
+    pub fn to_newick(&self) -> Result<String, Report> {
+        // the first parent is the earliest edge added to the graph
+        let first_parent = |node: NodeIndex| {
+            self.graph
+                .edges_directed(node, Direction::Incoming)
+                .min_by_key(|edge| edge.id())
+                .map(|edge| edge.source())
+        };
+
+        let lost_edges = self
+            .graph
+            .node_indices()
+            .filter_map(|node| {
+                let first = first_parent(node)?;
+                let lost = self
+                    .graph
+                    .neighbors_directed(node, Direction::Incoming)
+                    .filter(|parent| *parent != first)
+                    .map(|parent| self.graph[parent].clone())
+                    .collect_vec();
+                (!lost.is_empty())
+                    .then(|| format!("{} ({})", self.graph[node], lost.join(", ")))
+            })
+            .collect_vec();
+        if !lost_edges.is_empty() {
+            warn!("Recombinant edges cannot be represented in newick, retaining only the first parent of: {}", lost_edges.join(", "));
+        }
+
+        // children of each node, excluding recombinant edges that are lost
+        let children = |node: NodeIndex| {
+            self.graph
+                .edges_directed(node, Direction::Outgoing)
+                .sorted_by_key(|edge| edge.id())
+                .map(|edge| edge.target())
+                .filter(|child| first_parent(*child) == Some(node))
+                .collect_vec()
+        };
+
+        // depth-first traversal from the root(s), writing each subtree when
+        // all of its children have been written
+        let mut subtrees = Vec::new();
+        let roots = self.graph.externals(Direction::Incoming).collect_vec();
+        for root in roots {
+            let mut stack = vec![(root, false)];
+            let mut written: Vec<String> = Vec::new();
+            while let Some((node, visited)) = stack.pop() {
+                let node_children = children(node);
+                if !visited {
+                    stack.push((node, true));
+                    node_children.iter().rev().for_each(|c| stack.push((*c, false)));
+                    continue;
+                }
+                let subtree_children =
+                    written.split_off(written.len() - node_children.len());
+                let label = newick_label(&self.graph[node]);
+                let subtree = if subtree_children.is_empty() {
+                    label
+                } else {
+                    format!("({}){label}", subtree_children.join(","))
+                };
+                written.push(subtree);
+            }
+            subtrees.extend(written);
+        }
+
+        match subtrees.len() {
+            0 => Err(eyre!("Phylogeny is empty.")),
+            1 => Ok(format!("{};", subtrees[0])),
+            // multiple roots are joined under an unlabelled root
+            _ => Ok(format!("({});", subtrees.join(","))),
+        }
+    }
+
     /// Write phylogeny to file.
     pub fn write(&self, output_path: &Path) -> Result<(), Report> {
         // Create output file
@@ -213,11 +523,14 @@ impl Phylogeny {
             // JSON for rebar
             "json" => serde_json::to_string_pretty(&self)
                 .unwrap_or_else(|_| panic!("Failed to parse: {self:?}")),
+            // ----------------------------------------------------------------
+            // Newick for other phylogenetics tools
+            "nwk" | "tree" => format!("{}\n", self.to_newick()?),
             _ => {
                 return Err(eyre!(
                     "Phylogeny write for extension .{ext} is not supported."
                 )
-                .suggestion("Please try .json or .dot instead."))
+                .suggestion("Please try .json, .dot, or .nwk instead."))
             }
         };
 
@@ -292,12 +605,7 @@ impl Phylogeny {
             for i1 in 0..parents.len() - 1 {
                 let p1 = &parents[i1];
                 for p2 in parents.iter().skip(i1 + 1) {
-                    let mut descendants = self.get_descendants(p2)?;
-                    let ancestors =
-                        self.get_ancestors(p2)?.into_iter().flatten().collect_vec();
-                    descendants.extend(ancestors);
-
-                    if descendants.contains(p1) {
+                    if self.is_nested(p1, p2)? {
                         problematic_recombinants.push(recombinant.clone());
                         break;
                     }
@@ -308,6 +616,15 @@ impl Phylogeny {
         Ok(problematic_recombinants)
     }
 
+    /// Return true if two populations are in an ancestor-descendant relationship.
+    pub fn is_nested(&self, p1: &str, p2: &str) -> Result<bool, Report> {
+        let mut relatives = self.get_descendants(p2)?;
+        let ancestors = self.get_ancestors(p2)?.into_iter().flatten().collect_vec();
+        relatives.extend(ancestors);
+
+        Ok(relatives.iter().any(|p| p == p1))
+    }
+
     /// Get all paths from the origin node to the destination node, always traveling
     /// in the specified direction (Incoming towards root, Outgoing towards tips)/
     /// petgraph must have this already implemented, but I can't find it in docs
@@ -405,12 +722,7 @@ impl Phylogeny {
         let depths = common_ancestors
             .into_iter()
             .map(|pop| {
-                let paths = self.get_paths(pop, "root", Direction::Incoming)?;
-                let longest_path = paths
-                    .into_iter()
-                    .max_by(|a, b| a.len().cmp(&b.len()))
-                    .unwrap_or_default();
-                let depth = longest_path.len();
+                let depth = self.get_depth(pop)?;
                 debug!("{pop}: {depth}");
                 Ok((pop, depth))
             })
@@ -428,6 +740,13 @@ impl Phylogeny {
         Ok(common_ancestor)
     }
 
+    /// Get the depth of a named node, as the longest distance (edges) from root.
+    pub fn get_depth(&self, name: &str) -> Result<usize, Report> {
+        let paths = self.get_paths(name, "root", Direction::Incoming)?;
+        let longest_path = paths.into_iter().map(|p| p.len()).max().unwrap_or_default();
+        Ok(longest_path.saturating_sub(1))
+    }
+
     /// Identify the most recent ancestor that is a recombinant.
     pub fn get_recombinant_ancestor(&self, name: &str) -> Result<Option<String>, Report> {
         let mut recombinant: Option<String> = None;
@@ -467,3 +786,26 @@ impl Phylogeny {
         Err(eyre!("Node {node:?} is not in the phylogeny."))
     }
 }
+
+// ----------------------------------------------------------------------------
+// Functions
+
+/// Quote a Newick label, if it contains delimiters or whitespace.
+fn newick_label(label: &str) -> String {
+    let delimiters = ['(', ')', ',', ':', ';', '[', ']', '\''];
+    if label.chars().any(|c| delimiters.contains(&c) || c.is_whitespace()) {
+        format!("'{}'", label.replace('\'', "''"))
+    } else {
+        label.to_string()
+    }
+}
+
+/// Get the label of an auspice node, preferring lineage or clade attributes.
+fn auspice_label(node: &serde_json::Value) -> Option<String> {
+    let attributes = ["Nextclade_pango", "pango_lineage", "clade_membership"];
+    attributes
+        .iter()
+        .find_map(|attr| node.get("node_attrs")?.get(attr)?.get("value")?.as_str())
+        .or_else(|| node.get("name")?.as_str())
+        .map(String::from)
+}
@@ -1,31 +1,69 @@
 pub mod constants;
 pub mod polygon;
 pub mod text;
+pub mod tree;
 
 use crate::cli;
-use crate::utils::table::Table;
-use color_eyre::eyre::{eyre, Report, Result};
+use crate::dataset::attributes::Summary;
+use crate::dataset::strip_population_prefix;
+use crate::recombination::Breakpoint;
+use crate::sequence::read_reference;
+use crate::utils::{self, exit, table::Table};
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
 use color_eyre::Help;
 use itertools::Itertools;
 use log::{debug, info, warn};
 use raqote::*;
-use std::fs::create_dir_all;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, File};
+use std::io::Write;
 use std::path::Path;
+use std::str::FromStr;
+use tempfile::TempDir;
 
 /// Plot rebar output
 pub fn plot(args: &cli::plot::Args) -> Result<(), Report> {
     // ------------------------------------------------------------------------
     // Parse Args
 
-    let run_dir = &args.run_dir;
+    // unpack a bundle into a temporary run directory, and plot that instead
+    if let Some(bundle_path) = &args.bundle {
+        let tmp_dir = TempDir::new()?;
+        let bundle = Bundle::read(bundle_path)?;
+        bundle.unpack(tmp_dir.path())?;
+
+        let bundle_dir = bundle_path.parent().unwrap_or(Path::new("."));
+        let annotations = tmp_dir.path().join("annotations.tsv");
+        let args = cli::plot::Args {
+            run_dir: Some(tmp_dir.path().to_path_buf()),
+            bundle: None,
+            annotations: annotations.exists().then_some(annotations),
+            output_dir: Some(args.output_dir.clone().unwrap_or(bundle_dir.join("plots"))),
+            ..args.clone()
+        };
+        return plot(&args);
+    }
+
+    let run_dir = args
+        .run_dir
+        .as_ref()
+        .ok_or_else(|| eyre!(exit::Code::Usage).wrap_err("--run-dir is required."))?;
     if !run_dir.exists() {
-        return Err(eyre!("--run-dir {run_dir:?} does not exist."));
+        return Err(eyre!(exit::Code::Usage)
+            .wrap_err(format!("--run-dir {run_dir:?} does not exist.")));
     }
 
     // ------------------------------------------------------------------------
     // Check Mandatory Paths
 
-    let linelist = &run_dir.join("linelist.tsv");
+    // the linelist might have been gzip compressed (--compress-output)
+    let linelist_gz = run_dir.join("linelist.tsv.gz");
+    let linelist = &if linelist_gz.exists() {
+        linelist_gz
+    } else {
+        run_dir.join("linelist.tsv")
+    };
     if !linelist.exists() {
         return Err(eyre!(
             "Linelist file {linelist:?} does not exist in --run-dir {run_dir:?}."
@@ -38,7 +76,15 @@ pub fn plot(args: &cli::plot::Args) -> Result<(), Report> {
         ));
     }
 
-    if let Some(annotations) = &args.annotations {
+    // check the linelist is from a single run, and compatible with the dataset
+    check_linelist(linelist, args.dataset_dir.as_deref())?;
+
+    // default to the dataset annotations
+    let annotations = args.annotations.clone().or_else(|| {
+        let path = args.dataset_dir.as_ref()?.join("annotations.tsv");
+        path.exists().then_some(path)
+    });
+    if let Some(annotations) = &annotations {
         if !annotations.exists() {
             return Err(eyre!("Annotations do not exist: {annotations:?}"));
         }
@@ -69,8 +115,11 @@ pub fn plot(args: &cli::plot::Args) -> Result<(), Report> {
         let files = std::fs::read_dir(barcodes_dir)?;
         for result in files {
             let file_path = result?.path();
-            let file_ext = file_path.extension().unwrap_or(std::ffi::OsStr::new(""));
-            if file_ext == "tsv" {
+            let file_name = file_path
+                .file_name()
+                .unwrap_or(std::ffi::OsStr::new(""))
+                .to_string_lossy();
+            if file_name.ends_with(".tsv") || file_name.ends_with(".tsv.gz") {
                 barcodes_files.push(file_path.clone());
             } else {
                 warn!("Skipping barcodes file with unknown extension: {file_path:?}")
@@ -83,25 +132,53 @@ pub fn plot(args: &cli::plot::Args) -> Result<(), Report> {
 
     for barcodes_file in barcodes_files {
         info!("Plotting barcodes file: {:?}", barcodes_file);
-        let output_prefix = barcodes_file
-            .file_stem()
-            .expect("Failed to get file stem of {barcodes_file:?}")
-            .to_str()
-            .expect("Failed to convert file of stem {barcodes_file:?} to str.");
-        let output_path = output_dir.join(format!("{}.png", output_prefix));
-        let result = create(
+        let output_prefix = utils::path_to_stem(&barcodes_file)?;
+
+        // optionally split wide barcodes into multiple plots, by site ranges
+        let num_sites = num_sites(
             &barcodes_file,
-            linelist,
-            args.annotations.as_deref(),
-            &output_path,
             args.all_coords,
-        );
-        match result {
-            Ok(()) => (),
-            Err(e) => {
-                if e.to_string().contains("not found in the linelist") {
+            args.hide_private,
+            args.private_only,
+        )?;
+        if num_sites == 0 && args.private_only {
+            warn!("No private sites to plot in barcodes file: {barcodes_file:?}");
+            continue;
+        }
+        let max_sites = args.max_sites_per_plot.unwrap_or(num_sites).max(1);
+        let mut parts = (0..num_sites)
+            .step_by(max_sites)
+            .map(|start| start..(start + max_sites).min(num_sites))
+            .collect_vec();
+        if parts.is_empty() {
+            parts.push(0..0);
+        }
+
+        for (part_i, sites) in parts.iter().enumerate() {
+            let output_path = if parts.len() > 1 {
+                output_dir.join(format!("{output_prefix}.part{}.png", part_i + 1))
+            } else {
+                output_dir.join(format!("{output_prefix}.png"))
+            };
+            let sites = (parts.len() > 1).then_some(sites.clone());
+            let result = create(
+                &barcodes_file,
+                linelist,
+                annotations.as_deref(),
+                &output_path,
+                args.all_coords,
+                args.hide_private,
+                args.private_only,
+                args.region_details,
+                args.cluster_samples,
+                sites,
+            );
+            match result {
+                Ok(()) => (),
+                Err(e) if e.to_string().contains("not found in the linelist") => {
                     warn!("The following error was encountered but ignored: {:?}", e);
                 }
+                Err(e) => return Err(e),
             }
         }
     }
@@ -110,13 +187,335 @@ pub fn plot(args: &cli::plot::Args) -> Result<(), Report> {
     Ok(())
 }
 
-#[allow(unused_variables)]
+/// Count the number of sites (coordinates) that will be drawn from a barcodes file.
+pub fn num_sites(
+    barcodes_path: &Path,
+    all_coords: bool,
+    hide_private: bool,
+    private_only: bool,
+) -> Result<usize, Report> {
+    let barcodes = Table::read(barcodes_path)?;
+    let num_sites =
+        select_sites(&barcodes, all_coords, hide_private, private_only)?.len();
+    Ok(num_sites)
+}
+
+/// Assign overlapping intervals (start, end) to separate rows, for stacking.
+///
+/// Intervals are greedily placed in the first row where they don't overlap,
+/// in order of start coordinate. Returns the row of each interval, in input order.
+pub fn stack_intervals(intervals: &[(usize, usize)]) -> Vec<usize> {
+    let mut rows = vec![0; intervals.len()];
+    // the end coordinate of the last interval placed in each row
+    let mut row_ends: Vec<usize> = Vec::new();
+
+    let order = (0..intervals.len()).sorted_by_key(|i| intervals[*i]).collect_vec();
+    for i in order {
+        let (start, end) = intervals[i];
+        let row = match row_ends.iter().position(|row_end| *row_end < start) {
+            Some(row) => row,
+            None => {
+                row_ends.push(0);
+                row_ends.len() - 1
+            }
+        };
+        row_ends[row] = end;
+        rows[i] = row;
+    }
+
+    rows
+}
+
+/// Get the parents (origins) in a barcodes table, excluding unknown and private.
+pub fn get_parents(barcodes: &Table) -> Result<Vec<String>, Report> {
+    let origin_i = barcodes.header_position("origin")?;
+    let parents = barcodes
+        .rows
+        .iter()
+        .filter(|row| row[origin_i] != "?" && row[origin_i] != "private")
+        .map(|row| row[origin_i].to_string())
+        .unique()
+        .collect_vec();
+    Ok(parents)
+}
+
+/// Select the rows (sites) of a barcodes table to draw.
+///
+/// A private site has an origin of 'private', or a sequence base that differs
+/// from the reference and all parents (ignoring missing data). Private sites
+/// are omitted with `hide_private`, or exclusively drawn with `private_only`.
+pub fn select_sites(
+    barcodes: &Table,
+    all_coords: bool,
+    hide_private: bool,
+    private_only: bool,
+) -> Result<Vec<usize>, Report> {
+    let origin_i = barcodes.header_position("origin")?;
+    let reference_i = barcodes.header_position("Reference")?;
+    let parents = get_parents(barcodes)?;
+    let parents_i = parents
+        .iter()
+        .map(|p| barcodes.header_position(p))
+        .collect::<Result<Vec<_>, _>>()?;
+    // sequences are the columns after coord, origin, Reference, and parents
+    let num_fixed = 3 + parents.len();
+
+    let sites = barcodes
+        .rows
+        .iter()
+        .enumerate()
+        .filter(|(_i, row)| {
+            let is_private_origin = row[origin_i] == "private";
+            let is_private = is_private_origin
+                || row.iter().skip(num_fixed).any(|base| {
+                    base != "N"
+                        && *base != row[reference_i]
+                        && parents_i.iter().all(|i| row[*i] != *base)
+                });
+            if private_only {
+                is_private
+            } else if hide_private {
+                !is_private
+            } else {
+                all_coords || !is_private_origin
+            }
+        })
+        .map(|(i, _row)| i)
+        .collect_vec();
+
+    Ok(sites)
+}
+
+/// Self-contained input for plotting, exported by 'rebar run --plot-bundle'.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Bundle {
+    pub dataset_name: String,
+    pub dataset_tag: String,
+    pub genome_length: usize,
+    pub linelist: Table,
+    /// Barcodes tables, by recombinant unique_key.
+    pub barcodes: BTreeMap<String, Table>,
+    pub annotations: Table,
+}
+
+impl Bundle {
+    /// Read bundle from file.
+    pub fn read(path: &Path) -> Result<Bundle, Report> {
+        let bundle = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read file: {path:?}."))?;
+        let bundle = serde_json::from_str(&bundle)
+            .wrap_err_with(|| format!("Failed to parse file: {path:?}"))?;
+        Ok(bundle)
+    }
+
+    /// Write bundle to file.
+    pub fn write(&self, path: &Path) -> Result<(), Report> {
+        let mut file = File::create(path)
+            .wrap_err_with(|| format!("Failed to create file: {path:?}"))?;
+        let output = serde_json::to_string(self)
+            .wrap_err_with(|| format!("Failed to parse plot bundle: {path:?}"))?;
+        file.write_all(format!("{output}\n").as_bytes())
+            .wrap_err_with(|| format!("Failed to write file: {path:?}"))?;
+        Ok(())
+    }
+
+    /// Unpack the bundle into a run directory layout (linelist, barcodes, annotations).
+    pub fn unpack(&self, dir: &Path) -> Result<(), Report> {
+        self.linelist.write(&dir.join("linelist.tsv"))?;
+
+        let barcodes_dir = dir.join("barcodes");
+        create_dir_all(&barcodes_dir)?;
+        for (unique_key, barcodes) in &self.barcodes {
+            barcodes.write(&barcodes_dir.join(format!("{unique_key}.tsv")))?;
+        }
+
+        if !self.annotations.headers.is_empty() {
+            self.annotations.write(&dir.join("annotations.tsv"))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Check that a linelist comes from a single run, and is compatible with a dataset.
+///
+/// Mixing runs (or datasets) produces plots with the wrong coordinates.
+pub fn check_linelist(
+    linelist_path: &Path,
+    dataset_dir: Option<&Path>,
+) -> Result<(), Report> {
+    let linelist = Table::read(linelist_path)?;
+
+    // every row must share the same value in these columns
+    let mut values = BTreeMap::new();
+    for column in ["genome_length", "dataset_name", "dataset_tag"] {
+        let column_i = linelist.header_position(column)?;
+        let column_values =
+            linelist.rows.iter().map(|row| row[column_i].clone()).unique().collect_vec();
+        if column_values.len() > 1 {
+            return Err(eyre!(
+                "The linelist contains multiple values of {column}: {}",
+                column_values.join(", ")
+            )
+            .suggestion(format!(
+                "Was the linelist ({linelist_path:?}) combined from different runs?"
+            )));
+        }
+        if let Some(value) = column_values.into_iter().next() {
+            values.insert(column, value);
+        }
+    }
+
+    let Some(dataset_dir) = dataset_dir else {
+        return Ok(());
+    };
+    let suggestion = format!(
+        "Are you sure the --run-dir was created with --dataset-dir {dataset_dir:?}?"
+    );
+
+    // dataset name and tag, custom datasets might not have a summary
+    let summary_path = dataset_dir.join("summary.json");
+    if summary_path.exists() {
+        let summary = Summary::read(&summary_path)?;
+        for (column, expected) in [
+            ("dataset_name", summary.name.to_string()),
+            ("dataset_tag", summary.tag.to_string()),
+        ] {
+            if let Some(observed) = values.get(column) {
+                if *observed != expected {
+                    return Err(eyre!(
+                        "The linelist {column} ({observed}) does not match the dataset ({expected})."
+                    )
+                    .suggestion(suggestion));
+                }
+            }
+        }
+    } else {
+        warn!("No summary was found, skipping the dataset name and tag check: {summary_path:?}");
+    }
+
+    // genome length, from the dataset reference
+    let reference_path = utils::find_compressed(&dataset_dir.join("reference.fasta"));
+    let reference = read_reference(&reference_path, &vec![0, 0], None)?;
+    if let Some(observed) = values.get("genome_length") {
+        let expected = reference.genome_length.to_string();
+        if *observed != expected {
+            return Err(eyre!(
+                "The linelist genome_length ({observed}) does not match the dataset reference ({expected})."
+            )
+            .suggestion(suggestion));
+        }
+    }
+
+    Ok(())
+}
+
+/// Shift 0-based barcodes and linelist coordinates (--coordinate-base 0) back to 1-based.
+///
+/// Shifts the barcodes 'coord' column, and the linelist 'breakpoints' and
+/// 'regions' columns, which are the coordinates used for plotting.
+pub fn unshift_coords(barcodes: &mut Table, linelist: &mut Table) -> Result<(), Report> {
+    let coord_i = barcodes.header_position("coord")?;
+    for row in barcodes.rows.iter_mut() {
+        let coord = row[coord_i].parse::<usize>().wrap_err_with(|| {
+            format!("Unable to parse coordinate: {:?}", row[coord_i])
+        })?;
+        row[coord_i] = (coord + 1).to_string();
+    }
+
+    // start-end, optionally followed by |parent
+    let unshift_interval = |interval: &str| -> Result<String, Report> {
+        let (coords, parent) = match interval.split_once('|') {
+            Some((coords, parent)) => (coords, Some(parent)),
+            None => (interval, None),
+        };
+        let (start, end) = coords
+            .split_once('-')
+            .ok_or_else(|| eyre!("Unable to parse interval: {interval:?}"))?;
+        let (start, end) = (start.parse::<usize>()? + 1, end.parse::<usize>()? + 1);
+        let interval = match parent {
+            Some(parent) => format!("{start}-{end}|{parent}"),
+            None => format!("{start}-{end}"),
+        };
+        Ok(interval)
+    };
+    for column in ["breakpoints", "regions"] {
+        let column_i = linelist.header_position(column)?;
+        for row in linelist.rows.iter_mut() {
+            if row[column_i].is_empty() {
+                continue;
+            }
+            row[column_i] = row[column_i]
+                .split(',')
+                .map(unshift_interval)
+                .collect::<Result<Vec<_>, Report>>()?
+                .join(",");
+        }
+    }
+
+    Ok(())
+}
+
+/// Order samples so that those with similar barcodes are adjacent.
+///
+/// Each sample is summarized as a profile of which parent its base matches at
+/// every site ('?' if none or multiple). Samples are sorted by the site of
+/// their first breakpoint (where the matching parent first changes), then by
+/// their profile, so that samples sharing breakpoints and sites are grouped.
+pub fn order_samples(
+    barcodes: &Table,
+    parents: &[String],
+    sequence_ids: &[String],
+) -> Result<Vec<String>, Report> {
+    let parents_i = parents
+        .iter()
+        .map(|p| barcodes.header_position(p))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut profiles = Vec::new();
+    for sequence_id in sequence_ids {
+        let sequence_i = barcodes.header_position(sequence_id)?;
+        let profile = barcodes
+            .rows
+            .iter()
+            .map(|row| {
+                let base = &row[sequence_i];
+                let matches =
+                    parents_i.iter().positions(|i| row[*i] == *base).collect_vec();
+                match matches[..] {
+                    [parent] => char::from_digit(parent as u32, 36).unwrap_or('?'),
+                    _ => '?',
+                }
+            })
+            .collect::<String>();
+
+        // first site where the matching parent changes
+        let breakpoint = profile
+            .chars()
+            .filter(|c| *c != '?')
+            .tuple_windows()
+            .position(|(prev, next)| prev != next)
+            .unwrap_or(usize::MAX);
+
+        profiles.push((breakpoint, profile, sequence_id.clone()));
+    }
+
+    let order = profiles.into_iter().sorted().map(|(_b, _p, id)| id).collect_vec();
+    Ok(order)
+}
+
+#[allow(unused_variables, clippy::too_many_arguments)]
 pub fn create(
     barcodes_path: &Path,
     linelist_path: &Path,
     annotations_path: Option<&Path>,
     output_path: &Path,
     all_coords: bool,
+    hide_private: bool,
+    private_only: bool,
+    region_details: bool,
+    cluster_samples: bool,
+    sites: Option<std::ops::Range<usize>>,
 ) -> Result<(), Report> {
     // ------------------------------------------------------------------------
     // Import Data
@@ -124,12 +523,23 @@ pub fn create(
 
     let mut barcodes = Table::read(barcodes_path)?;
 
-    let unique_key = barcodes_path.file_stem().unwrap().to_str().unwrap();
+    let unique_key = utils::path_to_stem(barcodes_path)?;
 
     // filter the linelist to the current key
-    let mut linelist = Table::read(linelist_path)?;
+    let all_linelist = Table::read(linelist_path)?;
+    let mut linelist = all_linelist.clone();
     //linelist = linelist.filter("unique_key", unique_key)?;
-    linelist = linelist.filter("unique_key", unique_key)?;
+    linelist = linelist.filter("unique_key", &unique_key)?;
+    // per-sample barcodes (--barcodes-granularity sample) are named by strain
+    if linelist.rows.is_empty() {
+        let strain_i = all_linelist.header_position("strain")?;
+        linelist.rows = all_linelist
+            .rows
+            .iter()
+            .filter(|row| row[strain_i].replace('/', "_") == unique_key)
+            .cloned()
+            .collect_vec();
+    }
     if linelist.rows.is_empty() {
         return Err(
             eyre!("The barcodes unique key ({unique_key}) was not found in the linelist: {linelist_path:?}")
@@ -138,12 +548,31 @@ pub fn create(
         );
     }
 
+    // runs with --coordinate-base 0 are plotted in 1-based coordinates
+    let is_zero_based = linelist
+        .header_position("coordinate_base")
+        .is_ok_and(|i| linelist.rows.iter().any(|row| row[i] == "0"));
+    if is_zero_based {
+        unshift_coords(&mut barcodes, &mut linelist)?;
+    }
+
     // optional import data
     let mut annotations = Table::new();
     if let Some(annotations_path) = annotations_path {
         annotations = Table::read(annotations_path)?
     }
 
+    // annotations can be grouped into tracks (ex. genes, domains) by the optional
+    // 'track' column, each track is drawn as its own band in order of appearance
+    let track_i = annotations.header_position("track").ok();
+    let annot_tracks = annotations
+        .rows
+        .iter()
+        .map(|row| track_i.map(|i| row[i].clone()).unwrap_or_default())
+        .unique()
+        .collect_vec();
+    let num_annot_tracks = annot_tracks.len().max(1);
+
     // check for mandatory columns and header pos
     let genome_length_i = linelist.header_position("genome_length")?;
     let breakpoints_i = linelist.header_position("breakpoints")?;
@@ -161,22 +590,47 @@ pub fn create(
         .unwrap()
         .parse::<usize>()?;
 
+    // barcodes coordinates must fit within the genome of the linelist
+    let max_coord = barcodes
+        .rows
+        .iter()
+        .filter_map(|row| row[coord_i].parse::<usize>().ok())
+        .max()
+        .unwrap_or_default();
+    if max_coord > genome_length {
+        return Err(eyre!(
+            "The barcodes coordinate {max_coord} exceeds the linelist genome_length ({genome_length})."
+        )
+        .suggestion(format!(
+            "Are you sure the barcodes file ({barcodes_path:?}) corresponds to the linelist?"
+        )));
+    }
+
+    // get parents (origins column), exclude 'private' as name
+    let parents = get_parents(&barcodes)?;
+
     // check if we should include/exclude private mutations
-    if !all_coords {
-        barcodes.rows.retain(|row| row[origin_i] != "private");
+    let selected = select_sites(&barcodes, all_coords, hide_private, private_only)?;
+    barcodes.rows = selected.into_iter().map(|i| barcodes.rows[i].clone()).collect_vec();
+
+    // optionally, restrict to a range of sites (after parents, to keep colors)
+    let is_part = sites.is_some();
+    if let Some(sites) = sites {
+        barcodes.rows = barcodes.rows[sites].to_vec();
     }
 
     // get coords
     let coords = barcodes.rows.iter().map(|row| &row[coord_i]).unique().collect_vec();
-
-    // get parents (origins column), exclude 'private' as name
-    let parents = barcodes
-        .rows
+    let coords_numeric = coords
         .iter()
-        .filter(|row| row[origin_i] != "?" && row[origin_i] != "private")
-        .map(|row| row[origin_i].to_string())
-        .unique()
-        .collect_vec();
+        .map(|coord| coord.parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // the genomic span of a part, regions and breakpoints are clipped to it
+    let part_span = match (coords_numeric.first(), coords_numeric.last()) {
+        (Some(first), Some(last)) if is_part => Some((*first, *last)),
+        _ => None,
+    };
 
     // If multiple parents weren't confidently identified
     if parents.is_empty() {
@@ -197,8 +651,12 @@ pub fn create(
     }
 
     // get sequence ids (columns after mandatory cols and parents)
-    let sequence_ids =
+    let mut sequence_ids =
         barcodes.headers.iter().skip(3 + parents.len()).cloned().collect_vec();
+    // optionally, place samples with similar barcodes next to each other
+    if cluster_samples {
+        sequence_ids = order_samples(&barcodes, &parents, &sequence_ids)?;
+    }
 
     // search for sequence_ids in the linelist
     let strain_i = linelist.header_position("strain")?;
@@ -233,6 +691,7 @@ pub fn create(
     // longest sequence text id (in pixels)
     let mut default_ids =
         vec!["Reference", "Private"].into_iter().map(String::from).collect_vec();
+    default_ids.extend(annot_tracks.iter().skip(1).cloned());
     let mut sequence_ids_length_check = sequence_ids.clone();
     sequence_ids_length_check.append(&mut default_ids);
 
@@ -321,7 +780,7 @@ pub fn create(
 
     let canvas_height = constants::X_INC             // white-space top
         + (constants::X_INC * 2.) + section_gap          // parent regions and text labels
-        + (constants::X_INC * 2.) + section_gap          // annotations
+        + (constants::X_INC * 2. * num_annot_tracks as f32) + section_gap // annotations
         + constants::X_INC  + section_gap                // guide section
         + constants::X_INC                               // reference bases
         + (constants::X_INC * parents.len() as f32)      // parent bases
@@ -397,20 +856,52 @@ pub fn create(
     let regions =
         &linelist.rows.iter().map(|row| row[regions_i].to_string()).next().unwrap();
     // 0-1000|parent1,1000-2000|parent2;
-    let regions_split = regions.split(',').collect_vec();
-
-    for (region_i, region) in regions_split.iter().enumerate() {
+    let mut regions_split = Vec::new();
+    for region in regions.split(',') {
         // 0-1000|parent1
         let region_parts = region.split('|').collect_vec();
         let parent = region_parts[1];
         let regions_coords = region_parts[0].split('-').collect_vec();
+        let region_start = regions_coords[0].parse::<usize>()?;
+        let region_end = regions_coords[1].parse::<usize>()?;
+        regions_split.push((region_start, region_end, parent));
+    }
+    // in a part, only draw the regions within its span
+    if let Some((part_start, part_end)) = part_span {
+        regions_split = regions_split
+            .into_iter()
+            .filter(|(start, end, _parent)| *start <= part_end && *end >= part_start)
+            .map(|(start, end, parent)| {
+                (start.max(part_start), end.min(part_end), parent)
+            })
+            .collect_vec();
+    }
 
-        let mut region_start = regions_coords[0].parse::<usize>()?;
-        if region_i == 0 {
+    for (region_i, (mut region_start, mut region_end, parent)) in
+        regions_split.iter().copied().enumerate()
+    {
+        // optional label details: span and number of supporting substitutions
+        let label = if region_details {
+            let num_subs = barcodes
+                .rows
+                .iter()
+                .filter(|row| row[origin_i] == parent)
+                .filter_map(|row| row[coord_i].parse::<usize>().ok())
+                .filter(|coord| *coord >= region_start && *coord <= region_end)
+                .count();
+            format!(
+                "{parent} ({num_subs} subs, {}-{})",
+                format_coord(region_start),
+                format_coord(region_end)
+            )
+        } else {
+            parent.to_string()
+        };
+
+        if region_i == 0 && part_span.is_none() {
             region_start = 0;
         }
-        let mut region_end = regions_coords[1].parse::<usize>()?;
-        if region_i == regions_split.len() - 1 {
+        if region_i == regions_split.len() - 1 && part_span.is_none() {
             region_end = genome_length;
         }
 
@@ -424,7 +915,7 @@ pub fn create(
 
         // region text label
         let mut args = text::DrawRaqoteArgs::from_canvas(&mut canvas);
-        args.text = parent.to_string();
+        args.text = label;
         args.font_size = constants::FONT_SIZE - 5.0;
         args.x = box_x + (box_w / 2.0);
         args.y = section_y;
@@ -514,66 +1005,132 @@ pub fn create(
         .flat_map(|(dark, light)| vec![*dark, *light])
         .collect_vec();
 
-    for (i, row) in annotations.rows.iter().enumerate() {
-        let abbrev_i = annotations.header_position("abbreviation")?;
-        let start_i = annotations.header_position("start")?;
-        let end_i = annotations.header_position("end")?;
+    let (start_i, end_i) = if annotations.rows.is_empty() {
+        (0, 0)
+    } else {
+        (
+            annotations.header_position("start")?,
+            annotations.header_position("end")?,
+        )
+    };
+    let intervals = annotations
+        .rows
+        .iter()
+        .map(|row| Ok((row[start_i].parse::<usize>()?, row[end_i].parse::<usize>()?)))
+        .collect::<Result<Vec<_>, Report>>()?;
+    // strand is optional, reverse strand annotations are labelled
+    let strand_i = annotations.header_position("strand").ok();
+
+    for (track_num, track) in annot_tracks.iter().enumerate() {
+        let track_y = section_y + (constants::X_INC * 2. * track_num as f32);
 
-        let abbreviation = &annotations.rows[i][abbrev_i];
-        let start = annotations.rows[i][start_i].parse::<usize>()?;
-        let end = annotations.rows[i][end_i].parse::<usize>()?;
+        // the first track shares the genome guide, additional tracks get their own
+        if track_num > 0 {
+            let mut args = text::DrawRaqoteArgs::from_canvas(&mut canvas);
+            args.text = track.to_string();
+            args.font_style = text::FontStyle::Bold;
+            args.x = section_x - label_gap;
+            args.y = track_y + (constants::X_INC * 1.5);
+            args.horizontal_alignment = text::HorizontalAlignment::Right;
+            args.vertical_alignment = text::VerticalAlignment::Center;
+            text::draw_raqote(&mut args)?;
 
-        // use colors from the color palette that are not reserved for pops
-        let mut color_i = i;
-        if color_i >= annot_palette.len() {
-            color_i -= annot_palette.len();
+            let box_w = num_coords as f32 * constants::X_INC;
+            let box_y = track_y + constants::X_INC;
+            let draw_x = vec![section_x, section_x, section_x + box_w, section_x + box_w];
+            let draw_y = vec![
+                box_y,
+                box_y + constants::X_INC,
+                box_y + constants::X_INC,
+                box_y,
+            ];
+            polygon::draw_raqote(
+                &mut canvas,
+                &draw_x,
+                &draw_y,
+                &constants::GREY,
+                &constants::TRANSPARENT,
+                &constants::BASIC_STROKE_STYLE,
+            )?;
         }
-        let [r, g, b, a] = annot_palette[color_i];
-        let color = Source::Solid(SolidSource { r, g, b, a });
-        // draw the region box, leave X_INC gap at top for annotation labels
-        // convert genomic coordinates to pixel coordinates
-        let box_x = section_x + (start as f32 * pixels_per_base);
-        let box_y = section_y + constants::X_INC;
-        let box_w = (end - start) as f32 * pixels_per_base;
-        let box_h = constants::X_INC;
-        let draw_x = vec![box_x, box_x, box_x + box_w, box_x + box_w];
-        let draw_y = vec![box_y, box_y + box_h, box_y + box_h, box_y];
 
-        polygon::draw_raqote(
-            &mut canvas,
-            &draw_x,
-            &draw_y,
-            &color,
-            &constants::TRANSPARENT,
-            &constants::BASIC_STROKE_STYLE,
-        )?;
+        // annotations (row indices) in this track
+        let track_rows = annotations
+            .rows
+            .iter()
+            .enumerate()
+            .filter_map(|(i, row)| {
+                let row_track = track_i.map(|t| &row[t]);
+                (row_track.unwrap_or(&String::new()) == track).then_some(i)
+            })
+            .collect_vec();
+
+        // stack overlapping annotations (ex. ORF9b inside N) on separate sub-rows
+        let track_intervals = track_rows.iter().map(|i| intervals[*i]).collect_vec();
+        let annot_rows = stack_intervals(&track_intervals);
+        let num_annot_rows = annot_rows.iter().max().map(|r| r + 1).unwrap_or(1);
+
+        for (track_row_i, i) in track_rows.iter().enumerate() {
+            let i = *i;
+            let row = &annotations.rows[i];
+            let abbrev_i = annotations.header_position("abbreviation")?;
+
+            let mut abbreviation = row[abbrev_i].clone();
+            if strand_i.is_some_and(|strand_i| row[strand_i] == "-") {
+                abbreviation = format!("{abbreviation} (-)");
+            }
+            let (start, end) = intervals[i];
+
+            // use colors from the color palette that are not reserved for pops
+            let color_i = i % annot_palette.len();
+            let [r, g, b, a] = annot_palette[color_i];
+            let color = Source::Solid(SolidSource { r, g, b, a });
+            // draw the region box, leave X_INC gap at top for annotation labels
+            // convert genomic coordinates to pixel coordinates
+            let box_h = constants::X_INC / num_annot_rows as f32;
+            let box_x = section_x + (start as f32 * pixels_per_base);
+            let box_y =
+                track_y + constants::X_INC + (box_h * annot_rows[track_row_i] as f32);
+            let box_w = end.saturating_sub(start) as f32 * pixels_per_base;
+            let draw_x = vec![box_x, box_x, box_x + box_w, box_x + box_w];
+            let draw_y = vec![box_y, box_y + box_h, box_y + box_h, box_y];
 
-        // text label
-        let mut args = text::DrawRaqoteArgs::from_canvas(&mut canvas);
-        args.text = abbreviation.to_string();
-        args.font_style = text::FontStyle::Regular;
-        args.font_size = constants::FONT_SIZE - 5.0;
-        args.x = box_x + (box_w / 2.0);
-        args.y = if let 0 = i % 2 {
-            section_y
-        } else {
-            section_y - (constants::X_INC / 2.)
-        };
-        args.horizontal_alignment = text::HorizontalAlignment::Center;
-        args.vertical_alignment = text::VerticalAlignment::Top;
-        let image = text::draw_raqote(&mut args)?;
+            polygon::draw_raqote(
+                &mut canvas,
+                &draw_x,
+                &draw_y,
+                &color,
+                &constants::TRANSPARENT,
+                &constants::BASIC_STROKE_STYLE,
+            )?;
 
-        // text line
-        let draw_x = vec![box_x + (box_w / 2.), box_x + (box_w / 2.)];
-        let draw_y = vec![box_y, args.y + image.height() as f32];
-        polygon::draw_raqote(
-            &mut canvas,
-            &draw_x,
-            &draw_y,
-            &constants::TRANSPARENT,
-            &constants::BLACK,
-            &constants::BASIC_STROKE_STYLE,
-        )?;
+            // text label
+            let mut args = text::DrawRaqoteArgs::from_canvas(&mut canvas);
+            args.text = abbreviation.to_string();
+            args.font_style = text::FontStyle::Regular;
+            args.font_size = constants::FONT_SIZE - 5.0;
+            args.x = box_x + (box_w / 2.0);
+            args.y = if let 0 = track_row_i % 2 {
+                track_y
+            } else {
+                track_y - (constants::X_INC / 2.)
+            };
+            args.horizontal_alignment = text::HorizontalAlignment::Center;
+            args.vertical_alignment = text::VerticalAlignment::Top;
+            let image = text::draw_raqote(&mut args)?;
+
+            // text line
+            let draw_x = vec![box_x + (box_w / 2.), box_x + (box_w / 2.)];
+            let draw_y = vec![box_y, args.y + image.height() as f32];
+            polygon::draw_raqote(
+                &mut canvas,
+                &draw_x,
+                &draw_y,
+                &constants::TRANSPARENT,
+                &constants::BLACK,
+                &constants::BASIC_STROKE_STYLE,
+            )?;
+        }
     }
 
     // ------------------------------------------------------------------------
@@ -601,7 +1158,7 @@ pub fn create(
         )?;
     }
 
-    section_y += constants::X_INC * 2.;
+    section_y += constants::X_INC * 2. * num_annot_tracks as f32;
 
     // ------------------------------------------------------------------------
     // Guide to Sub Polyons
@@ -699,12 +1256,24 @@ pub fn create(
     args.vertical_alignment = text::VerticalAlignment::Center;
     text::draw_raqote(&mut args)?;
 
-    let breakpoints = linelist
+    let mut breakpoints = linelist
         .rows
         .iter()
         .flat_map(|row| row[breakpoints_i].split(','))
+        .filter(|breakpoint| !breakpoint.is_empty())
         .unique()
-        .collect_vec();
+        .map(Breakpoint::from_str)
+        .collect::<Result<Vec<_>, Report>>()?;
+
+    // in a part, only draw the breakpoints between two of its coordinates
+    if let Some((part_start, part_end)) = part_span {
+        breakpoints.retain(|breakpoint| {
+            part_start < breakpoint.start
+                && (part_end > breakpoint.end
+                    || (breakpoint.start == breakpoint.end
+                        && part_end >= breakpoint.start))
+        });
+    }
 
     let dash_stroke_style: StrokeStyle = StrokeStyle {
         cap: LineCap::Square,
@@ -716,26 +1285,20 @@ pub fn create(
     };
 
     for (i, breakpoint) in breakpoints.iter().enumerate() {
-        // get the region start/end
-        let breakpoint_parts = breakpoint.split('-').collect_vec();
-        // check if breakpoints is single coordinate
-        let mut prev_region_end = breakpoint_parts[0].parse::<f32>()?;
-        let mut next_region_start = breakpoint_parts[1].parse::<f32>()?;
-
-        if prev_region_end == next_region_start {
-            prev_region_end -= 1.0;
-        } else {
-            prev_region_end -= 1.0;
-            next_region_start += 1.0;
-        }
-        //println!("{prev_region_end} {next_region_start}");
-
-        // which subs does this fall between
-        let coord_prev_i =
-            coords.iter().position(|c| **c == prev_region_end.to_string()).unwrap();
-        let coord_next_i =
-            coords.iter().position(|c| **c == next_region_start.to_string()).unwrap();
-        //println!("\t{coord_prev_i} {coord_next_i}");
+        // which subs does this fall between, the previous region ends before
+        // the breakpoint start. When regions are adjacent, a single-coordinate
+        // breakpoint is also the start of the next region.
+        let coord_prev_i = coords_numeric
+            .iter()
+            .rposition(|c| *c < breakpoint.start)
+            .ok_or_else(|| eyre!("No coordinate found before breakpoint {breakpoint}"))?;
+        let coord_next_i = coords_numeric
+            .iter()
+            .position(|c| {
+                *c > breakpoint.end
+                    || (breakpoint.start == breakpoint.end && *c == breakpoint.start)
+            })
+            .ok_or_else(|| eyre!("No coordinate found after breakpoint {breakpoint}"))?;
 
         // middle will depend on breakpoints uncertainy
         let line_x;
@@ -885,7 +1448,7 @@ pub fn create(
             // pop label has same pos as section_label, use that function
             if coord_i == 0 {
                 let mut args = text::DrawRaqoteArgs::from_canvas(&mut canvas);
-                args.text = population.replace("population_", "").to_string();
+                args.text = strip_population_prefix(population).to_string();
                 args.x = section_x - label_gap;
                 args.y = y + (constants::X_INC / 2.0);
                 args.horizontal_alignment = text::HorizontalAlignment::Right;
@@ -1165,3 +1728,12 @@ pub fn get_base_rgba(base: &String, ref_base: &String, pal_i: usize) -> [u8; 4]
 
     rgba
 }
+
+/// Format a genomic coordinate for labels, abbreviating thousands (ex. 21k).
+fn format_coord(coord: usize) -> String {
+    if coord >= 1000 {
+        format!("{}k", coord / 1000)
+    } else {
+        coord.to_string()
+    }
+}
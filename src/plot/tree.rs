@@ -0,0 +1,217 @@
+use crate::cli;
+use crate::phylogeny::Phylogeny;
+use crate::plot::{constants, polygon, text};
+use crate::utils::exit;
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use itertools::Itertools;
+use log::info;
+use petgraph::graph::NodeIndex;
+use petgraph::Direction;
+use raqote::*;
+use std::collections::BTreeMap;
+
+// the figure is an overview, wide layers are squeezed to fit this width
+const MAX_WIDTH: f32 = 10_000.0;
+
+/// Plot the dataset phylogeny, highlighting recombinants.
+///
+/// Populations are laid out in layers by depth from the root. Depth is the
+/// longest path, so recombinants sit below all of their parents. Recombinants
+/// are drawn in black, with the edges to each of their parents colored.
+pub fn plot_tree(args: &cli::dataset::plot_tree::Args) -> Result<(), Report> {
+    let output = &args.output;
+    if output.extension().and_then(|ext| ext.to_str()) != Some("png") {
+        return Err(eyre!(exit::Code::Usage)
+            .wrap_err(format!("--output {output:?} must be a PNG (.png) file.")));
+    }
+
+    let phylogeny_path = args.dataset_dir.join("phylogeny.json");
+    let phylogeny = Phylogeny::read(&phylogeny_path)?;
+    if phylogeny.is_empty() {
+        return Err(eyre!("The dataset phylogeny is empty: {phylogeny_path:?}"));
+    }
+    let graph = &phylogeny.graph;
+
+    // ------------------------------------------------------------------------
+    // Layout
+    // ------------------------------------------------------------------------
+
+    // depth of each node, visiting parents before their children
+    let order = petgraph::algo::toposort(graph, None)
+        .map_err(|_| eyre!("The dataset phylogeny has a cycle: {phylogeny_path:?}"))?;
+    let mut depths: BTreeMap<NodeIndex, usize> = BTreeMap::new();
+    for node in &order {
+        let depth = graph
+            .neighbors_directed(*node, Direction::Incoming)
+            .filter_map(|parent| depths.get(&parent))
+            .map(|depth| depth + 1)
+            .max()
+            .unwrap_or(0);
+        depths.insert(*node, depth);
+    }
+
+    let num_layers = depths.values().max().unwrap_or(&0) + 1;
+    let mut layers: Vec<Vec<NodeIndex>> = vec![Vec::new(); num_layers];
+    depths.iter().for_each(|(node, depth)| layers[*depth].push(*node));
+    let max_layer_len = layers.iter().map(|layer| layer.len()).max().unwrap_or(1);
+
+    let node_spacing = (MAX_WIDTH / max_layer_len as f32).min(constants::X_INC);
+    let node_size = (node_spacing * 0.5).max(constants::LINE_WIDTH * 2.);
+    let layer_height = constants::X_INC * 3.;
+
+    let canvas_width = (constants::X_INC * 2. + max_layer_len as f32 * node_spacing)
+        .max(constants::X_INC * 12.);
+    let canvas_height = constants::X_INC * 3. + num_layers as f32 * layer_height;
+
+    // order each layer by the mean position of parents, so that children
+    // sit close to their parents, then by name
+    let mut positions: BTreeMap<NodeIndex, (f32, f32)> = BTreeMap::new();
+    for (depth, layer) in layers.iter().enumerate() {
+        let mean_parent_x = |node: NodeIndex| -> f32 {
+            let parents_x = graph
+                .neighbors_directed(node, Direction::Incoming)
+                .filter_map(|parent| positions.get(&parent))
+                .map(|(x, _y)| *x)
+                .collect_vec();
+            if parents_x.is_empty() {
+                0.0
+            } else {
+                parents_x.iter().sum::<f32>() / parents_x.len() as f32
+            }
+        };
+        let layer = layer
+            .iter()
+            .sorted_by(|a, b| {
+                mean_parent_x(**a)
+                    .total_cmp(&mean_parent_x(**b))
+                    .then(graph[**a].cmp(&graph[**b]))
+            })
+            .collect_vec();
+
+        // center the layer horizontally
+        let offset = (canvas_width - layer.len() as f32 * node_spacing) / 2.;
+        let y = constants::X_INC * 2. + depth as f32 * layer_height;
+        for (i, node) in layer.into_iter().enumerate() {
+            let x = offset + (i as f32 + 0.5) * node_spacing;
+            positions.insert(*node, (x, y));
+        }
+    }
+
+    // ------------------------------------------------------------------------
+    // Canvas
+    // ------------------------------------------------------------------------
+
+    let mut canvas = DrawTarget::new(canvas_width as i32, canvas_height as i32);
+
+    // draw white background
+    let mut background = PathBuilder::new();
+    background.rect(0., 0., canvas_width, canvas_height);
+    let background = background.finish();
+    canvas.fill(&background, &constants::WHITE, &DrawOptions::new());
+
+    // draw title
+    let mut text_args = text::DrawRaqoteArgs::from_canvas(&mut canvas);
+    text_args.text = format!(
+        "Phylogeny: {} populations, {} recombinants",
+        graph.node_count(),
+        phylogeny.recombinants.len()
+    );
+    text_args.font_style = text::FontStyle::Bold;
+    text_args.x = constants::X_INC;
+    text_args.y = constants::X_INC;
+    text_args.vertical_alignment = text::VerticalAlignment::Center;
+    text::draw_raqote(&mut text_args)?;
+
+    // ------------------------------------------------------------------------
+    // Edges
+    // ------------------------------------------------------------------------
+
+    // edges to recombinants are drawn last, so they are on top
+    let edges = graph
+        .edge_indices()
+        .filter_map(|edge| graph.edge_endpoints(edge))
+        .sorted_by_key(|(_parent, child)| phylogeny.recombinants.contains(&graph[*child]))
+        .collect_vec();
+
+    for (parent, child) in edges {
+        let (x1, y1) = positions[&parent];
+        let (x2, y2) = positions[&child];
+
+        // color the edges to each parent of a recombinant
+        let color = if phylogeny.recombinants.contains(&graph[child]) {
+            let parent_i = graph
+                .neighbors_directed(child, Direction::Incoming)
+                .sorted_by_key(|p| graph[*p].clone())
+                .position(|p| p == parent)
+                .unwrap_or_default();
+            let [r, g, b, a] =
+                constants::PALETTE_DARK[parent_i % constants::PALETTE_DARK.len()];
+            Source::Solid(SolidSource { r, g, b, a })
+        } else {
+            constants::DARK_GREY
+        };
+
+        let mut path = PathBuilder::new();
+        path.move_to(x1, y1);
+        path.line_to(x2, y2);
+        let path = path.finish();
+        canvas.stroke(
+            &path,
+            &color,
+            &constants::BASIC_STROKE_STYLE,
+            &DrawOptions::new(),
+        );
+    }
+
+    // ------------------------------------------------------------------------
+    // Nodes
+    // ------------------------------------------------------------------------
+
+    // label every node if there is room, otherwise just the recombinants
+    let label_all = node_spacing >= constants::FONT_SIZE;
+
+    for (node, (x, y)) in &positions {
+        let name = &graph[*node];
+        let is_recombinant = phylogeny.recombinants.contains(name);
+        let fill = if is_recombinant {
+            constants::BLACK
+        } else {
+            constants::DARK_GREY
+        };
+
+        let half = node_size / 2.;
+        let draw_x = [x - half, x - half, x + half, x + half];
+        let draw_y = [y - half, y + half, y + half, y - half];
+        polygon::draw_raqote(
+            &mut canvas,
+            &draw_x,
+            &draw_y,
+            &fill,
+            &constants::TRANSPARENT,
+            &constants::BASIC_STROKE_STYLE,
+        )?;
+
+        if label_all || is_recombinant {
+            let mut text_args = text::DrawRaqoteArgs::from_canvas(&mut canvas);
+            text_args.text = name.clone();
+            text_args.font_size = constants::FONT_SIZE - 10.;
+            text_args.x = *x;
+            text_args.y = y + half + constants::BUFFER;
+            text_args.rotate = 90;
+            text_args.horizontal_alignment = text::HorizontalAlignment::Center;
+            text_args.vertical_alignment = text::VerticalAlignment::Top;
+            text::draw_raqote(&mut text_args)?;
+        }
+    }
+
+    // ------------------------------------------------------------------------
+    // Export
+    // ------------------------------------------------------------------------
+
+    info!("Exporting phylogeny plot: {output:?}");
+    canvas
+        .write_png(output)
+        .wrap_err_with(|| format!("Failed to write file: {output:?}"))?;
+
+    Ok(())
+}
@@ -3,15 +3,18 @@ pub mod validate;
 
 use crate::cli::run;
 use crate::dataset::SearchResult;
+use crate::phylogeny::Phylogeny;
 use crate::sequence::{Sequence, Substitution};
 use crate::utils::table::Table;
-use color_eyre::eyre::{eyre, Report, Result};
+use clap::ValueEnum;
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
 use color_eyre::Help;
 use indoc::formatdoc;
 use itertools::Itertools;
 use log::debug;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::str::FromStr;
 use strum::{EnumIter, EnumProperty};
 
 // ----------------------------------------------------------------------------
@@ -29,14 +32,17 @@ pub struct Recombination<'seq> {
     pub parents: Vec<String>,
     pub breakpoints: Vec<Breakpoint>,
     pub regions: BTreeMap<usize, Region>,
-    pub genome_length: usize,
     pub edge_case: bool,
     pub hypothesis: Option<Hypothesis>,
     pub support: BTreeMap<String, Vec<Substitution>>,
     pub conflict_ref: BTreeMap<String, Vec<Substitution>>,
     pub conflict_alt: BTreeMap<String, Vec<Substitution>>,
     pub private: BTreeMap<String, Vec<Substitution>>,
+    pub unresolved: Vec<Substitution>,
     pub score: BTreeMap<String, isize>,
+    pub timed_out: bool,
+    /// The runner-up call (population or parents) and its score.
+    pub alternative: Option<(String, isize)>,
     #[serde(skip_serializing)]
     pub table: Table,
 }
@@ -51,14 +57,56 @@ impl<'seq> Recombination<'seq> {
             breakpoints: Vec::new(),
             regions: BTreeMap::new(),
             table: Table::new(),
-            genome_length: sequence.genome_length,
             edge_case: false,
             hypothesis: None,
             support: BTreeMap::new(),
             conflict_ref: BTreeMap::new(),
             conflict_alt: BTreeMap::new(),
             private: BTreeMap::new(),
+            unresolved: Vec::new(),
             score: BTreeMap::new(),
+            timed_out: false,
+            alternative: None,
+        }
+    }
+
+    /// Genome length of the owning sequence.
+    ///
+    /// Read from the sequence, rather than stored, so it can never be stale.
+    pub fn genome_length(&self) -> usize {
+        self.sequence.genome_length
+    }
+
+    /// Assign the recombination hypothesis supported by this result.
+    ///
+    /// 1. No recombination passed the filters: NonRecombinant.
+    /// 2. The consensus is a designated recombinant, and the detected parents
+    ///    match its parents in the phylogeny: DesignatedRecombinant.
+    /// 3. Any parent is a recombinant (or descendant of): RecursiveRecombinant.
+    /// 4. Otherwise: NonRecursiveRecombinant.
+    pub fn assign_hypothesis(&self, phylogeny: &Phylogeny) -> Hypothesis {
+        // the designated parents, in any order
+        let is_designated = |recombinant: &str| {
+            phylogeny.get_parents(recombinant).is_ok_and(|designated| {
+                designated.iter().sorted().eq(self.parents.iter().sorted())
+            })
+        };
+        match &self.recombinant {
+            None => Hypothesis::NonRecombinant,
+            Some(recombinant) if recombinant != "novel" && is_designated(recombinant) => {
+                Hypothesis::DesignatedRecombinant
+            }
+            Some(_) => {
+                // parents might not be in the phylogeny (ex. custom datasets)
+                let is_recursive = self.parents.iter().any(|parent| {
+                    phylogeny.get_recombinant_ancestor(parent).ok().flatten().is_some()
+                });
+                if is_recursive {
+                    Hypothesis::RecursiveRecombinant
+                } else {
+                    Hypothesis::NonRecursiveRecombinant
+                }
+            }
         }
     }
 
@@ -95,21 +143,41 @@ impl<'seq> Recombination<'seq> {
             private.push_str(&msg);
         });
 
+        // unresolved
+        let unresolved = format!(
+            "  - ({}): {}\n",
+            self.unresolved.len(),
+            self.unresolved.iter().join(", ")
+        );
+
         formatdoc!(
             "score:\n{score}
             support:\n{support}
             conflict_ref:\n{conflict_ref}
             conflict_alt:\n{conflict_alt}
             private:\n{private}
+            unresolved:\n{unresolved}
             "
         )
     }
 
+    /// Assign each substitution in the sequence to an origin.
+    ///
+    /// Origins are assigned with the following rules:
+    ///
+    /// 1. Parent: (recombinants only) subs supporting a parent are assigned to that parent.
+    /// 2. Best match: subs found in the consensus population of the best match.
+    ///    For recombinants, this only considers the private recombination subs,
+    ///    and is controlled by the [PrivatePolicy].
+    /// 3. Private: novel subs not found in any parent or the best match.
+    /// 4. Reversion: (optional) parental or best match subs which are
+    ///    absent in the sequence (ie. reference base), assigned to 'reversion'.
     pub fn get_substitution_origins(
         &self,
         best_match: &SearchResult,
+        policy: &PrivatePolicy,
+        reversions: bool,
     ) -> Result<BTreeMap<String, Vec<Substitution>>, Report> {
-        // todo!() think about if we want reversions in here or not...
         let mut subs_by_origin = BTreeMap::new();
 
         // recombination parents
@@ -123,24 +191,21 @@ impl<'seq> Recombination<'seq> {
         // consensus/best match and private
         let p = &best_match.consensus_population;
 
-        let support = best_match.support.get(p).cloned().unwrap_or_default();
-
         let (best_match_subs, private_subs) = if self.recombinant.is_some() {
             // Ex. XBB (XBB consensus, BJ.1 and BA.2.75 as parents)
-            // Ex. XBB --knockout XBB, (BJ.1 consensus, BJ.1 and BA.2.75 as parents)
-            //     A19326G comes from parent XBB
             // Ex. XBB.1 --knockout XBB.1, (XBB consensus, BJ.1 and BA.2.75 as parents)
-            //     A19326G comes from parent XBB
             //     G22317T will be private (XBB.1 specific)
-
-            // check the private recombination subs against best match
-            let mut private_subs =
+            let private_subs =
                 self.private.values().flatten().sorted().cloned().collect_vec();
-            let mut best_match_subs = private_subs.clone();
-            best_match_subs.retain(|s| support.contains(s));
-            // private subs are the remaining ones not found in best match
-            private_subs.retain(|s| !best_match_subs.contains(s));
-            (best_match_subs, private_subs)
+            match policy {
+                PrivatePolicy::BestMatch => {
+                    let support = best_match.support.get(p).cloned().unwrap_or_default();
+                    let (best_match_subs, private_subs): (Vec<_>, Vec<_>) =
+                        private_subs.into_iter().partition(|s| support.contains(s));
+                    (best_match_subs, private_subs)
+                }
+                PrivatePolicy::Private => (Vec::new(), private_subs),
+            }
         } else {
             let best_match_subs = best_match.support.get(p).cloned().unwrap_or_default();
             let private_subs = best_match.private.clone();
@@ -155,10 +220,54 @@ impl<'seq> Recombination<'seq> {
         // private
         subs_by_origin.insert("private".to_string(), private_subs);
 
+        // reversions, the expected alt bases which are reference in the sequence
+        if reversions {
+            let reversion_subs = if self.recombinant.is_some() {
+                self.parents
+                    .iter()
+                    .filter_map(|p| self.conflict_ref.get(p))
+                    .flatten()
+                    .unique()
+                    .sorted()
+                    .cloned()
+                    .collect_vec()
+            } else {
+                best_match.conflict_ref.get(p).cloned().unwrap_or_default()
+            };
+            subs_by_origin.insert("reversion".to_string(), reversion_subs);
+        }
+
         Ok(subs_by_origin)
     }
 }
 
+// ----------------------------------------------------------------------------
+// Private Policy
+
+/// How private recombination subs are assigned an origin.
+///
+/// Only applies to recombinants, non-recombinant private subs are always 'private'.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize, ValueEnum)]
+pub enum PrivatePolicy {
+    /// Private subs found in the consensus best match are assigned to the best match.
+    ///
+    /// Ex. XBB.1 (XBB consensus), subs shared with XBB are assigned to XBB.
+    #[default]
+    BestMatch,
+    /// All private subs, including those found in the best match, are 'private'.
+    Private,
+}
+
+impl std::fmt::Display for PrivatePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let policy = match self {
+            PrivatePolicy::BestMatch => "best-match",
+            PrivatePolicy::Private => "private",
+        };
+        write!(f, "{policy}")
+    }
+}
+
 // ----------------------------------------------------------------------------
 // Hypthoses
 
@@ -197,7 +306,9 @@ impl std::fmt::Display for Hypothesis {
 // Breakpoint
 
 /// Recombination breakpoint intervals (left and right inclusive)
-#[derive(Clone, Debug, Deserialize, Serialize)]
+///
+/// Ordered by start, then end.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct Breakpoint {
     pub start: usize,
     pub end: usize,
@@ -209,6 +320,35 @@ impl std::fmt::Display for Breakpoint {
     }
 }
 
+impl FromStr for Breakpoint {
+    type Err = Report;
+
+    /// Parse a breakpoint from either 'start-end' or a single coordinate 'start'.
+    fn from_str(text: &str) -> Result<Self, Report> {
+        let parts = text.split('-').map(|p| p.trim()).collect_vec();
+        let (start, end) = match parts.len() {
+            1 => (parts[0], parts[0]),
+            2 => (parts[0], parts[1]),
+            _ => return Err(eyre!("Unable to parse breakpoint: {text:?}")),
+        };
+
+        let start = start
+            .parse::<usize>()
+            .wrap_err_with(|| eyre!("Unable to parse breakpoint start: {text:?}"))?;
+        let end = end
+            .parse::<usize>()
+            .wrap_err_with(|| eyre!("Unable to parse breakpoint end: {text:?}"))?;
+
+        if end < start {
+            return Err(eyre!(
+                "Breakpoint end ({end}) is less than the start ({start}): {text:?}"
+            ));
+        }
+
+        Ok(Breakpoint { start, end })
+    }
+}
+
 // ----------------------------------------------------------------------------
 // Direction
 
@@ -221,7 +361,10 @@ pub enum Direction {
 // ----------------------------------------------------------------------------
 // Region
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+/// Parental region (left and right inclusive)
+///
+/// Ordered by start, then end, then origin.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 #[allow(dead_code)]
 pub struct Region {
     pub start: usize,
@@ -237,12 +380,37 @@ impl std::fmt::Display for Region {
     }
 }
 
+// ----------------------------------------------------------------------------
+// Subthreshold
+
+/// Parental regions that were rejected by the recombination filters.
+///
+/// Returned as the error of [detect_recombination] when a filter fails, so
+/// that near-misses can be reviewed (ex. with --report-subthreshold).
+#[derive(Clone, Debug, Default)]
+pub struct Subthreshold {
+    pub parents: Vec<String>,
+    pub regions: BTreeMap<usize, Region>,
+    pub reason: String,
+}
+
+impl std::fmt::Display for Subthreshold {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.reason)
+    }
+}
+
+impl std::error::Error for Subthreshold {}
+
 // ----------------------------------------------------------------------------
 // Functions
 // ----------------------------------------------------------------------------
 
 /// Detect recombination in a query sequence.
 ///
+/// If the parental regions fail the recombination filters, the error is a
+/// [Subthreshold] with the rejected regions and the reason why.
+///
 ///
 /// # Arguments
 ///
@@ -406,6 +574,7 @@ pub fn detect_recombination<'seq>(
 
     // First: 5' -> 3', filter separately on min_consecutive then min_length
     let mut regions_5p = identify_regions(&table_no_private)?;
+    regions_5p = bridge_regions(&regions_5p, args.consecutive_gap_tolerance)?;
     regions_5p = filter_regions(
         &regions_5p,
         Direction::Forward,
@@ -421,6 +590,7 @@ pub fn detect_recombination<'seq>(
 
     // Second: 3' -> 5', filter separately on min_consecutive then min_length
     let mut regions_3p = identify_regions(&table_no_private)?;
+    regions_3p = bridge_regions(&regions_3p, args.consecutive_gap_tolerance)?;
     regions_3p = filter_regions(
         &regions_3p,
         Direction::Reverse,
@@ -457,19 +627,26 @@ pub fn detect_recombination<'seq>(
         .unique()
         .collect_vec();
 
+    // if a filter fails, keep the rejected regions to explain the near-miss
+    let subthreshold = |reason: String| Subthreshold {
+        parents: parents.iter().map(|p| p.consensus_population.clone()).collect(),
+        regions: regions_intersect.clone(),
+        reason,
+    };
+
     for parent in &parents {
         if !region_origins.contains(&parent.consensus_population) {
-            return Err(eyre!(
+            return Err(Report::new(subthreshold(format!(
                 "No recombination detected for parent {}.",
                 &parent.consensus_population
-            ));
+            ))));
         }
     }
     if !region_origins.contains(&parent_candidate.consensus_population) {
-        return Err(eyre!(
+        return Err(Report::new(subthreshold(format!(
             "No recombination detected for parent {}.",
             &parent_candidate.consensus_population
-        ));
+        ))));
     }
 
     // --------------------------------------------------------------------
@@ -491,20 +668,22 @@ pub fn detect_recombination<'seq>(
     }
     debug!("uniq_subs_count: {uniq_subs_count:?}");
     // check if any parent failed the filter
-    let mut min_subs_fail = false;
+    let mut min_subs_fail = Vec::new();
     for (parent, count) in uniq_subs_count {
         if count < args.min_subs {
             debug!(
                 "Parent {} subs ({count}) do not meet the min_subs filter ({}).",
                 parent, args.min_subs,
             );
-            min_subs_fail = true;
+            min_subs_fail.push(format!("{parent} ({count})"));
         }
     }
-    if min_subs_fail {
-        return Err(eyre!(
-            "No recombination detected, min_subs filter was not satisfied by all parents."
-        ));
+    if !min_subs_fail.is_empty() {
+        return Err(Report::new(subthreshold(format!(
+            "No recombination detected, min_subs filter ({}) was not satisfied by parents: {}.",
+            args.min_subs,
+            min_subs_fail.join(", ")
+        ))));
     }
 
     // --------------------------------------------------------------------
@@ -517,6 +696,16 @@ pub fn detect_recombination<'seq>(
         serde_json::to_string(&breakpoints).unwrap()
     );
 
+    // too many breakpoints suggests a poor alignment or chimeric assembly
+    if let Some(max_breakpoints) = args.max_breakpoints {
+        if breakpoints.len() > max_breakpoints {
+            return Err(Report::new(subthreshold(format!(
+                "No recombination detected, max_breakpoints filter ({max_breakpoints}) was exceeded: {}.",
+                breakpoints.len()
+            ))));
+        }
+    }
+
     // --------------------------------------------------------------------
     // Update
     // --------------------------------------------------------------------
@@ -565,6 +754,59 @@ pub fn detect_recombination<'seq>(
     }
 
     // record the substitutions+reversions that are unresolved by any parent
+    // these are the 'private' rows of the table, where the sequence base
+    // matches none of the parents, that fall within the parental regions
+    for row in &recombination.table.rows {
+        if row[origin_col_i] != "private" {
+            continue;
+        }
+        let coord = row[coord_col_i].parse::<usize>()?;
+
+        // missing data and deletions are not evidence against a parent
+        if sequence.missing.contains(&coord)
+            || sequence.deletions.iter().any(|d| d.coord == coord)
+        {
+            continue;
+        }
+
+        let Some(region) = recombination
+            .regions
+            .values()
+            .find(|region| coord >= region.start && coord <= region.end)
+        else {
+            continue;
+        };
+
+        let ref_base = row[ref_col_i].chars().next().unwrap();
+        let seq_base = row[seq_col_i].chars().next().unwrap();
+        // novel substitutions are already reported as private to a parent
+        let is_private = recombination
+            .private
+            .values()
+            .flatten()
+            .any(|sub| sub.coord == coord && sub.alt == seq_base);
+        if seq_base != ref_base && is_private {
+            continue;
+        }
+
+        // reversion: sequence has the reference base, but the parent of the
+        // region does not
+        let parent_col_i = recombination.table.header_position(&region.origin)?;
+        let substitution = if seq_base == ref_base {
+            Substitution {
+                coord,
+                reference: row[parent_col_i].chars().next().unwrap(),
+                alt: seq_base,
+            }
+        } else {
+            Substitution {
+                coord,
+                reference: ref_base,
+                alt: seq_base,
+            }
+        };
+        recombination.unresolved.push(substitution);
+    }
 
     // convoluted debug message
     debug!(
@@ -575,6 +817,9 @@ pub fn detect_recombination<'seq>(
     Ok(recombination)
 }
 
+/// Identify regions of consecutive rows with the same origin in a table.
+///
+/// Regions are keyed by their start coordinate, and are disjoint.
 pub fn identify_regions(table: &Table) -> Result<BTreeMap<usize, Region>, Report> {
     let mut origin_prev: Option<String> = None;
     let mut regions = BTreeMap::new();
@@ -587,10 +832,18 @@ pub fn identify_regions(table: &Table) -> Result<BTreeMap<usize, Region>, Report
     let seq_col_i = table.headers.len() - 1;
 
     for row in table.rows.iter() {
-        let coord = row[coord_col_i].parse::<usize>().unwrap();
+        let coord = row[coord_col_i]
+            .parse::<usize>()
+            .wrap_err_with(|| format!("Unable to parse coordinate: {row:?}"))?;
         let origin = row[origin_col_i].to_string();
-        let reference = row[ref_col_i].chars().next().unwrap();
-        let alt = row[seq_col_i].chars().next().unwrap();
+        let base = |i: usize| {
+            row[i]
+                .chars()
+                .next()
+                .ok_or_else(|| eyre!("Missing base at coordinate {coord}: {row:?}"))
+        };
+        let reference = base(ref_col_i)?;
+        let alt = base(seq_col_i)?;
         let substitutions = vec![Substitution {
             coord,
             reference,
@@ -621,7 +874,55 @@ pub fn identify_regions(table: &Table) -> Result<BTreeMap<usize, Region>, Report
     Ok(regions)
 }
 
+/// Bridge short interruptions between regions of the same origin.
+///
+/// A region with `gap_tolerance` or fewer substitutions, flanked on both sides
+/// by regions of the same origin, is treated as noise (ex. homoplasy or
+/// sequencing error). The flanking regions are merged, and the interrupting
+/// substitutions are dropped, so they don't count towards min_consecutive.
+pub fn bridge_regions(
+    regions: &BTreeMap<usize, Region>,
+    gap_tolerance: usize,
+) -> Result<BTreeMap<usize, Region>, Report> {
+    if gap_tolerance == 0 {
+        return Ok(regions.clone());
+    }
+
+    let mut regions_bridge: Vec<Region> = Vec::new();
+
+    for region in regions.values() {
+        let num_regions = regions_bridge.len();
+        let is_bridge = num_regions >= 2 && {
+            let gap = &regions_bridge[num_regions - 1];
+            let prev = &regions_bridge[num_regions - 2];
+            gap.substitutions.len() <= gap_tolerance
+                && gap.origin != region.origin
+                && prev.origin == region.origin
+        };
+
+        if is_bridge {
+            let gap = regions_bridge.pop().unwrap();
+            debug!(
+                "Bridging region {gap} between regions of {}.",
+                region.origin
+            );
+            let prev = regions_bridge.last_mut().unwrap();
+            prev.end = region.end;
+            prev.substitutions.extend(region.substitutions.clone());
+        } else {
+            regions_bridge.push(region.to_owned());
+        }
+    }
+
+    let regions_bridge = regions_bridge.into_iter().map(|r| (r.start, r)).collect();
+    Ok(regions_bridge)
+}
+
 /// Filter recombinant regions based on the length and consecutive bases.
+///
+/// Regions that fail the filters are dropped, and neighboring regions of the
+/// same origin are merged. Regions are visited 5' -> 3' (Forward) or 3' -> 5'
+/// (Reverse), and filtering the output again has no further effect.
 pub fn filter_regions(
     regions: &BTreeMap<usize, Region>,
     direction: Direction,
@@ -688,11 +989,18 @@ pub fn filter_regions(
 }
 
 /// Find the intersect between two regions.
+///
+/// Regions of the same origin intersect at their shared substitutions. The
+/// result does not depend on the order of the arguments. Within each input,
+/// regions are expected to be disjoint, in which case the intersected regions
+/// are too. If they are not, intersected regions that start at the same
+/// coordinate are combined when they share an origin, and are an error if
+/// their origins conflict.
 pub fn intersect_regions(
     regions_1: &BTreeMap<usize, Region>,
     regions_2: &BTreeMap<usize, Region>,
 ) -> Result<BTreeMap<usize, Region>, Report> {
-    let mut regions_intersect = BTreeMap::new();
+    let mut regions_intersect: BTreeMap<usize, Region> = BTreeMap::new();
 
     for r1 in regions_1.values() {
         for r2 in regions_2.values() {
@@ -701,12 +1009,14 @@ pub fn intersect_regions(
                 continue;
             }
 
-            // find the shared substitutions
+            // find the shared substitutions, sorted so that the intersect is
+            // the same regardless of which region came first
             let subs_intersect = r1
                 .substitutions
                 .iter()
                 .filter(|sub| r2.substitutions.contains(sub))
                 .map(|sub| sub.to_owned())
+                .sorted()
                 .collect::<Vec<_>>();
 
             // if no shared subs, an intersection is not possible
@@ -715,8 +1025,24 @@ pub fn intersect_regions(
             }
 
             // start coordinate is the min sub, end is the max sub
-            let start = subs_intersect.iter().min().map(|sub| sub.coord).unwrap();
-            let end = subs_intersect.iter().max().map(|sub| sub.coord).unwrap();
+            let start = subs_intersect[0].coord;
+            let end = subs_intersect[subs_intersect.len() - 1].coord;
+
+            // overlapping input regions can intersect at the same start
+            if let Some(region) = regions_intersect.get_mut(&start) {
+                if region.origin != r1.origin {
+                    return Err(eyre!(
+                        "Regions overlap with conflicting origins ({} and {}) at coordinate {start}.",
+                        region.origin,
+                        r1.origin
+                    ));
+                }
+                region.end = region.end.max(end);
+                region.substitutions.extend(subs_intersect);
+                region.substitutions.sort();
+                region.substitutions.dedup();
+                continue;
+            }
 
             let region = Region {
                 start,
@@ -728,8 +1054,6 @@ pub fn intersect_regions(
         }
     }
 
-    // Do we need to go back the other way at all?
-
     Ok(regions_intersect)
 }
 
@@ -762,10 +1086,21 @@ pub fn identify_breakpoints(
 }
 
 /// Combine recombination tables.
+///
+/// Columns are ordered as: coord, origin, Reference, <parents...>, <sequences...>.
+/// Parents are in the order of the first recombination, and sequence ids
+/// are sorted lexicographically, so that the output is deterministic
+/// regardless of the order of the input recombinations.
 pub fn combine_tables(
     recombinations: &[Recombination],
     reference: &Sequence,
 ) -> Result<Table, Report> {
+    // sort by sequence id, for a reproducible column order
+    let recombinations = recombinations
+        .iter()
+        .sorted_by(|a, b| a.sequence.id.cmp(&b.sequence.id))
+        .collect_vec();
+
     // ------------------------------------------------------------------------
     // Input Checking
 
@@ -842,12 +1177,20 @@ pub fn combine_tables(
         let mut origins = vec![];
 
         // iterate through recombinants, identifying ref, parents, seq bases
-        for recombination in recombinations {
+        for recombination in &recombinations {
             // get sequence base directly from sequence
-            let rec_base = recombination.sequence.seq[coord - 1].to_string();
+            let rec_base = recombination.sequence.seq[coord - 1];
             let rec_output_i =
                 combine_table.header_position(&recombination.sequence.id)?;
-            row[rec_output_i] = rec_base;
+
+            // missing data is rendered as 'N', and does not contribute an origin
+            let is_missing =
+                rec_base == 'N' || recombination.sequence.missing.contains(coord);
+            if is_missing {
+                row[rec_output_i] = "N".to_string();
+            } else {
+                row[rec_output_i] = rec_base.to_string();
+            }
 
             // check which coords appeared in this sample
             let coord_input_i = recombination.table.header_position("coord")?;
@@ -866,7 +1209,9 @@ pub fn combine_tables(
                 // get origin of the particular sample's base
                 let origin_input_i = recombination.table.header_position("origin")?;
                 let origin = &recombination.table.rows[row_input_i][origin_input_i];
-                origins.push(origin.to_string());
+                if !is_missing {
+                    origins.push(origin.to_string());
+                }
 
                 // if it's the first sample encountered add the coord,
                 // Reference base and parent bases
@@ -888,12 +1233,12 @@ pub fn combine_tables(
         }
 
         // Dedup, and summarise the origins, code as "?" if ambiguous
+        // or if the coord is missing in all samples
         let origins = origins.into_iter().unique().collect_vec();
-        if origins.len() > 1 {
-            row[origin_output_i] = "?".to_string()
-        } else {
-            row[origin_output_i] = origins[0].clone();
-        }
+        row[origin_output_i] = match origins.len() {
+            1 => origins[0].clone(),
+            _ => "?".to_string(),
+        };
         // Add processed row to table
         combine_table.rows.push(row);
     }
@@ -1,13 +1,44 @@
 use crate::cli::run;
 use crate::dataset::{Dataset, SearchResult};
-use crate::recombination::{detect_recombination, validate, Hypothesis, Recombination};
+use crate::recombination::{
+    detect_recombination, validate, Hypothesis, Recombination, Subthreshold,
+};
 use crate::sequence::Sequence;
 use color_eyre::eyre::{eyre, Report, Result};
 use itertools::Itertools;
 use log::{debug, warn};
 use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
 use strum::IntoEnumIterator;
 
+// ----------------------------------------------------------------------------
+// Structs
+// ----------------------------------------------------------------------------
+
+/// A candidate parent evaluated during the parent search, for tracing.
+#[derive(Clone, Debug, Default)]
+pub struct Step {
+    pub hypothesis: String,
+    pub parent: usize,
+    pub iteration: usize,
+    pub candidate: String,
+    pub score: isize,
+    pub outcome: String,
+    pub subthreshold: Option<Subthreshold>,
+}
+
+/// The parent search passed its deadline (--timeout) and was abandoned.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Timeout;
+
+impl std::fmt::Display for Timeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Parent search timed out.")
+    }
+}
+
+impl std::error::Error for Timeout {}
+
 // ----------------------------------------------------------------------------
 // Functions
 // ----------------------------------------------------------------------------
@@ -16,6 +47,8 @@ use strum::IntoEnumIterator;
 ///
 /// Uses a recursion_limit for safety. It is not intended to
 /// run this wrapper function more than once recursively.
+///
+/// Each candidate parent that is evaluated is recorded in the `trace`.
 #[allow(clippy::needless_if)]
 pub fn all_parents<'seq>(
     sequence: &'seq Sequence,
@@ -23,6 +56,7 @@ pub fn all_parents<'seq>(
     best_match: &mut SearchResult,
     populations: &[&String],
     args: &run::Args,
+    trace: &mut Vec<Step>,
 ) -> Result<Recombination<'seq>, Report> {
     // copy args, we don't want to modify the original global parameters
     let mut args = args.clone();
@@ -32,6 +66,9 @@ pub fn all_parents<'seq>(
 
     let consensus_population = &best_match.consensus_population;
 
+    // optionally, abandon the search if it takes too long
+    let deadline = args.timeout.map(|t| Instant::now() + Duration::from_secs(t));
+
     // ------------------------------------------------------------------------
     // Edge Case
     // ------------------------------------------------------------------------
@@ -75,6 +112,10 @@ pub fn all_parents<'seq>(
 
     // iterate through the potential hypotheses
     for hypothesis in Hypothesis::iter() {
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            return Ok(timed_out(sequence));
+        }
+
         debug!("Testing Hypothesis: {hypothesis:?}");
 
         // ----------------------------------------------------------------------------
@@ -90,6 +131,14 @@ pub fn all_parents<'seq>(
                     Hypothesis::NonRecombinant,
                     (None, Vec::new(), score, conflict),
                 );
+                trace.push(Step {
+                    hypothesis: format!("{hypothesis:?}"),
+                    parent: 1,
+                    candidate: consensus_population.clone(),
+                    score,
+                    outcome: "non_recombinant".to_string(),
+                    ..Default::default()
+                });
             }
             continue;
         }
@@ -162,7 +211,14 @@ pub fn all_parents<'seq>(
             if hyp_populations.contains(&&best_match.consensus_population) {
                 Ok(best_match.clone())
             } else {
-                dataset.search(sequence, Some(&hyp_populations), None)
+                dataset.search(
+                    sequence,
+                    Some(&hyp_populations),
+                    None,
+                    args.candidate_margin,
+                    args.score_deletions,
+                    args.score_ambiguity,
+                )
             };
 
         // exclude is inverse of
@@ -173,9 +229,36 @@ pub fn all_parents<'seq>(
         // Check if primary search found anything
         if let Ok(primary_parent) = primary_search {
             debug!("Primary Parent Search was successful.");
+            let trace_start = trace.len();
+            let candidate = &primary_parent.consensus_population;
+            trace.push(Step {
+                parent: 1,
+                candidate: candidate.clone(),
+                score: primary_parent.score.get(candidate).copied().unwrap_or_default(),
+                outcome: "primary".to_string(),
+                ..Default::default()
+            });
+
             debug!("Secondary Parent(s) Search.");
-            let secondary_search =
-                secondary_parents(sequence, dataset, &[primary_parent], &hyp_args);
+            let secondary_search = secondary_parents(
+                sequence,
+                dataset,
+                &[primary_parent],
+                &hyp_args,
+                deadline,
+                trace,
+            );
+            trace[trace_start..]
+                .iter_mut()
+                .for_each(|step| step.hypothesis = format!("{hypothesis:?}"));
+
+            // the deadline can pass during any hypothesis, including the last
+            if secondary_search
+                .as_ref()
+                .is_err_and(|e| e.downcast_ref::<Timeout>().is_some())
+            {
+                return Ok(timed_out(sequence));
+            }
 
             if let Ok((recombination, parents)) = secondary_search {
                 debug!("Secondary Parent(s) Search was successful.");
@@ -208,8 +291,16 @@ pub fn all_parents<'seq>(
                     hypothesis
                 };
 
+                // optionally, the designated parents explain the sequence, so
+                // skip the exhaustive search of the remaining hypotheses
+                let is_designated = hypothesis == Hypothesis::DesignatedRecombinant;
                 hypotheses
                     .insert(hypothesis, (Some(recombination), parents, score, conflict));
+
+                if args.parents_auto && is_designated {
+                    debug!("Designated parents were successful, skipping full search.");
+                    break;
+                }
             } else {
                 debug!("Secondary Parent(s) Search was unsuccessful.");
             }
@@ -300,9 +391,17 @@ pub fn all_parents<'seq>(
 
     debug!("best_hypothesis: {best_hypothesis:?}");
 
-    // non-recombinant means that the parent search "failed"
+    // non-recombinant means that the parent search "failed", but keep the
+    // best recombinant hypothesis as an alternative call
     if best_hypothesis == Hypothesis::NonRecombinant {
-        return Err(eyre!("Best hypothesis is Non-Recombinant."));
+        let mut recombination = Recombination::new(sequence);
+        recombination.hypothesis = Some(Hypothesis::NonRecombinant);
+        recombination.alternative = hypotheses
+            .into_values()
+            .filter_map(|(r, _p, score, _c)| Some((r?.parents.join(","), score)))
+            .max_by_key(|(_parents, score)| *score);
+        debug!("Best hypothesis is Non-Recombinant.");
+        return Ok(recombination);
     }
     let result = hypotheses
         .remove(&best_hypothesis)
@@ -310,6 +409,18 @@ pub fn all_parents<'seq>(
     let mut recombination = result.0.unwrap();
     let primary_parent = result.1[0].clone();
 
+    // ------------------------------------------------------------------------
+    // Near-Identical Parents
+
+    // If two parents have barely enough discriminating sites to pass the region
+    // filters, regions can be assigned arbitrarily between them.
+    for (parent_1, parent_2, sites) in similar_parents(&result.1, args) {
+        warn!(
+            "{}: parents {parent_1} and {parent_2} are nearly identical ({sites} discriminating sites), the recombination call may be unstable. Consider raising --min-subs or using --knockout on one of them.",
+            sequence.id
+        );
+    }
+
     // ------------------------------------------------------------------------
     // Recombinant attributes
 
@@ -343,17 +454,140 @@ pub fn all_parents<'seq>(
         &recombination.breakpoints.iter().join("_"),
     );
 
-    recombination.hypothesis = Some(best_hypothesis);
+    recombination.hypothesis = Some(recombination.assign_hypothesis(&dataset.phylogeny));
 
     Ok(recombination)
 }
 
+/// Enumerate and rank all parent combinations for a dataset population.
+///
+/// Evaluates every combination of 2 up to `max_parents` candidate parents
+/// (expanded with wildcards) for recombination, and returns the successful
+/// hypotheses ranked by parsimony (highest score, then least conflict). This
+/// exposes the runner-up hypotheses that a single best call hides.
+pub fn enumerate_hypotheses<'seq>(
+    population: &str,
+    dataset: &'seq Dataset,
+    candidates: &[String],
+    max_parents: usize,
+    args: &run::Args,
+) -> Result<Vec<Recombination<'seq>>, Report> {
+    let sequence = dataset
+        .populations
+        .get(population)
+        .ok_or_else(|| eyre!("{population} is not present in the dataset."))?;
+
+    // parsimony summary of each candidate parent
+    let candidates = dataset
+        .expand_populations(candidates)?
+        .into_iter()
+        .filter(|c| c != population && dataset.populations.contains_key(c))
+        .collect_vec();
+    let parents = candidates
+        .iter()
+        .filter_map(|c| {
+            dataset
+                .search(
+                    sequence,
+                    Some(&vec![c]),
+                    None,
+                    args.candidate_margin,
+                    args.score_deletions,
+                    args.score_ambiguity,
+                )
+                .ok()
+        })
+        .collect_vec();
+
+    let mut recombinations = Vec::new();
+    for num_parents in 2..=max_parents {
+        for combination in parents.iter().cloned().combinations(num_parents) {
+            let (candidate, known) = combination.split_last().unwrap();
+            let result = detect_recombination(
+                sequence,
+                &known.to_vec(),
+                Some(candidate),
+                &dataset.reference,
+                args,
+            );
+            if let Ok(recombination) = result {
+                recombinations.push(recombination);
+            }
+        }
+    }
+
+    // rank by score (descending) then conflict (ascending)
+    let conflict = |r: &Recombination| -> usize {
+        r.conflict_alt.values().chain(r.conflict_ref.values()).map(|s| s.len()).sum()
+    };
+    recombinations.sort_by(|a, b| {
+        let score_a: isize = a.score.values().sum();
+        let score_b: isize = b.score.values().sum();
+        score_b.cmp(&score_a).then(conflict(a).cmp(&conflict(b)))
+    });
+
+    Ok(recombinations)
+}
+
+/// Identify pairs of parents with few discriminating sites between them.
+///
+/// Returns each pair of parents with fewer discriminating sites than the
+/// region filters require (`--min-subs` per parent), plus `--similar-parents-margin`,
+/// along with the number of discriminating sites.
+pub fn similar_parents(
+    parents: &[SearchResult],
+    args: &run::Args,
+) -> Vec<(String, String, usize)> {
+    let min_sites = args.min_subs * parents.len() + args.similar_parents_margin;
+    parents
+        .iter()
+        .tuple_combinations()
+        .filter_map(|(parent_1, parent_2)| {
+            // substitutions found in only one of the two parents
+            let sites = parent_1
+                .substitutions
+                .iter()
+                .filter(|sub| !parent_2.substitutions.contains(sub))
+                .chain(
+                    parent_2
+                        .substitutions
+                        .iter()
+                        .filter(|sub| !parent_1.substitutions.contains(sub)),
+                )
+                .map(|sub| sub.coord)
+                .unique()
+                .count();
+            (sites < min_sites).then_some((
+                parent_1.consensus_population.clone(),
+                parent_2.consensus_population.clone(),
+                sites,
+            ))
+        })
+        .collect_vec()
+}
+
+/// Abandon the parent search of a sequence, keeping its best match.
+fn timed_out(sequence: &Sequence) -> Recombination<'_> {
+    warn!(
+        "{}: parent search timed out, keeping best match.",
+        sequence.id
+    );
+    let mut recombination = Recombination::new(sequence);
+    recombination.timed_out = true;
+    recombination
+}
+
 // Search for the secondary recombination parent(s).
+//
+// If a deadline is provided, the search is abandoned once it has passed.
+// Each candidate parent that is evaluated is recorded in the trace.
 pub fn secondary_parents<'seq>(
     sequence: &'seq Sequence,
     dataset: &Dataset,
     parents: &[SearchResult],
     args: &run::Args,
+    deadline: Option<Instant>,
+    trace: &mut Vec<Step>,
 ) -> Result<(Recombination<'seq>, Vec<SearchResult>), Report> {
     // Initialize our 'Recombination' result, that we will modify and update
     // as we iterate through potential parents
@@ -384,6 +618,9 @@ pub fn secondary_parents<'seq>(
         // Check if we can break out of the loop based on simple checks like the
         // max number of iterations max number of parents achieved.
 
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            return Err(Report::new(Timeout));
+        }
         if num_parents >= args.max_parents {
             // Maxing out the number of parents is a SUCCESS
             debug!("Maximum parents reached ({num_parents}).");
@@ -593,6 +830,9 @@ pub fn secondary_parents<'seq>(
                 sequence,
                 Some(&include_populations),
                 Some(&search_coords),
+                args.candidate_margin,
+                args.score_deletions,
+                args.score_ambiguity,
             );
 
             // if the search found parents, check for recombination
@@ -610,9 +850,49 @@ pub fn secondary_parents<'seq>(
                     args,
                 );
 
+                let mut step = Step {
+                    parent: num_parents + 1,
+                    iteration: num_iter,
+                    candidate: parent_candidate.consensus_population.clone(),
+                    outcome: "no_recombination".to_string(),
+                    ..Default::default()
+                };
+
+                // keep the rejected regions of a near-miss, for reporting
+                if let Err(e) = &detect_result {
+                    step.subthreshold = e.downcast_ref::<Subthreshold>().cloned();
+                }
+
                 // if successful, add this parent to the list and update recombination
                 // break out of the search mode loop
                 if let Ok(detect_result) = detect_result {
+                    step.score = detect_result.score.values().sum();
+                    // early stop, if the additional parent doesn't improve the score
+                    if !recombination.parents.is_empty() {
+                        let score_prev: isize = recombination.score.values().sum();
+                        let score_new: isize = detect_result.score.values().sum();
+                        if score_new <= score_prev {
+                            step.outcome = "no_improvement".to_string();
+                            trace.push(step);
+                            debug!(
+                                "Parent {} did not improve the combined score ({score_prev} -> {score_new}), stopping parent search.",
+                                parent_candidate.consensus_population
+                            );
+                            // Finding minimum parents is a SUCCESS
+                            if num_parents >= args.min_parents {
+                                return Ok((recombination, parents));
+                            }
+                            // Otherwise FAILURE
+                            else {
+                                return Err(eyre!(
+                                    "Number of parents ({num_parents}) is less than the minimum ({}).",
+                                    args.min_parents
+                                ));
+                            }
+                        }
+                    }
+                    step.outcome = "accepted".to_string();
+                    trace.push(step);
                     num_parents += 1;
                     // reset the iter counter
                     num_iter = 0;
@@ -620,6 +900,7 @@ pub fn secondary_parents<'seq>(
                     recombination = detect_result;
                     break;
                 }
+                trace.push(step);
             }
         }
     }
@@ -1,4 +1,4 @@
-use crate::dataset::{Dataset, SearchResult};
+use crate::dataset::{strip_population_prefix, Dataset, SearchResult};
 use crate::recombination::Recombination;
 use color_eyre::eyre::{eyre, Report, Result};
 use itertools::Itertools;
@@ -95,7 +95,8 @@ pub fn validate(
     recombination: &Recombination,
 ) -> Result<Option<Validate>, Report> {
     // Use the sequence ID as the expected population
-    let expected_population = best_match.sequence_id.replace("population_", "");
+    let expected_population =
+        strip_population_prefix(&best_match.sequence_id).to_string();
 
     // If the sequence ID was not in the dataset, return no validation results
     if !dataset.populations.contains_key(&expected_population) {
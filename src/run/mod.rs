@@ -1,19 +1,34 @@
 use crate::cli;
+use crate::cli::run::Output;
 use crate::dataset;
 use crate::export;
 use crate::recombination;
 
-use crate::dataset::{attributes::Name, SearchResult};
+use crate::dataset::attributes::Summary;
+use crate::dataset::SearchResult;
+use crate::phylogeny::Phylogeny;
+use crate::plot;
 use crate::recombination::Recombination;
-use crate::sequence::Sequence;
+use crate::sequence::{
+    open_fasta, validate_alignment, validate_record, InsertionMode, Sequence,
+};
+use crate::utils::{exit, table::Table, warnings::Warnings};
 use bio::io::fasta;
 use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use color_eyre::Help;
 use indicatif::{style::ProgressStyle, ProgressBar};
 use itertools::Itertools;
 use log::{debug, info, warn};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs::{create_dir_all, File};
 use std::io::Write;
+use std::path::Path;
+
+/// Sequence id of the --sequence input.
+pub const INPUT_SEQUENCE_ID: &str = "sequence";
 
 /// Run rebar on input alignment and/or dataset population(s)
 pub fn run(args: &mut cli::run::Args) -> Result<(), Report> {
@@ -50,13 +65,13 @@ pub fn run(args: &mut cli::run::Args) -> Result<(), Report> {
         num_threads = default_thread_pool.current_num_threads();
     }
 
-    // configure the global thread pool
+    // configure a local thread pool, rather than the global one, so that
+    // repeated runs in the same process respect their own --threads
     info!("Using {} thread(s).", num_threads);
-    let result = rayon::ThreadPoolBuilder::new().num_threads(num_threads).build_global();
-
-    if result.is_err() {
-        warn!("Failed to build global thread pool.");
-    }
+    let thread_pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .build()
+        .wrap_err("Failed to build thread pool.")?;
 
     // configure progress bar style
     let progress_bar_style = ProgressStyle::with_template(
@@ -66,14 +81,67 @@ pub fn run(args: &mut cli::run::Args) -> Result<(), Report> {
     // Collect files in dataset_dir into a dataset object
     // This mainly includes parent populations sequences
     //   and optionally a phylogenetic representation.
-    let mut dataset = dataset::load::dataset(&args.dataset_dir, &args.mask)?;
+    // Populations are parsed in parallel, with the requested --threads.
+    let mut dataset = thread_pool.install(|| {
+        dataset::load::dataset(
+            &args.dataset_dir,
+            &args.mask,
+            args.reference_id.as_deref(),
+            args.strict,
+        )
+    })?;
+
+    if args.no_phylogeny {
+        info!("Ignoring the dataset phylogeny (--no-phylogeny).");
+        dataset.phylogeny = Phylogeny::new();
+    }
+
+    if let Some(subset) = &args.subset {
+        info!("Restricting the dataset to a subset of populations: {subset:?}");
+        dataset = dataset.subset(subset)?;
+    }
+
+    if let Some(priors) = &args.priors {
+        info!("Using population priors: {priors:?}");
+        dataset.priors = dataset::read_priors(priors)?;
+    }
 
     // init a container to hold query sequences, dataset
     // populations and/or sequences from an input alignment
     let mut sequences = Vec::new();
+    // repeated warnings are tallied by category, and summarized at the end
+    let mut warnings = Warnings::new(args.max_warnings);
+
+    // invalid records that were skipped, and the reason why
+    let mut skipped = Vec::new();
     // keep track of ids we've seen to remove duplicates later
     let mut ids_seen = Vec::new();
 
+    // ------------------------------------------------------------------------
+    // Recombinants Only
+    // ------------------------------------------------------------------------
+
+    if args.input.recombinants_only {
+        if dataset.phylogeny.is_empty() {
+            return Err(eyre!(exit::Code::Usage)
+                .wrap_err("--recombinants-only requires a dataset phylogeny."));
+        }
+        info!("Using all recombinant populations as input.");
+        args.input.populations.get_or_insert_with(Vec::new).push("X*".to_string());
+
+        // restrict parents to non-recombinants, that have sequence data
+        if args.parents.is_none() {
+            info!("Restricting parent search to non-recombinant populations.");
+            let non_recombinants = dataset
+                .phylogeny
+                .get_non_recombinants_all()?
+                .into_iter()
+                .filter(|p| dataset.populations.contains_key(p))
+                .collect_vec();
+            args.parents = Some(non_recombinants);
+        }
+    }
+
     // ------------------------------------------------------------------------
     // Parse Input Populations
     // ------------------------------------------------------------------------
@@ -83,22 +151,25 @@ pub fn run(args: &mut cli::run::Args) -> Result<(), Report> {
     if let Some(populations) = &args.input.populations {
         info!("Parsing input populations: {populations:?}");
 
-        // limit the amount of warnings emitted
-        let mut num_warnings = 0;
-        let max_warnings = 10;
+        let populations = if args.require_sequence {
+            dataset.expand_populations_with_sequence(populations)?
+        } else {
+            dataset.expand_populations(populations)?
+        };
 
-        dataset.expand_populations(populations)?.into_iter().for_each(|p| {
+        populations.into_iter().for_each(|p| {
             if !dataset.populations.contains_key(&p) {
-                if num_warnings < max_warnings {
-                    warn!("Population {p} is not in the dataset populations fasta.");
-                } else {
-                    warn!("... (Additional warnings ommitted)");
-                }
-                num_warnings += 1;
+                warnings.warn(
+                    "populations not in the dataset",
+                    &format!("Population {p} is not in the dataset populations fasta."),
+                );
             } else {
                 debug!("Adding population {p} to query sequences.");
                 let mut sequence = dataset.populations.get(&p).unwrap().clone();
-                sequence.id = format!("population_{}", sequence.id);
+                if !args.no_population_prefix {
+                    sequence.id =
+                        format!("{}{}", dataset::POPULATION_PREFIX, sequence.id);
+                }
                 ids_seen.push(sequence.id.clone());
                 sequences.push(sequence.clone());
             }
@@ -110,21 +181,74 @@ pub fn run(args: &mut cli::run::Args) -> Result<(), Report> {
     // ------------------------------------------------------------------------
 
     if let Some(alignment) = &args.input.alignment {
+        // optionally, records are compared to the gapped reference in the alignment
+        let (reference, insertion_mode) = if args.record_insertions {
+            let reference = read_alignment_reference(alignment, &dataset.reference)?;
+            (reference, InsertionMode::Record)
+        } else {
+            (dataset.reference.clone(), InsertionMode::Ignore)
+        };
+
+        // optionally, report all input problems up front, before parsing
+        if args.validate_input {
+            info!("Validating query alignment: {alignment:?}");
+            let (num_records, problems) = validate_alignment(alignment, &reference)?;
+            info!(
+                "Found {num_records} records, with {} problem(s).",
+                problems.len()
+            );
+            problems
+                .iter()
+                .for_each(|(_id, reason)| warnings.warn("input problems", reason));
+            if !problems.is_empty() && !args.skip_invalid {
+                return Err(eyre!(
+                    "Query alignment has {} problem(s): {alignment:?}",
+                    problems.len()
+                )
+                .suggestion("Use --skip-invalid to skip invalid records."));
+            }
+        }
+
         info!("Loading query alignment: {:?}", alignment);
-        let alignment_reader = fasta::Reader::from_file(alignment)
-            .map_err(|e| eyre!(e))
-            .wrap_err("Failed to read file: {alignment:?}")?;
+        let alignment_reader = open_fasta(alignment)?;
 
         for result in alignment_reader.records() {
-            let record = result.wrap_err("Unable to parse alignment: {alignment:?}")?;
-            let sequence =
-                Sequence::from_record(record, Some(&dataset.reference), &args.mask)?;
+            let record = result
+                .wrap_err_with(|| format!("Unable to parse alignment: {alignment:?}"))?;
+            let id = record.id().to_string();
+            if args.record_insertions && id == dataset.reference.id {
+                continue;
+            }
+            let sequence = validate_record(&record, &reference).and_then(|_| {
+                Sequence::from_record_with_insertions(
+                    record,
+                    Some(&reference),
+                    &args.mask,
+                    insertion_mode,
+                )
+            });
+            let sequence = match sequence {
+                Ok(sequence) => sequence,
+                // optionally, skip invalid records rather than failing the run
+                Err(e) if args.skip_invalid => {
+                    warnings.warn(
+                        "invalid records skipped",
+                        &format!("Skipping invalid sequence {id}: {e}"),
+                    );
+                    skipped.push((id, e.to_string()));
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
 
             // check for duplicates
             if ids_seen.contains(&sequence.id) {
-                warn!(
-                    "Sequence {} is duplicated, retaining first one.",
-                    sequence.id
+                warnings.warn(
+                    "duplicate IDs dropped",
+                    &format!(
+                        "Sequence {} is duplicated, retaining first one.",
+                        sequence.id
+                    ),
                 );
                 continue;
             } else {
@@ -134,6 +258,35 @@ pub fn run(args: &mut cli::run::Args) -> Result<(), Report> {
         }
     }
 
+    // ------------------------------------------------------------------------
+    // Parse Input Sequence
+
+    // a single raw genome string, from the command line or a file
+    let input_sequence = match (&args.input.sequence, &args.input.sequence_file) {
+        (Some(sequence), _) => Some(sequence.clone()),
+        (None, Some(path)) => Some(
+            std::fs::read_to_string(path)
+                .wrap_err_with(|| format!("Failed to read file: {path:?}"))?,
+        ),
+        (None, None) => None,
+    };
+    if let Some(input_sequence) = input_sequence {
+        info!("Parsing input sequence.");
+        let seq = input_sequence.split_whitespace().join("");
+        let record = fasta::Record::with_attrs(INPUT_SEQUENCE_ID, None, seq.as_bytes());
+        validate_record(&record, &dataset.reference)?;
+        let sequence =
+            Sequence::from_record(record, Some(&dataset.reference), &args.mask)
+                .suggestion("The --sequence must be aligned to the reference.")?;
+        if ids_seen.contains(&sequence.id) {
+            return Err(eyre!(exit::Code::Usage).wrap_err(format!(
+                "Sequence {INPUT_SEQUENCE_ID} is duplicated in the input."
+            )));
+        }
+        ids_seen.push(sequence.id.clone());
+        sequences.push(sequence);
+    }
+
     // ------------------------------------------------------------------------
     // Parse and expand input parents
 
@@ -146,6 +299,9 @@ pub fn run(args: &mut cli::run::Args) -> Result<(), Report> {
     // Dataset Knockout
     // ------------------------------------------------------------------------
 
+    // keep the original phylogeny, to validate knocked out recombinants
+    let phylogeny_original = dataset.phylogeny.clone();
+
     if let Some(knockout) = &args.knockout {
         info!("Performing dataset knockout: {knockout:?}");
 
@@ -164,7 +320,7 @@ pub fn run(args: &mut cli::run::Args) -> Result<(), Report> {
         dataset.populations.retain(|id, _| !knockout_expanded.contains(id));
 
         debug!("Removing knockout populations from the mutations.");
-        dataset.mutations.iter_mut().for_each(|(_sub, populations)| {
+        dataset.mutations.iter_mut().for_each(|(_mutation, populations)| {
             populations.retain(|p| !knockout_expanded.contains(p));
         });
 
@@ -181,6 +337,49 @@ pub fn run(args: &mut cli::run::Args) -> Result<(), Report> {
     // Recombination Search
     // ------------------------------------------------------------------------
 
+    // optionally, process only a subset of the query sequences
+    if let Some(limit) = args.limit {
+        info!("Limiting query sequences to the first {limit}.");
+        sequences.truncate(limit);
+    }
+    if let Some(fraction) = args.sample {
+        if !(0.0..=1.0).contains(&fraction) {
+            return Err(eyre!("--sample must be between 0 and 1: {fraction}"));
+        }
+        let mut rng = match args.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let amount = (sequences.len() as f32 * fraction).round() as usize;
+        info!("Sampling {amount}/{} query sequences.", sequences.len());
+        // keep the original input order of the sampled sequences
+        let indices: BTreeSet<usize> =
+            rand::seq::index::sample(&mut rng, sequences.len(), amount)
+                .into_iter()
+                .collect();
+        sequences = sequences
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, sequence)| indices.contains(&i).then_some(sequence))
+            .collect_vec();
+    }
+
+    if sequences.is_empty() {
+        return Err(eyre!(exit::Code::EmptyResult)
+            .wrap_err("No query sequences were found in the input."));
+    }
+
+    // optionally, write the resolved query sequences, for QC and reuse
+    if let Some(outpath) = &args.write_query_fasta {
+        info!("Exporting query sequences: {outpath:?}");
+        let sequences = sequences.iter().collect_vec();
+        let fasta = export::alignment(&sequences, &dataset.reference);
+        let mut file = File::create(outpath)
+            .wrap_err_with(|| format!("Failed to create file: {outpath:?}"))?;
+        file.write_all(fasta.as_bytes())
+            .wrap_err_with(|| format!("Failed to write file: {outpath:?}"))?;
+    }
+
     info!("Running recombination search.");
 
     // this step is the slowest, use progress bar and parallel threads
@@ -197,11 +396,29 @@ pub fn run(args: &mut cli::run::Args) -> Result<(), Report> {
     if let Some(populations) = &args.knockout {
         parent_search_populations.retain(|pop| !populations.contains(pop))
     }
+    // if args.parent_min_subs supplied on the CLI, drop near-reference populations
+    if args.parent_min_subs > 0 {
+        let num_populations = parent_search_populations.len();
+        parent_search_populations.retain(|pop| {
+            dataset.populations[*pop].substitutions.len() >= args.parent_min_subs
+        });
+        let num_excluded = num_populations - parent_search_populations.len();
+        info!(
+            "Excluding {num_excluded} population(s) with fewer than {} substitutions from the parent search.",
+            args.parent_min_subs
+        );
+    }
+
+    // the population the reference represents, for queries without mutations
+    let reference_population = dataset.reference_population();
 
     // Search for the best match and recombination parents for each sequence.
     // This loop/closure is structured weirdly for rayon compatability, and the
     // fact that we need to return multiple types of objects
-    let results: Vec<(SearchResult, Recombination)> = sequences
+    // Results are collected in input order, regardless of the number of threads.
+    let (results, traces): (Vec<(SearchResult, Recombination)>, Vec<_>) =
+        thread_pool.install(|| {
+            sequences
         .par_iter()
         .map(|sequence| {
             // initialize with default results, regardless of whether our
@@ -210,6 +427,7 @@ pub fn run(args: &mut cli::run::Args) -> Result<(), Report> {
             // which will include the "negative" results
             let mut best_match = SearchResult::new(sequence);
             let mut recombination = Recombination::new(sequence);
+            let mut trace = Vec::new();
 
             // ------------------------------------------------------------------------
             // Best Match (Consensus)
@@ -218,11 +436,39 @@ pub fn run(args: &mut cli::run::Args) -> Result<(), Report> {
             // this will represent the consensus population call.
 
             debug!("Identifying best match (consensus population).");
-            let search_result = dataset.search(sequence, None, None);
+            let search_result =
+                dataset.search(
+                    sequence,
+                    None,
+                    None,
+                    args.candidate_margin,
+                    args.score_deletions,
+                    args.score_ambiguity,
+                );
+
+            // reject consensus calls that are too broad (close to the root), but
+            // keep the rejected call to report the sequence as ambiguous
+            let mut consensus_ambiguous = None;
+            let search_result = search_result.and_then(|result| {
+                if args.min_consensus_depth > 0 && !dataset.phylogeny.is_empty() {
+                    let population = &result.consensus_population;
+                    let depth = dataset.phylogeny.get_depth(population)?;
+                    if depth < args.min_consensus_depth {
+                        consensus_ambiguous = Some(population.clone());
+                        return Err(eyre!(
+                            "Consensus population {population} (depth {depth}) is ambiguous."
+                        ));
+                    }
+                }
+                Ok(result)
+            });
 
             // if we found a match, proceed with recombinant search
             if let Ok(search_result) = search_result {
                 best_match = search_result;
+                // the non-recombinant call, before the parent search can change it
+                let consensus_population = best_match.consensus_population.clone();
+                let consensus_score = best_match.score.get(&consensus_population).copied();
 
                 debug!("Searching for recombination parents.");
                 let parent_search = recombination::search::all_parents(
@@ -231,93 +477,468 @@ pub fn run(args: &mut cli::run::Args) -> Result<(), Report> {
                     &mut best_match,
                     &parent_search_populations,
                     args,
+                    &mut trace,
                 );
                 match parent_search {
                     Ok(search_result) => recombination = search_result,
                     Err(e) => debug!("Parent search did not succeed. {e}"),
                 }
+                recombination.hypothesis =
+                    Some(recombination.assign_hypothesis(&dataset.phylogeny));
+
+                // compare the recombinant and non-recombinant calls, and only
+                // keep the runner-up if it scores within the margin
+                let (score, alternative) = if recombination.parents.is_empty() {
+                    (consensus_score.unwrap_or_default(), recombination.alternative.take())
+                } else {
+                    let score: isize = recombination.score.values().sum();
+                    (score, consensus_score.map(|s| (consensus_population, s)))
+                };
+                recombination.alternative = args.alternative_margin.and_then(|margin| {
+                    alternative.filter(|(_call, alt)| score.abs_diff(*alt) <= margin)
+                });
             }
             // what to do if not a single population matched?
             else {
-                // temporary handling for root population B
-                if dataset.name == Name::SarsCov2 {
-                    if sequence.id == "population_B" {
-                        best_match.consensus_population = "B".to_string();
+                // a sequence identical to the reference has no mutations to
+                // match, call it as the population the reference represents
+                let is_reference = export::identical_to_reference(sequence, args);
+                match (&reference_population, is_reference) {
+                    (Some(population), true) => {
+                        debug!("Sequence is identical to the reference ({population}).");
+                        best_match.consensus_population = population.clone();
+                        best_match.top_populations = vec![population.clone()];
                     }
-                } else {
-                    debug!("No matches found.");
+                    _ => debug!("No matches found."),
                 }
+                best_match.consensus_ambiguous = consensus_ambiguous;
             }
 
             progress_bar.inc(1);
 
-            (best_match, recombination)
+            ((best_match, recombination), (sequence.id.clone(), trace))
         })
-        .collect();
+        .unzip()
+        });
 
     progress_bar.finish();
 
     // ------------------------------------------------------------------------
     // Export CLI args
 
-    let outpath_args = args.output_dir.join("run_args.json");
-    info!("Exporting CLI Run Args: {outpath_args:?}");
-    // create output file
-    let mut file = File::create(&outpath_args)
-        .wrap_err_with(|| format!("Failed to create file: {outpath_args:?}"))?;
+    if args.outputs.contains(&Output::Args) {
+        let outpath_args = args.output_dir.join("run_args.json");
+        info!("Exporting CLI Run Args: {outpath_args:?}");
+        // create output file
+        let mut file = File::create(&outpath_args)
+            .wrap_err_with(|| format!("Failed to create file: {outpath_args:?}"))?;
 
-    // parse to string
-    let output = serde_json::to_string_pretty(&args_export)
-        .wrap_err_with(|| "Failed to parse mutations.".to_string())?;
+        // parse to string
+        let output = serde_json::to_string_pretty(&args_export)
+            .wrap_err_with(|| "Failed to parse mutations.".to_string())?;
 
-    // write to file
-    file.write_all(format!("{}\n", output).as_bytes())
-        .wrap_err_with(|| format!("Failed to write file: {outpath_args:?}"))?;
+        // write to file
+        file.write_all(format!("{}\n", output).as_bytes())
+            .wrap_err_with(|| format!("Failed to write file: {outpath_args:?}"))?;
+    }
 
     // ------------------------------------------------------------------------
-    // Export Linelist (single)
+    // Export Dataset Provenance
+
+    // record exactly which dataset produced these results (name, tag, file
+    // SHAs and download dates), so the outputs can be traced back to it later
+    if args.outputs.contains(&Output::Provenance) {
+        let outpath_provenance = args.output_dir.join("dataset_provenance.json");
+        info!("Exporting dataset provenance: {outpath_provenance:?}");
+        let summary_path = args.dataset_dir.join("summary.json");
+        let provenance = if summary_path.exists() {
+            Summary::read(&summary_path)?
+        } else {
+            let mut summary = Summary::new();
+            summary.name = dataset.name;
+            summary.tag = dataset.tag.clone();
+            summary
+        };
+        provenance.write(&outpath_provenance)?;
+    }
 
-    let outpath_linelist = args.output_dir.join("linelist.tsv");
-    info!("Exporting linelist: {outpath_linelist:?}");
+    // optionally gzip compress the tsv outputs
+    let tsv_ext = if args.compress_output {
+        "tsv.gz"
+    } else {
+        "tsv"
+    };
 
-    let linelist_table = export::linelist(&results, &dataset)?;
+    // ------------------------------------------------------------------------
+    // Export Linelist (single)
+
+    let mut linelist_table = export::linelist(&results, &dataset, args)?;
     //let linelist_table = export::linelist(&best_matches, &recombinations, &dataset)?;
-    linelist_table.write(&outpath_linelist)?;
+
+    // optionally, only write samples where recombination was detected
+    if args.recombinants_only_output {
+        let recombinant_i = linelist_table.header_position("recombinant")?;
+        let parents_i = linelist_table.header_position("parents")?;
+        let num_rows = linelist_table.rows.len();
+        linelist_table
+            .rows
+            .retain(|row| !row[recombinant_i].is_empty() || !row[parents_i].is_empty());
+        let num_filtered = num_rows - linelist_table.rows.len();
+        info!("Excluding {num_filtered} non-recombinant sample(s) from the linelist.");
+    }
+
+    // for a single input sequence, also print the call for convenience
+    if args.input.sequence.is_some() || args.input.sequence_file.is_some() {
+        let strain_i = linelist_table.header_position("strain")?;
+        let row =
+            linelist_table.rows.iter().find(|row| row[strain_i] == INPUT_SEQUENCE_ID);
+        if let Some(row) = row {
+            let call: BTreeMap<_, _> = linelist_table.headers.iter().zip(row).collect();
+            let output = serde_json::to_string_pretty(&call)
+                .wrap_err_with(|| "Failed to parse linelist.".to_string())?;
+            println!("{output}");
+        }
+    }
+
+    if args.outputs.contains(&Output::Linelist) {
+        let outpath_linelist = args.output_dir.join(format!("linelist.{tsv_ext}"));
+        info!("Exporting linelist: {outpath_linelist:?}");
+        linelist_table.write(&outpath_linelist)?;
+    }
 
     // ------------------------------------------------------------------------
-    // Export Barcodes (multiple, collected by recombinant)
+    // Export Recombinations (structured, alongside the linelist)
+
+    if args.outputs.contains(&Output::Recombinations) {
+        let outpath = args.output_dir.join("recombinations.json");
+        info!("Exporting recombinations: {outpath:?}");
+        let output = export::recombinations(&results)?;
+        let mut file = File::create(&outpath)
+            .wrap_err_with(|| format!("Failed to create file: {outpath:?}"))?;
+        file.write_all(output.as_bytes())
+            .wrap_err_with(|| format!("Failed to write file: {outpath:?}"))?;
+    }
 
-    let outdir_barcodes = args.output_dir.join("barcodes");
+    // ------------------------------------------------------------------------
+    // Export Summary (optional)
+
+    if args.summary || args.outputs.contains(&Output::Report) {
+        let summary = export::summary(&results, args);
+
+        let outpath = args.output_dir.join("summary.txt");
+        info!("Exporting run summary: {outpath:?}");
+        let mut file = File::create(&outpath)
+            .wrap_err_with(|| format!("Failed to create file: {outpath:?}"))?;
+        file.write_all(summary.to_string().as_bytes())
+            .wrap_err_with(|| format!("Failed to write file: {outpath:?}"))?;
+
+        let outpath = args.output_dir.join("summary.json");
+        let output = serde_json::to_string_pretty(&summary)
+            .wrap_err_with(|| "Failed to parse summary.".to_string())?;
+        let mut file = File::create(&outpath)
+            .wrap_err_with(|| format!("Failed to create file: {outpath:?}"))?;
+        file.write_all(format!("{}\n", output).as_bytes())
+            .wrap_err_with(|| format!("Failed to write file: {outpath:?}"))?;
+    }
 
-    create_dir_all(&outdir_barcodes)?;
+    // ------------------------------------------------------------------------
+    // Export Parent Pairs (optional)
 
-    // get unique keys of recombinants identified
-    let unique_keys = results
-        .iter()
-        .filter_map(|(_b, r)| (*r.unique_key != String::new()).then_some(&r.unique_key))
-        .unique()
-        .collect_vec();
+    if args.group_by_parents {
+        let outpath = args.output_dir.join(format!("parent_pairs.{tsv_ext}"));
+        info!("Exporting recombinants grouped by parents: {outpath:?}");
+        export::parent_pairs(&results, &args.coordinate_base)?.write(&outpath)?;
+    }
 
-    if unique_keys.is_empty() {
-        warn!("No recombination detected, no barcodes will be outputted.");
-    } else {
-        info!("Exporting recombination barcodes: {outdir_barcodes:?}");
+    // ------------------------------------------------------------------------
+    // Export Search Trace (optional)
+
+    if args.trace_search {
+        let outpath = args.output_dir.join(format!("trace.{tsv_ext}"));
+        info!("Exporting parent search trace: {outpath:?}");
+        export::trace(&traces)?.write(&outpath)?;
+    }
+
+    // ------------------------------------------------------------------------
+    // Export Subthreshold Recombination (optional)
+
+    if args.report_subthreshold {
+        let outpath = args.output_dir.join(format!("subthreshold.{tsv_ext}"));
+        info!("Exporting subthreshold recombination: {outpath:?}");
+        export::subthreshold(&results, &traces, &args.coordinate_base)?
+            .write(&outpath)?;
+    }
+
+    // ------------------------------------------------------------------------
+    // Export Skipped Sequences (optional)
+
+    if args.skip_invalid {
+        let outpath = args.output_dir.join(format!("skipped.{tsv_ext}"));
+        info!("Exporting skipped sequences: {outpath:?}");
+        let mut table = Table::new();
+        table.headers = vec!["strain".to_string(), "reason".to_string()];
+        table.rows = skipped.into_iter().map(|(id, reason)| vec![id, reason]).collect();
+        table.write(&outpath)?;
+    }
+
+    // ------------------------------------------------------------------------
+    // Export Knockout Validation (optional)
+
+    if args.knockout_validate {
+        let outpath = args.output_dir.join(format!("knockout_validate.{tsv_ext}"));
+        info!("Exporting knockout validation: {outpath:?}");
+        let knockout = args.knockout.clone().unwrap_or_default();
+        let table = export::knockout_validate(
+            &results,
+            &dataset,
+            &phylogeny_original,
+            &knockout,
+            &args.coordinate_base,
+        )?;
+        table.write(&outpath)?;
+    }
+
+    // ------------------------------------------------------------------------
+    // Export Substitutions (optional)
+
+    if args.output_substitutions {
+        let outpath = args.output_dir.join(format!("substitutions.{tsv_ext}"));
+        info!("Exporting substitutions: {outpath:?}");
+        export::substitutions(&results, args)?.write(&outpath)?;
+    }
+
+    // ------------------------------------------------------------------------
+    // Export Features (optional)
+
+    if args.features {
+        let outpath = args.output_dir.join(format!("features.{tsv_ext}"));
+        info!("Exporting features: {outpath:?}");
+        export::features(&results)?.write(&outpath)?;
+    }
+
+    // ------------------------------------------------------------------------
+    // Export Pangolin (optional)
+
+    if args.pangolin {
+        let outpath_pangolin = args.output_dir.join("pangolin.csv");
+        info!("Exporting pangolin report: {outpath_pangolin:?}");
+        export::pangolin_compat(&results, &args.coordinate_base)?
+            .write(&outpath_pangolin)?;
     }
 
-    for unique_key in unique_keys {
-        // filter recombinations down to just this recombinant unique_key
-        let unique_rec = results
+    // ------------------------------------------------------------------------
+    // Export Barcodes (multiple, collected by recombinant)
+
+    // barcodes are only built if they are written, or needed for the plot bundle
+    let write_barcodes = args.outputs.contains(&Output::Barcodes);
+    let mut barcode_tables = BTreeMap::new();
+
+    if write_barcodes || args.plot_bundle {
+        let outdir_barcodes = args.output_dir.join("barcodes");
+
+        // get unique keys of recombinants identified
+        let unique_keys = results
             .iter()
-            .filter_map(|(_b, r)| (r.unique_key == *unique_key).then_some(r))
-            .cloned()
+            .filter_map(|(_b, r)| {
+                (*r.unique_key != String::new()).then_some(&r.unique_key)
+            })
+            .unique()
             .collect_vec();
-        // combine all the sample barcode tables
-        let barcode_table =
-            recombination::combine_tables(&unique_rec, &dataset.reference)?;
-        let barcode_table_path = outdir_barcodes.join(format!("{unique_key}.tsv"));
-        barcode_table.write(&barcode_table_path)?;
+
+        if write_barcodes {
+            create_dir_all(&outdir_barcodes)?;
+            if unique_keys.is_empty() {
+                warn!("No recombination detected, no barcodes will be outputted.");
+            } else {
+                info!("Exporting recombination barcodes: {outdir_barcodes:?}");
+            }
+        }
+
+        // optionally, write each sample's own barcodes, without combining
+        if args.barcodes_granularity == cli::run::BarcodesGranularity::Sample {
+            for (_best_match, recombination) in &results {
+                if recombination.unique_key.is_empty() {
+                    continue;
+                }
+                // sequence ids might contain path separators (ex. hCoV-19/...)
+                let file_name = recombination.sequence.id.replace('/', "_");
+                let barcode_table =
+                    export::shift_coords(&recombination.table, &args.coordinate_base)?;
+                if write_barcodes {
+                    let barcode_table_path =
+                        outdir_barcodes.join(format!("{file_name}.{tsv_ext}"));
+                    barcode_table.write(&barcode_table_path)?;
+                }
+                barcode_tables.insert(file_name, barcode_table);
+            }
+        }
+
+        let unique_keys = match args.barcodes_granularity {
+            cli::run::BarcodesGranularity::Recombinant => unique_keys,
+            cli::run::BarcodesGranularity::Sample => Vec::new(),
+        };
+        for unique_key in unique_keys {
+            // filter recombinations down to just this recombinant unique_key
+            let unique_rec = results
+                .iter()
+                .filter_map(|(_b, r)| (r.unique_key == *unique_key).then_some(r))
+                .cloned()
+                .collect_vec();
+            // combine all the sample barcode tables
+            let barcode_table =
+                recombination::combine_tables(&unique_rec, &dataset.reference)?;
+            let barcode_table =
+                export::shift_coords(&barcode_table, &args.coordinate_base)?;
+            if write_barcodes {
+                let barcode_table_path =
+                    outdir_barcodes.join(format!("{unique_key}.{tsv_ext}"));
+                barcode_table.write(&barcode_table_path)?;
+            }
+            barcode_tables.insert(unique_key.clone(), barcode_table);
+        }
+    }
+
+    // ------------------------------------------------------------------------
+    // Export Plot Bundle (optional, self-contained input for plotting)
+
+    if args.plot_bundle {
+        let outpath = args.output_dir.join("plot_bundle.json");
+        info!("Exporting plot bundle: {outpath:?}");
+        let annotations_path = args.dataset_dir.join("annotations.tsv");
+        let annotations = if annotations_path.exists() {
+            Table::read(&annotations_path)?
+        } else {
+            Table::new()
+        };
+        let bundle = plot::Bundle {
+            dataset_name: dataset.name.to_string(),
+            dataset_tag: dataset.tag.to_string(),
+            genome_length: dataset.reference.genome_length,
+            linelist: linelist_table,
+            barcodes: barcode_tables,
+            annotations,
+        };
+        bundle.write(&outpath)?;
     }
 
+    // ------------------------------------------------------------------------
+    // Export Parent Alignments (optional, one per recombinant sequence)
+
+    if args.parent_alignment {
+        let outdir_alignments = args.output_dir.join("parent_alignments");
+        info!("Exporting parent alignments: {outdir_alignments:?}");
+        create_dir_all(&outdir_alignments)?;
+
+        for (_best_match, recombination) in &results {
+            if recombination.recombinant.is_none() {
+                continue;
+            }
+            let fasta = export::parent_alignment(recombination)?;
+            // sequence ids might contain path separators (ex. hCoV-19/...)
+            let file_name = recombination.sequence.id.replace('/', "_");
+            let outpath = outdir_alignments.join(format!("{file_name}.fasta"));
+            let mut file = File::create(&outpath)
+                .wrap_err_with(|| format!("Failed to create file: {outpath:?}"))?;
+            file.write_all(fasta.as_bytes())
+                .wrap_err_with(|| format!("Failed to write file: {outpath:?}"))?;
+        }
+    }
+
+    // ------------------------------------------------------------------------
+    // Export BED (optional)
+
+    if args.emit_bed {
+        let outpath = args.output_dir.join("breakpoints.bed");
+        info!("Exporting breakpoints BED: {outpath:?}");
+        let bed = export::bed(&results, &dataset.reference.id);
+        let mut file = File::create(&outpath)
+            .wrap_err_with(|| format!("Failed to create file: {outpath:?}"))?;
+        file.write_all(bed.as_bytes())
+            .wrap_err_with(|| format!("Failed to write file: {outpath:?}"))?;
+    }
+
+    // ------------------------------------------------------------------------
+    // Export Expected Recombinants (optional)
+
+    if args.emit_expected {
+        let outpath = args.output_dir.join("expected.fasta");
+        info!("Exporting expected recombinants: {outpath:?}");
+        let mut file = File::create(&outpath)
+            .wrap_err_with(|| format!("Failed to create file: {outpath:?}"))?;
+        for (_best_match, recombination) in &results {
+            if recombination.recombinant.is_none() {
+                continue;
+            }
+            let expected = export::expected_recombinant(recombination, &dataset)?;
+            let record = format!(">{}\n{}\n", expected.id, expected.seq.iter().join(""));
+            file.write_all(record.as_bytes())
+                .wrap_err_with(|| format!("Failed to write file: {outpath:?}"))?;
+        }
+    }
+
+    // ------------------------------------------------------------------------
+    // Export Alignment (optional, all queries in reference coordinates)
+
+    if let Some(outpath) = &args.output_alignment {
+        info!("Exporting alignment: {outpath:?}");
+        let sequences = results.iter().map(|(_b, r)| r.sequence).collect_vec();
+        let fasta = export::alignment(&sequences, &dataset.reference);
+        let mut file = File::create(outpath)
+            .wrap_err_with(|| format!("Failed to create file: {outpath:?}"))?;
+        file.write_all(fasta.as_bytes())
+            .wrap_err_with(|| format!("Failed to write file: {outpath:?}"))?;
+    }
+
+    // ------------------------------------------------------------------------
+    // Warning Summary
+
+    let num_low_coverage = results
+        .iter()
+        .filter(|(_b, r)| {
+            let sequence = r.sequence;
+            let genome_length = sequence.genome_length.max(1) as f32;
+            1.0 - (sequence.missing.len() as f32 / genome_length) < args.qc_min_coverage
+        })
+        .count();
+    warnings.count("sequences with low coverage", num_low_coverage);
+    warnings.summary();
+
     info!("Done.");
     Ok(())
 }
+
+/// Read the gapped reference record from an alignment, for --record-insertions.
+///
+/// The record must have the same id as the dataset reference, and the same
+/// bases once gaps are removed.
+pub fn read_alignment_reference(
+    alignment: &Path,
+    reference: &Sequence,
+) -> Result<Sequence, Report> {
+    let mut gapped = None;
+    for result in open_fasta(alignment)?.records() {
+        let record = result
+            .wrap_err_with(|| format!("Unable to parse alignment: {alignment:?}"))?;
+        if record.id() == reference.id {
+            gapped = Some(Sequence::from_record(record, None, &Vec::new())?);
+            break;
+        }
+    }
+    let gapped = gapped.ok_or_else(|| {
+        eyre!(
+            "Reference {} was not found in the alignment: {alignment:?}",
+            reference.id
+        )
+        .suggestion("--record-insertions requires the reference in the alignment.")
+    })?;
+
+    let ungapped = gapped.seq.iter().filter(|b| **b != '-').collect::<String>();
+    if ungapped != reference.seq.iter().collect::<String>() {
+        return Err(eyre!(
+            "Reference {} in the alignment does not match the dataset reference: {alignment:?}",
+            reference.id
+        ));
+    }
+
+    Ok(gapped)
+}
@@ -1,18 +1,95 @@
 pub mod parsimony;
 
+use crate::utils;
 use bio::io::fasta;
 use color_eyre::eyre::{eyre, Report, Result, WrapErr};
 use color_eyre::Help;
+use itertools::Itertools;
+use log::warn;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::default::Default;
+use std::io::{BufReader, Read};
 use std::path::Path;
 use std::str::FromStr;
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+// ----------------------------------------------------------------------------
+// Mutation
+// ----------------------------------------------------------------------------
+
+/// A substitution or deletion, ordered by coordinate then alt base.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub enum Mutation {
-    Substitution,
-    Deletion,
+    Substitution(Substitution),
+    Deletion(Deletion),
+}
+
+impl std::fmt::Display for Mutation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Mutation::Substitution(sub) => write!(f, "{sub}"),
+            Mutation::Deletion(del) => write!(f, "{del}"),
+        }
+    }
+}
+
+impl FromStr for Mutation {
+    type Err = Report;
+
+    /// Parse a mutation (ex. 'A100T' or 'A100-'), a deletion if the alt is '-'.
+    fn from_str(text: &str) -> Result<Self, Report> {
+        let mut chars = text.chars();
+        let (Some(reference), Some(alt)) = (chars.next(), chars.next_back()) else {
+            return Err(eyre!("Failed to parse mutation: {text:?}"));
+        };
+        let coord = chars
+            .as_str()
+            .parse::<usize>()
+            .wrap_err_with(|| eyre!("Failed to parse mutation coordinate: {text:?}"))?;
+
+        let mutation = if alt == '-' {
+            Mutation::Deletion(Deletion {
+                coord,
+                reference,
+                alt,
+            })
+        } else {
+            Mutation::Substitution(Substitution {
+                coord,
+                reference,
+                alt,
+            })
+        };
+        Ok(mutation)
+    }
+}
+
+impl Ord for Mutation {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.coord().cmp(&other.coord()).then(self.alt().cmp(&other.alt()))
+    }
+}
+
+impl PartialOrd for Mutation {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Mutation {
+    pub fn coord(&self) -> usize {
+        match self {
+            Mutation::Substitution(sub) => sub.coord,
+            Mutation::Deletion(del) => del.coord,
+        }
+    }
+
+    pub fn alt(&self) -> char {
+        match self {
+            Mutation::Substitution(sub) => sub.alt,
+            Mutation::Deletion(del) => del.alt,
+        }
+    }
 }
 
 // ----------------------------------------------------------------------------
@@ -26,6 +103,16 @@ pub struct Deletion {
     pub alt: char,
 }
 
+impl Deletion {
+    pub fn to_substitution(&self) -> Substitution {
+        Substitution {
+            coord: self.coord,
+            reference: self.reference,
+            alt: self.alt,
+        }
+    }
+}
+
 impl std::fmt::Display for Deletion {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "{}{}{}", self.reference, self.coord, self.alt)
@@ -113,7 +200,34 @@ impl Substitution {
 }
 
 // ----------------------------------------------------------------------------
-// Substitution
+// Insertion
+// ----------------------------------------------------------------------------
+
+/// Bases inserted after a reference coordinate (0 if before the first base).
+#[derive(Clone, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct Insertion {
+    pub coord: usize,
+    pub bases: String,
+}
+
+impl std::fmt::Display for Insertion {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}", self.coord, self.bases)
+    }
+}
+
+/// How alignment columns with a gap in the reference are handled.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum InsertionMode {
+    /// Skip the columns, coordinates are alignment columns.
+    #[default]
+    Ignore,
+    /// Record the columns as insertions, coordinates are reference bases.
+    Record,
+}
+
+// ----------------------------------------------------------------------------
+// Sequence
 // ----------------------------------------------------------------------------
 
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
@@ -124,7 +238,12 @@ pub struct Sequence {
     pub genome_length: usize,
     pub substitutions: Vec<Substitution>,
     pub deletions: Vec<Deletion>,
+    #[serde(default)]
+    pub insertions: Vec<Insertion>,
     pub missing: Vec<usize>,
+    /// IUPAC ambiguity codes (alt), which are also recorded as missing.
+    #[serde(default)]
+    pub ambiguous: Vec<Substitution>,
 }
 
 impl Sequence {
@@ -136,7 +255,9 @@ impl Sequence {
             genome_length: 0,
             substitutions: Vec::new(),
             deletions: Vec::new(),
+            insertions: Vec::new(),
             missing: Vec::new(),
+            ambiguous: Vec::new(),
         }
     }
 
@@ -144,11 +265,38 @@ impl Sequence {
         record: bio::io::fasta::Record,
         reference: Option<&Sequence>,
         mask: &Vec<usize>,
+    ) -> Result<Self, Report> {
+        Sequence::from_record_with_insertions(
+            record,
+            reference,
+            mask,
+            InsertionMode::default(),
+        )
+    }
+
+    /// Parse a record, optionally recording insertions relative to the reference.
+    ///
+    /// Insertion columns are identified by gaps ('-') in the reference, so the
+    /// reference must be aligned with the record (ex. the reference row of the
+    /// same alignment). With [InsertionMode::Record], the bases of the record in
+    /// those columns are recorded as [Insertion]s, and removed from the
+    /// sequence, so that all other coordinates (and the genome length) are in
+    /// the ungapped reference.
+    pub fn from_record_with_insertions(
+        record: bio::io::fasta::Record,
+        reference: Option<&Sequence>,
+        mask: &Vec<usize>,
+        insertions: InsertionMode,
     ) -> Result<Self, Report> {
         let mut sample = Sequence::new();
         sample.id = record.id().to_string();
         sample.seq = record.seq().iter().map(|b| *b as char).collect();
 
+        // check for degenerate (empty) records, before masking and coordinates
+        if sample.seq.is_empty() {
+            return Err(eyre!("Sequence {} is empty.", sample.id));
+        }
+
         // check mask coord
         for bases in mask {
             if *bases > sample.seq.len() {
@@ -172,27 +320,54 @@ impl Sequence {
                     .suggestion("Are you sure your --alignment is aligned correctly?")
                 );
             }
-            sample.genome_length = reference.seq.len();
+            sample.genome_length = match insertions {
+                InsertionMode::Ignore => reference.seq.len(),
+                InsertionMode::Record => {
+                    reference.seq.iter().filter(|r| **r != '-').count()
+                }
+            };
+            let genome_length = sample.genome_length;
+            let is_masked = |coord: usize| {
+                (!mask.is_empty() && coord <= mask[0])
+                    || (mask.len() == 2 && coord > genome_length.saturating_sub(mask[1]))
+            };
             // Construct iterator to traverse sample and reference bases together
             let it = sample.seq.iter().zip(reference.seq.iter());
+            let mut coord = 0;
             for (i, (s, r)) in it.enumerate() {
-                // Genomic coordinates are 1-based
-                let coord: usize = i + 1;
                 let mut s = *s;
                 let r = *r;
-                // Mask 5' and 3' ends
-                if !mask.is_empty() && coord <= mask[0] {
-                    s = 'N';
+                // Genomic coordinates are 1-based
+                match insertions {
+                    InsertionMode::Ignore => coord = i + 1,
+                    // insertions are between reference bases, after the previous one
+                    InsertionMode::Record if r == '-' => {
+                        if s == '-' || is_masked(coord) {
+                            continue;
+                        }
+                        match sample.insertions.last_mut() {
+                            Some(insertion) if insertion.coord == coord => {
+                                insertion.bases.push(s)
+                            }
+                            _ => sample.insertions.push(Insertion {
+                                coord,
+                                bases: s.to_string(),
+                            }),
+                        }
+                        continue;
+                    }
+                    InsertionMode::Record => coord += 1,
                 }
-                if mask.len() == 2 && coord > sample.genome_length - mask[1] {
+                // Mask 5' and 3' ends
+                if is_masked(coord) {
                     s = 'N';
                 }
 
                 match s {
                     // Missing data (N)
                     'N' => sample.missing.push(coord),
-                    // Reference Missing data (N)
-                    _s if r == 'N' => continue,
+                    // Reference Missing data (N) or gap (-), not in the reference frame
+                    _s if r == 'N' || r == '-' => continue,
                     // Deletion
                     '-' => {
                         let deletion = Deletion {
@@ -204,6 +379,15 @@ impl Sequence {
                     }
                     // Ambiguous data (IUPAC not in alphabet)
                     s if s != r && !sample.alphabet.contains(&s) => {
+                        // retain IUPAC codes, to optionally score compatible bases
+                        if iupac_bases(s).is_some() {
+                            let ambiguous = Substitution {
+                                coord,
+                                reference: r,
+                                alt: s,
+                            };
+                            sample.ambiguous.push(ambiguous);
+                        }
                         sample.missing.push(coord)
                     }
                     // Substitution
@@ -219,34 +403,283 @@ impl Sequence {
                     _ => continue,
                 }
             }
+            // the insertion columns are not in the reference frame
+            if insertions == InsertionMode::Record {
+                sample.seq = sample
+                    .seq
+                    .iter()
+                    .zip(reference.seq.iter())
+                    .filter_map(|(s, r)| (*r != '-').then_some(*s))
+                    .collect();
+            }
         } else {
             sample.genome_length = sample.seq.len();
         }
 
         Ok(sample)
     }
+
+    /// Reconstruct a sequence from its mutations, without comparing every base.
+    ///
+    /// The mutations, missing data, and ambiguity codes must be relative to
+    /// the (unmasked) reference, as from [Sequence::from_record] with no mask.
+    /// They are then masked at the 5' and 3' ends, as if parsed with `mask`.
+    pub fn from_mutations(
+        id: &str,
+        reference: &Sequence,
+        mutations: &[Mutation],
+        missing: &[usize],
+        ambiguous: &[Substitution],
+        mask: &[usize],
+    ) -> Result<Self, Report> {
+        let mut sample = Sequence::new();
+        sample.id = id.to_string();
+        sample.seq = reference.seq.clone();
+        sample.genome_length = reference.seq.len();
+
+        let genome_length = sample.genome_length;
+        let outside = mutations
+            .iter()
+            .map(|m| m.coord())
+            .chain(missing.iter().copied())
+            .chain(ambiguous.iter().map(|a| a.coord))
+            .find(|c| *c == 0 || *c > genome_length);
+        if let Some(coord) = outside {
+            return Err(eyre!(
+                "Coordinate {coord} of {id} is outside the reference ({genome_length})."
+            ));
+        }
+
+        let mask_5p = mask.first().copied().unwrap_or(0).min(genome_length);
+        let mask_3p = mask.get(1).copied().unwrap_or(0).min(genome_length);
+        let is_masked =
+            |coord: usize| coord <= mask_5p || coord > genome_length - mask_3p;
+
+        // the sequence itself is not masked, only the mutations
+        for mutation in mutations {
+            sample.seq[mutation.coord() - 1] = mutation.alt();
+            match mutation {
+                Mutation::Substitution(sub) if !is_masked(sub.coord) => {
+                    sample.substitutions.push(*sub)
+                }
+                Mutation::Deletion(del) if !is_masked(del.coord) => {
+                    sample.deletions.push(*del)
+                }
+                _ => (),
+            }
+        }
+        missing.iter().for_each(|coord| sample.seq[coord - 1] = 'N');
+        for sub in ambiguous {
+            sample.seq[sub.coord - 1] = sub.alt;
+            if !is_masked(sub.coord) {
+                sample.ambiguous.push(*sub);
+            }
+        }
+
+        // masked ends are missing data
+        let masked = (1..=mask_5p).chain(genome_length - mask_3p + 1..=genome_length);
+        sample.missing = missing.iter().copied().chain(masked).sorted().dedup().collect();
+
+        Ok(sample)
+    }
+
+    /// Reconstruct the sequence in reference coordinates.
+    ///
+    /// Substitutions are applied to the reference, deletions become '-' and
+    /// missing data becomes 'N'.
+    pub fn to_reference_frame(&self, reference: &Sequence) -> Vec<char> {
+        let mut seq = reference.seq.clone();
+        // Genomic coordinates are 1-based
+        self.substitutions.iter().for_each(|sub| seq[sub.coord - 1] = sub.alt);
+        self.deletions.iter().for_each(|del| seq[del.coord - 1] = del.alt);
+        self.missing.iter().for_each(|coord| seq[coord - 1] = 'N');
+        seq
+    }
 }
 
 // ----------------------------------------------------------------------------
 // Functions
 // ----------------------------------------------------------------------------
 
-/// Read first record of fasta path into sequence record.
-pub fn read_reference(path: &Path, mask: &Vec<usize>) -> Result<Sequence, Report> {
-    // start reading in the reference as fasta, raise error if file doesn't exist
-    let reader = fasta::Reader::from_file(path).expect("Unable to read reference");
+/// Expand an IUPAC nucleotide code to the bases it represents.
+///
+/// Returns None if the character is not an IUPAC nucleotide code.
+pub fn iupac_bases(code: char) -> Option<&'static [char]> {
+    let bases: &[char] = match code {
+        'A' => &['A'],
+        'C' => &['C'],
+        'G' => &['G'],
+        'T' => &['T'],
+        'R' => &['A', 'G'],
+        'Y' => &['C', 'T'],
+        'S' => &['C', 'G'],
+        'W' => &['A', 'T'],
+        'K' => &['G', 'T'],
+        'M' => &['A', 'C'],
+        'B' => &['C', 'G', 'T'],
+        'D' => &['A', 'G', 'T'],
+        'H' => &['A', 'C', 'T'],
+        'V' => &['A', 'C', 'G'],
+        'N' => &['A', 'C', 'G', 'T'],
+        _ => return None,
+    };
+    Some(bases)
+}
 
-    // parse just the first record from the reference
-    // 1. raise error if record iterator doesn't work
-    // 2. raise error if first record is not proper fasta format.
-    let reference = reader
-        .records()
-        .next()
-        .ok_or_else(|| eyre!("Unable to read reference records: {path:?}"))?
-        .wrap_err_with(|| eyre!("Unable to read first fasta record: {path:?}"))?;
+/// Recognized fasta file extensions, optionally compressed (ex. .fasta.gz).
+pub const FASTA_EXTENSIONS: [&str; 4] = ["fasta", "fa", "fna", "fas"];
+
+/// Open a fasta file for reading, transparently decompressing gzip (.gz) and zstd (.zst).
+///
+/// Files without a recognized fasta extension ([FASTA_EXTENSIONS]) are still
+/// read, with a warning.
+pub fn open_fasta(
+    path: &Path,
+) -> Result<fasta::Reader<BufReader<Box<dyn Read>>>, Report> {
+    // the fasta extension comes before the compression extension
+    let ext =
+        |path: &Path| path.extension().and_then(|ext| ext.to_str()).map(String::from);
+    let fasta_ext = match ext(path).as_deref() {
+        Some("gz") | Some("zst") => ext(&path.with_extension("")),
+        _ => ext(path),
+    }
+    .unwrap_or_default();
+
+    if !FASTA_EXTENSIONS.contains(&fasta_ext.as_str()) {
+        warn!(
+            "File extension {fasta_ext:?} is not a recognized fasta extension ({}): {path:?}",
+            FASTA_EXTENSIONS.join(", ")
+        );
+    }
+
+    Ok(fasta::Reader::new(utils::open_reader(path)?))
+}
+
+/// Read reference record of fasta path into sequence record.
+///
+/// If no id is provided, the first record is used as the reference.
+pub fn read_reference(
+    path: &Path,
+    mask: &Vec<usize>,
+    id: Option<&str>,
+) -> Result<Sequence, Report> {
+    // start reading in the reference as fasta, raise error if file doesn't exist
+    // the reference might be compressed (.gz, .zst)
+    let reader = open_fasta(path)?;
+
+    let reference = if let Some(id) = id {
+        // parse records until we find the requested id
+        let mut ids_seen = Vec::new();
+        let mut reference = None;
+        for result in reader.records() {
+            let record = result
+                .wrap_err_with(|| eyre!("Unable to read fasta record: {path:?}"))?;
+            if record.id() == id {
+                reference = Some(record);
+                break;
+            }
+            ids_seen.push(record.id().to_string());
+        }
+        reference.ok_or_else(|| {
+            eyre!("Reference {id} was not found: {path:?}")
+                .suggestion(format!("Available records: {}", ids_seen.join(", ")))
+        })?
+    } else {
+        // parse just the first record from the reference
+        // 1. raise error if record iterator doesn't work
+        // 2. raise error if first record is not proper fasta format.
+        reader
+            .records()
+            .next()
+            .ok_or_else(|| eyre!("Unable to read reference records: {path:?}"))?
+            .wrap_err_with(|| eyre!("Unable to read first fasta record: {path:?}"))?
+    };
 
     // convert to sequence
     let reference = Sequence::from_record(reference, None, mask)?;
 
     Ok(reference)
 }
+
+/// Minimum length of a query, as a fraction of the reference genome length.
+pub const MIN_LENGTH_FRACTION: f32 = 0.5;
+
+/// Check that a fasta record is a valid nucleotide sequence.
+///
+/// Records that are empty, or far shorter than the reference, are rejected
+/// with a clear message. Other length mismatches with the reference are
+/// checked later by [Sequence::from_record].
+pub fn validate_record(
+    record: &fasta::Record,
+    reference: &Sequence,
+) -> Result<(), Report> {
+    let id = record.id();
+    let length = record.seq().len();
+    if length == 0 {
+        return Err(eyre!("Sequence {id} is empty."));
+    }
+    let min_length = (reference.genome_length as f32 * MIN_LENGTH_FRACTION) as usize;
+    if length < min_length {
+        return Err(eyre!(
+            "Sequence {id} ({length}) is too short compared to the reference ({}).",
+            reference.genome_length
+        )
+        .suggestion("Are you sure your --alignment is aligned correctly?"));
+    }
+    // IUPAC nucleotides, missing data (N), and gaps (-, .)
+    let invalid = record
+        .seq()
+        .iter()
+        .map(|b| (*b as char).to_ascii_uppercase())
+        .filter(|b| !"ACGTURYSWKMBDHVN-.".contains(*b))
+        .unique()
+        .collect_vec();
+    if !invalid.is_empty() {
+        return Err(eyre!(
+            "Sequence {id} has invalid characters: {}",
+            invalid.iter().join(",")
+        ));
+    }
+    Ok(())
+}
+
+/// Scan an alignment once, and collect all problems with its records.
+///
+/// Checks that records are valid ([validate_record]), share the reference
+/// length, and have unique ids. Returns the number of records and a list of
+/// problems (id, reason), rather than failing on the first one.
+pub fn validate_alignment(
+    path: &Path,
+    reference: &Sequence,
+) -> Result<(usize, Vec<(String, String)>), Report> {
+    let reader = open_fasta(path)?;
+
+    let mut num_records = 0;
+    let mut ids_seen = std::collections::HashSet::new();
+    let mut problems = Vec::new();
+
+    for result in reader.records() {
+        let record =
+            result.wrap_err_with(|| format!("Unable to parse alignment: {path:?}"))?;
+        num_records += 1;
+        let id = record.id().to_string();
+
+        if !ids_seen.insert(id.clone()) {
+            problems.push((id.clone(), format!("Sequence {id} is duplicated.")));
+        }
+        if let Err(e) = validate_record(&record, reference) {
+            problems.push((id, e.to_string()));
+        } else if record.seq().len() != reference.seq.len() {
+            problems.push((
+                id.clone(),
+                format!(
+                    "Reference sequence ({}) and {id} ({}) are different lengths!",
+                    reference.seq.len(),
+                    record.seq().len()
+                ),
+            ));
+        }
+    }
+
+    Ok((num_records, problems))
+}
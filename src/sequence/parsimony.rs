@@ -1,4 +1,4 @@
-use crate::sequence::{Sequence, Substitution};
+use crate::sequence::{iupac_bases, Sequence, Substitution};
 use color_eyre::eyre::{Report, Result};
 use indoc::formatdoc;
 use itertools::Itertools;
@@ -27,10 +27,20 @@ impl Summary {
     }
 
     /// Summarize support and conflicts between two sequences.
+    ///
+    /// If `deletions` is true, deletions are scored like substitutions (alt '-'),
+    /// otherwise coordinates with deletions in the sequence are excluded.
+    ///
+    /// If `ambiguity` is true, IUPAC ambiguity codes in the sequence are expanded:
+    /// a code compatible with the query base is support, a code compatible with
+    /// neither the query nor reference base is conflict. Otherwise (and always
+    /// for 'N') ambiguous coordinates are missing and excluded.
     pub fn from_sequence(
         sequence: &Sequence,
         query: &Sequence,
         coordinates: Option<&[usize]>,
+        deletions: bool,
+        ambiguity: bool,
     ) -> Result<Self, Report> {
         let mut parsimony_summary = Summary::new();
 
@@ -38,12 +48,24 @@ impl Summary {
         let mut seq_subs = sequence.substitutions.clone();
         //println!("seq_subs: {}", seq_subs.iter().join(","));
         // exclude coordinates that are in the sequence missing or deletions
-        let mut exclude_coordinates =
-            sequence.deletions.iter().map(|d| d.coord).collect_vec();
-        exclude_coordinates.extend(sequence.missing.clone());
+        let mut exclude_coordinates = sequence.missing.clone();
+        if deletions {
+            seq_subs.extend(sequence.deletions.iter().map(|d| d.to_substitution()));
+        } else {
+            exclude_coordinates.extend(sequence.deletions.iter().map(|d| d.coord));
+        }
+        // ambiguous coordinates are scored separately, rather than as missing
+        let mut ambiguous = Vec::new();
+        if ambiguity {
+            ambiguous = sequence.ambiguous.clone();
+            exclude_coordinates.retain(|c| !ambiguous.iter().any(|a| a.coord == *c));
+        }
         // get all the substitutions found in this query
         // exclude missing and deletion coordinates
         let mut query_subs = query.substitutions.clone();
+        if deletions {
+            query_subs.extend(query.deletions.iter().map(|d| d.to_substitution()));
+        }
         query_subs.retain(|s| !exclude_coordinates.contains(&s.coord));
         //println!("query_subs: {}", query_subs.iter().join(","));
 
@@ -51,6 +73,7 @@ impl Summary {
         if let Some(coordinates) = coordinates {
             query_subs.retain(|sub| coordinates.contains(&sub.coord));
             seq_subs.retain(|sub| coordinates.contains(&sub.coord));
+            ambiguous.retain(|sub| coordinates.contains(&sub.coord));
         }
 
         // support: sub in seq that is also in query
@@ -63,9 +86,29 @@ impl Summary {
             }
         });
 
-        // conflict_ref: sub in query that is not in seq
-        parsimony_summary.conflict_ref =
-            query_subs.into_iter().filter(|sub| !seq_subs.contains(sub)).collect_vec();
+        // ambiguous: support if compatible with the query sub, conflict_alt if
+        // compatible with neither the query nor the reference base
+        ambiguous.iter().for_each(|amb| {
+            let bases = iupac_bases(amb.alt).unwrap_or_default();
+            match query_subs.iter().find(|sub| sub.coord == amb.coord) {
+                Some(sub) if bases.contains(&sub.alt) => {
+                    parsimony_summary.support.push(*sub)
+                }
+                Some(_) => {}
+                None if !bases.contains(&amb.reference) => {
+                    parsimony_summary.conflict_alt.push(*amb)
+                }
+                None => {}
+            }
+        });
+
+        // conflict_ref: sub in query that is not in seq (or a compatible ambiguity)
+        parsimony_summary.conflict_ref = query_subs
+            .into_iter()
+            .filter(|sub| {
+                !seq_subs.contains(sub) && !parsimony_summary.support.contains(sub)
+            })
+            .collect_vec();
 
         // score: support - conflict_alt - conflict_ref
         // why did we previously use only conflict_ref and not conflict_alt?
@@ -20,7 +20,7 @@ pub fn simulate(args: &cli::simulate::Args) -> Result<(), Report> {
     // Load dataset, disable masking
     info!("Loading dataset: {:?}", &args.dataset_dir);
     let mask = vec![0, 0];
-    let dataset = dataset::load::dataset(&args.dataset_dir, &mask)?;
+    let dataset = dataset::load::dataset(&args.dataset_dir, &mask, None, false)?;
     let genome_length = dataset.reference.genome_length;
 
     // Check to make sure all parents are in dataset
@@ -0,0 +1,60 @@
+use color_eyre::eyre::Report;
+use std::fmt;
+
+// ----------------------------------------------------------------------------
+// Exit Code
+
+/// Machine-readable exit codes, by category of error.
+///
+/// Errors can be tagged with a category as context (ex. `.wrap_err(Code::Usage)`),
+/// otherwise the category is inferred from the underlying error type.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Code {
+    /// Unclassified error.
+    General = 1,
+    /// Invalid arguments or input data.
+    Usage = 2,
+    /// Failure to read or write files.
+    Io = 3,
+    /// Network failure, including rate limits.
+    Network = 4,
+    /// The run completed, but produced no results.
+    EmptyResult = 5,
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let result = match self {
+            Code::General => "Error",
+            Code::Usage => "Invalid input",
+            Code::Io => "File input/output error",
+            Code::Network => "Network error",
+            Code::EmptyResult => "No results",
+        };
+        write!(f, "{result} (exit code {})", *self as i32)
+    }
+}
+
+impl std::error::Error for Code {}
+
+// ----------------------------------------------------------------------------
+// Functions
+
+/// Classify an error report into an exit code.
+pub fn classify(report: &Report) -> Code {
+    // explicitly tagged categories
+    if let Some(code) = report.downcast_ref::<Code>() {
+        return *code;
+    }
+    // otherwise, infer from the underlying error types
+    for cause in report.chain() {
+        if let Some(code) = cause.downcast_ref::<Code>() {
+            return *code;
+        } else if cause.downcast_ref::<reqwest::Error>().is_some() {
+            return Code::Network;
+        } else if cause.downcast_ref::<std::io::Error>().is_some() {
+            return Code::Io;
+        }
+    }
+    Code::General
+}
@@ -1,22 +1,29 @@
+pub mod exit;
 pub mod remote_file;
 pub mod table;
+pub mod warnings;
 
 use crate::dataset::attributes::Tag;
 use crate::utils::remote_file::RemoteFile;
 use chrono::prelude::*;
 use color_eyre::eyre::{eyre, Report, Result, WrapErr};
 use color_eyre::Help;
+use flate2::read::MultiGzDecoder;
 use itertools::Itertools;
 use log::{debug, warn};
 use reqwest::header::{ACCESS_CONTROL_EXPOSE_HEADERS, USER_AGENT};
 use std::collections::BTreeMap;
 use std::fs::{remove_file, write, File};
-use std::io::Read;
+use std::io::{BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tempfile::TempDir;
 //use zip::ZipArchive;
 use zstd::stream::read::Decoder;
 
+/// Maximum number of attempts to download a file.
+pub const DOWNLOAD_RETRIES: u32 = 3;
+
 /// Download file from url to path, with optional decompression.
 pub async fn download_file(
     url: &str,
@@ -25,13 +32,33 @@ pub async fn download_file(
 ) -> Result<(), Report> {
     let ext = Path::new(&url).extension().unwrap().to_str().unwrap();
 
-    let response = reqwest::get(url).await?;
-    if response.status() != 200 {
-        return Err(eyre!(
-            "Unable to download file: {url}\nStatus code {}.",
-            response.status()
-        ));
-    }
+    // retry transient failures (connection errors, server errors, rate limits)
+    // with exponential backoff, but fail immediately on client errors (ex. 404)
+    let mut attempt = 1;
+    let response = loop {
+        let (retry, error) = match reqwest::get(url).await {
+            Ok(response) if response.status() == 200 => break response,
+            Ok(response) => {
+                let status = response.status();
+                let retry = status.is_server_error()
+                    || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+                let error =
+                    eyre!("Unable to download file: {url}\nStatus code {status}.");
+                (retry, error)
+            }
+            Err(e) => (
+                true,
+                eyre!(e).wrap_err(format!("Unable to download file: {url}")),
+            ),
+        };
+        if !retry || attempt >= DOWNLOAD_RETRIES {
+            return Err(error);
+        }
+        let backoff = Duration::from_secs(2_u64.pow(attempt));
+        warn!("Download attempt {attempt}/{DOWNLOAD_RETRIES} failed, retrying in {backoff:?}: {url}");
+        tokio::time::sleep(backoff).await;
+        attempt += 1;
+    };
 
     if decompress {
         // Write bytes to a tmp file
@@ -79,7 +106,8 @@ pub fn check_github_response(response: &reqwest::Response) -> Result<(), Report>
                     .into();
 
             return Err(
-                eyre!("GitHub API rate limit has been exceeded.")
+                eyre!(exit::Code::Network)
+                .wrap_err("GitHub API rate limit has been exceeded.")
                 .suggestion(format!("Please wait for the rate limit to reset at: {rate_limit_reset:?}"))
                 .suggestion("Alternatively, set the environment variables GITHUB_USERNAME and GITHUB_TOKEN.")
                 .suggestion("https://docs.github.com/en/authentication/keeping-your-account-and-data-secure/managing-your-personal-access-tokens")
@@ -88,24 +116,37 @@ pub fn check_github_response(response: &reqwest::Response) -> Result<(), Report>
         // --------------------------------------------------------------------
         // Other unhandled errors
         else {
-            return Err(eyre!(
+            return Err(eyre!(exit::Code::Network).wrap_err(format!(
                 "GitHub query had status code {status}: {url}",
                 status = response.status()
-            ));
+            )));
         }
     }
 
     Ok(())
 }
 
-/// Query and download files using the GitHub API
-pub async fn download_github(
+/// A GitHub commit, resolved for a repository and dataset tag.
+#[derive(Clone, Debug)]
+pub struct GithubCommit {
+    pub sha: String,
+    pub date_created: DateTime<Utc>,
+}
+
+/// Resolved GitHub commits, keyed by repository and tag.
+///
+/// Files downloaded from the same repository share a single commit, which
+/// cuts down on API calls and ensures all files are from a consistent snapshot.
+pub type GithubCommits = BTreeMap<(String, String), GithubCommit>;
+
+/// Resolve the most recent commit of a GitHub repository for a dataset tag.
+///
+/// Optionally restrict to commits which modified a remote path.
+pub async fn github_commit(
     repo: &str,
     tag: &Tag,
-    remote_path: &str,
-    output_path: &Path,
-    sha: &Option<String>,
-) -> Result<RemoteFile, Report> {
+    remote_path: Option<&str>,
+) -> Result<GithubCommit, Report> {
     // GitHub API Setup
     let github_token: Option<String> = std::env::var("GITHUB_TOKEN").ok();
     let github_username = std::env::var("GITHUB_USERNAME").unwrap_or("".to_string());
@@ -114,23 +155,15 @@ pub async fn download_github(
     let client = reqwest::Client::new();
 
     // GitHub API Query
-    let mut query = vec![("path", remote_path), ("per_page", "1"), ("page", "1")];
-    // if manual SHA was specified
-    if let Some(sha) = sha {
-        query.push(("sha", sha));
+    let mut query = vec![("per_page", "1"), ("page", "1")];
+    if let Some(remote_path) = remote_path {
+        query.push(("path", remote_path));
     }
 
     // convert to string
     let mut query =
         query.into_iter().map(|(k, v)| (String::from(k), String::from(v))).collect_vec();
 
-    // Note: the reqwest::RequestBuilder doesn't implement Clone for the
-    // non-blocking (asynchronous) version :( we're going to have to full
-    // define the request over and over again.
-
-    // --------------------------------------------------------------------------
-    // STEP 1: Archive Pagination
-
     // Use the Archive Tag as a maximum date filter (&until=...)
     if matches!(tag, Tag::Archive(_)) {
         query.push(("until".to_string(), tag.to_string()));
@@ -153,20 +186,49 @@ pub async fn download_github(
     if body.is_empty() {
         return Err(eyre!("No GitHub commits were found for: {}", url)
             .suggestion(format!(
-                "Does your dataset tag ({tag}) predate the creation of this file?"
+                "Does your dataset tag ({tag}) predate the creation of this repository?"
             ))
-            .suggestion(format!(
-                "Repository: https://github.com/{repo}, File: {remote_path:?}"
-            )));
+            .suggestion(format!("Repository: https://github.com/{repo}")));
     }
 
     let sha = body[0]["sha"].to_string().replace('"', "");
     let commit_date = body[0]["commit"]["author"]["date"].to_string().replace('"', "");
     let date_created: DateTime<Utc> = DateTime::parse_from_rfc3339(&commit_date)?.into();
 
+    Ok(GithubCommit { sha, date_created })
+}
+
+/// Query and download files using the GitHub API
+///
+/// The commit is resolved once per repository and tag, and then reused from
+/// `commits` for all other files in the same repository.
+pub async fn download_github(
+    repo: &str,
+    tag: &Tag,
+    remote_path: &str,
+    output_path: &Path,
+    commits: &mut GithubCommits,
+) -> Result<RemoteFile, Report> {
+    // --------------------------------------------------------------------------
+    // STEP 1: Resolve Commit
+
+    let key = (repo.to_string(), tag.to_string());
+    let commit = match commits.get(&key) {
+        Some(commit) => {
+            debug!("Using cached commit for {repo} {tag}: {}", commit.sha);
+            commit.clone()
+        }
+        None => {
+            let commit = github_commit(repo, tag, None).await?;
+            commits.insert(key, commit.clone());
+            commit
+        }
+    };
+
     // --------------------------------------------------------------------------
     // STEP 2: DOWNLOAD
 
+    let sha = commit.sha;
     let download_url =
         format!("https://raw.githubusercontent.com/{repo}/{sha}/{remote_path}");
 
@@ -177,14 +239,21 @@ pub async fn download_github(
 
     // Download the file
     debug!("Downloading file: {download_url} to {output_path:?}");
-    download_file(&download_url, output_path, decompress).await?;
+    download_file(&download_url, output_path, decompress)
+        .await
+        .suggestion(format!(
+            "Does your dataset tag ({tag}) predate the creation of this file?"
+        ))
+        .suggestion(format!(
+            "Repository: https://github.com/{repo}, File: {remote_path:?}"
+        ))?;
 
     // Store all the information about the remote file for the dataset summary
     let remote_file = RemoteFile {
         url: download_url,
         sha,
         local_path: output_path.to_path_buf(),
-        date_created,
+        date_created: commit.date_created,
         date_downloaded: Utc::now(),
     };
     debug!("Downloaded file: {remote_file:?}");
@@ -200,12 +269,18 @@ pub fn decompress_file(input: &Path, output: &Path, inplace: bool) -> Result<(),
 
     match ext.to_str().unwrap() {
         "zst" => {
-            let reader = File::open(input)?;
+            // stream decode to disk in chunks, rather than into memory
+            let reader = File::open(input)
+                .wrap_err_with(|| format!("Unable to read file: {input:?}"))?;
             let mut decoder = Decoder::new(reader)?;
-            let mut buffer = String::new();
-            decoder.read_to_string(&mut buffer)?;
-            write(output, buffer)
-                .wrap_err(format!("Unable to write file: {:?}", output))?;
+            let file = File::create(output)
+                .wrap_err_with(|| format!("Unable to create file: {output:?}"))?;
+            let mut writer = BufWriter::new(file);
+            std::io::copy(&mut decoder, &mut writer)
+                .wrap_err_with(|| format!("Unable to decompress file: {input:?}"))?;
+            writer
+                .flush()
+                .wrap_err_with(|| format!("Unable to write file: {output:?}"))?;
 
             if inplace {
                 remove_file(input)?;
@@ -223,6 +298,53 @@ pub fn decompress_file(input: &Path, output: &Path, inplace: bool) -> Result<(),
     Ok(())
 }
 
+/// Open a file for reading, transparently decompressing gzip (.gz) and zstd (.zst).
+pub fn open_reader(path: &Path) -> Result<Box<dyn Read>, Report> {
+    let file =
+        File::open(path).wrap_err_with(|| eyre!("Failed to read file: {path:?}"))?;
+    let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+    let reader: Box<dyn Read> = match ext {
+        "gz" => Box::new(MultiGzDecoder::new(file)),
+        "zst" => Box::new(
+            Decoder::new(file)
+                .wrap_err_with(|| eyre!("Failed to decompress file: {path:?}"))?,
+        ),
+        _ => Box::new(file),
+    };
+    Ok(reader)
+}
+
+/// Get the SHA-256 (hex) of a file, decompressed like [open_reader].
+pub fn sha256(path: &Path) -> Result<String, Report> {
+    let mut reader = open_reader(path)?;
+    let mut hasher = openssl::sha::Sha256::new();
+    let mut buffer = [0; 8192];
+    loop {
+        let n = reader
+            .read(&mut buffer)
+            .wrap_err_with(|| eyre!("Failed to read file: {path:?}"))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(hasher.finish().iter().map(|b| format!("{b:02x}")).join(""))
+}
+
+/// Find a file, or its compressed equivalent (.gz, .zst) if it doesn't exist.
+///
+/// Falls back to the original path, so that missing files are reported as-is.
+pub fn find_compressed(path: &Path) -> PathBuf {
+    if path.exists() {
+        return path.to_path_buf();
+    }
+    ["gz", "zst"]
+        .into_iter()
+        .map(|ext| PathBuf::from(format!("{}.{ext}", path.display())))
+        .find(|path| path.exists())
+        .unwrap_or(path.to_path_buf())
+}
+
 pub fn ext_to_delim(ext: &str) -> Result<char, Report> {
     let delim = match ext {
         "tsv" => '\t',
@@ -242,7 +364,12 @@ pub fn ext_to_delim(ext: &str) -> Result<char, Report> {
 
 pub fn path_to_delim(path: &Path) -> Result<char, Report> {
     // get the path extension
-    let ext = path_to_ext(path)?;
+    let mut ext = path_to_ext(path)?;
+
+    // compressed files, use the extension before .gz (ex. .tsv.gz)
+    if ext == "gz" {
+        ext = path_to_ext(&path.with_extension(""))?;
+    }
 
     // convert extension to the expected delimiter
     let delim = match ext.as_str() {
@@ -261,6 +388,21 @@ pub fn path_to_delim(path: &Path) -> Result<char, Report> {
     Ok(delim)
 }
 
+/// Get the file stem of a path, excluding any .gz compression extension.
+pub fn path_to_stem(path: &Path) -> Result<String, Report> {
+    let path = if path_to_ext(path)? == "gz" {
+        path.with_extension("")
+    } else {
+        path.to_path_buf()
+    };
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| eyre!("Unable to parse file stem from file: {path:?}"))?;
+
+    Ok(stem.to_string())
+}
+
 pub fn path_to_ext(path: &Path) -> Result<String, Report> {
     let result = path.extension();
     let ext = match result {
@@ -1,15 +1,20 @@
 use crate::utils;
 use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 use std::default::Default;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Write};
+use std::io::{BufRead, BufReader, Read, Write};
 use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Table {
     pub headers: Vec<String>,
     pub rows: Vec<Vec<String>>,
+    #[serde(skip)]
     pub path: PathBuf,
 }
 
@@ -37,8 +42,14 @@ impl Table {
         // attempt to open the file path
         let file =
             File::open(path).wrap_err_with(|| eyre!("Failed to read file: {path:?}"))?;
+        // transparently decompress gzip files (ex. .tsv.gz)
+        let reader: Box<dyn Read> = if utils::path_to_ext(path)? == "gz" {
+            Box::new(GzDecoder::new(file))
+        } else {
+            Box::new(file)
+        };
         // read in the lines
-        let lines = BufReader::new(file).lines();
+        let lines = BufReader::new(reader).lines();
         //.map_err(|e| eyre!(e))
         //.wrap_err_with(|| eyre!("Failed to parse file: {path:?}"))?;
 
@@ -86,6 +97,8 @@ impl Table {
     }
 
     /// write to file
+    ///
+    /// If the path ends in .gz (ex. .tsv.gz), the output is gzip compressed.
     pub fn write(&self, path: &Path) -> Result<(), Report> {
         let mut file = File::create(path)
             .wrap_err_with(|| format!("Unable to create file: {path:?}"))?;
@@ -93,15 +106,36 @@ impl Table {
         // Parse line delimiter from file extension
         let delim = utils::path_to_delim(path)?.to_string();
 
+        // optionally wrap the file in a gzip encoder
+        let compress = utils::path_to_ext(path)? == "gz";
+        if compress {
+            let mut encoder = GzEncoder::new(file, Compression::default());
+            self.write_lines(&mut encoder, &delim)?;
+            // finish explicitly, so that errors writing the gzip footer surface
+            encoder
+                .finish()
+                .wrap_err_with(|| format!("Unable to write file: {path:?}"))?;
+        } else {
+            self.write_lines(&mut file, &delim)?;
+            file.flush().wrap_err_with(|| format!("Unable to write file: {path:?}"))?;
+        }
+
+        Ok(())
+    }
+
+    /// write headers and rows, delimited, to a writer
+    fn write_lines(&self, writer: &mut dyn Write, delim: &str) -> Result<(), Report> {
         // write headers
-        let line = format!("{}\n", self.headers.iter().join(&delim));
-        file.write_all(line.as_bytes())
+        let line = format!("{}\n", self.headers.iter().join(delim));
+        writer
+            .write_all(line.as_bytes())
             .wrap_err_with(|| format!("Unable to write table headers: {line}"))?;
 
         // write regular rows
         for row in &self.rows {
-            let line = format!("{}\n", row.iter().join(&delim));
-            file.write_all(line.as_bytes())
+            let line = format!("{}\n", row.iter().join(delim));
+            writer
+                .write_all(line.as_bytes())
                 .wrap_err_with(|| format!("Unable to write table rows: {line}"))?;
         }
 
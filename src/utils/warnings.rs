@@ -0,0 +1,58 @@
+use log::warn;
+use std::collections::BTreeMap;
+
+// ----------------------------------------------------------------------------
+// Warnings
+
+/// Collect warnings by category, to keep logs readable on large or bad inputs.
+///
+/// The first `max` warnings of each category are logged as usual, the rest are
+/// only counted. A one-line summary of all categories is logged at the end.
+#[derive(Clone, Debug, Default)]
+pub struct Warnings {
+    /// Maximum number of warnings to log per category.
+    pub max: usize,
+    /// Number of warnings per category (ex. "duplicate IDs dropped").
+    pub counts: BTreeMap<String, usize>,
+}
+
+impl Warnings {
+    pub fn new(max: usize) -> Self {
+        Warnings {
+            max,
+            counts: BTreeMap::new(),
+        }
+    }
+
+    /// Log a warning, unless this category has already reached the maximum.
+    pub fn warn(&mut self, category: &str, message: &str) {
+        let count = self.counts.entry(category.to_string()).or_insert(0);
+        *count += 1;
+        if *count <= self.max {
+            warn!("{message}");
+        } else if *count == self.max + 1 {
+            warn!("... (Additional warnings ommitted: {category})");
+        }
+    }
+
+    /// Count a warning without logging it, it will only appear in the summary.
+    pub fn count(&mut self, category: &str, n: usize) {
+        if n > 0 {
+            *self.counts.entry(category.to_string()).or_insert(0) += n;
+        }
+    }
+
+    /// Log a one-line summary of warnings across all categories.
+    pub fn summary(&self) {
+        if self.counts.is_empty() {
+            return;
+        }
+        let summary = self
+            .counts
+            .iter()
+            .map(|(category, count)| format!("{count} {category}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        warn!("Warning summary: {summary}.");
+    }
+}
@@ -1,13 +1,40 @@
+use rebar::benchmark::benchmark;
 use rebar::cli;
-use rebar::dataset::attributes::{Name, Tag};
-use rebar::dataset::download;
+use rebar::dataset::attributes::{Name, Summary, Tag};
+use rebar::dataset::{download, load};
+use rebar::phylogeny::Phylogeny;
 use rebar::plot::plot;
+use rebar::plot::tree::plot_tree;
+use rebar::recombination::{
+    self, Breakpoint, Direction, Hypothesis, PrivatePolicy, Region,
+};
 use rebar::run::run;
+use rebar::sequence::{
+    open_fasta, parsimony, read_reference, InsertionMode, Sequence, Substitution,
+};
+use rebar::utils::{table::Table, warnings::Warnings};
 
 use color_eyre::eyre::{Report, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
 use std::path::PathBuf;
 use std::str::FromStr;
 
+/// Run arguments for the local toy1 fixture, no network access required.
+fn toy1_args(name: &str) -> cli::run::Args {
+    cli::run::Args {
+        input: cli::run::Input {
+            populations: Some(vec!["*".to_string()]),
+            ..Default::default()
+        },
+        dataset_dir: PathBuf::from("tests").join("data").join("toy1"),
+        output_dir: PathBuf::from("output").join("tests").join(name),
+        mask: vec![0, 0],
+        min_length: 3,
+        ..Default::default()
+    }
+}
+
 #[tokio::test]
 async fn toy1() -> Result<(), Report> {
     let output_dir = PathBuf::from("output").join("tests").join("toy1");
@@ -28,6 +55,7 @@ async fn toy1() -> Result<(), Report> {
         output_dir: output_dir.join("run"),
         mask: vec![0, 0],
         min_length: 3,
+        plot_bundle: true,
         ..Default::default()
     };
     run(&mut args)?;
@@ -35,11 +63,1196 @@ async fn toy1() -> Result<(), Report> {
     // Plot
     let args = cli::plot::Args {
         annotations: Some(output_dir.join("dataset").join("annotations.tsv")),
-        run_dir: output_dir.join("run"),
+        run_dir: Some(output_dir.join("run")),
+        ..Default::default()
+    };
+    plot(&args)?;
+
+    // Plot from bundle, without the run or dataset dir
+    let args = cli::plot::Args {
+        bundle: Some(output_dir.join("run").join("plot_bundle.json")),
+        output_dir: Some(output_dir.join("plots_bundle")),
+        ..Default::default()
+    };
+    plot(&args)?;
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1() -> Result<(), Report> {
+    // Run
+    let mut args = cli::run::Args {
+        alternative_margin: Some(100),
+        features: true,
+        ..toy1_args("fixture_toy1")
+    };
+    let output_dir = args.output_dir.clone();
+    run(&mut args)?;
+
+    // Linelist
+    let linelist = Table::read(&output_dir.join("linelist.tsv"))?;
+    let recombinant_i = linelist.header_position("recombinant")?;
+    let parents_i = linelist.header_position("parents")?;
+    let breakpoints_i = linelist.header_position("breakpoints")?;
+    let alternative_i = linelist.header_position("alternative_call")?;
+
+    // D is a recombinant of A and B, with adjacent parental regions
+    let row = &linelist.filter("strain", "population_D")?.rows[0];
+    assert_eq!(row[recombinant_i], "D");
+    assert_eq!(row[parents_i], "A,B");
+    assert_eq!(row[breakpoints_i], "12-12");
+    assert_eq!(
+        row[linelist.header_position("hypothesis")?],
+        "designated_recombinant"
+    );
+    // A (1-11) and B (12-20) span 11 and 9 of the 20 bases
+    assert_eq!(
+        row[linelist.header_position("parent_fractions")?],
+        "A:0.55,B:0.45"
+    );
+    // the alternative is the non-recombinant best match
+    assert!(row[alternative_i].starts_with("D:"));
+
+    // A is not a recombinant, and is a good match to itself
+    let row = &linelist.filter("strain", "population_A")?.rows[0];
+    assert_eq!(row[recombinant_i], "");
+    assert_eq!(row[parents_i], "");
+    assert_eq!(row[linelist.header_position("parent_fractions")?], "");
+    assert_eq!(row[linelist.header_position("low_quality_match")?], "false");
+    assert_eq!(
+        row[linelist.header_position("hypothesis")?],
+        "non_recombinant"
+    );
+
+    // Barcodes
+    let barcodes_path = output_dir.join("barcodes").join("D_A_B_12-12.tsv");
+    assert!(barcodes_path.exists());
+
+    // Features, the parents of D have regions
+    let features = Table::read(&output_dir.join("features.tsv"))?;
+    let features = features.filter("strain", "population_D")?;
+    let candidate_i = features.header_position("candidate")?;
+    let is_parent_i = features.header_position("is_parent")?;
+    let num_regions_i = features.header_position("num_regions")?;
+    let parents = features
+        .rows
+        .iter()
+        .filter(|row| row[is_parent_i] == "true")
+        .map(|row| {
+            assert_ne!(row[num_regions_i], "0");
+            row[candidate_i].as_str()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(parents, ["A", "B"]);
+
+    // Dataset provenance, the fixture has no summary so it's a custom dataset
+    let provenance = Summary::read(&output_dir.join("dataset_provenance.json"))?;
+    assert_eq!(provenance.name, Name::Custom);
+    assert_eq!(provenance.tag, Tag::Custom);
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_summary() -> Result<(), Report> {
+    let mut args = cli::run::Args {
+        group_by_parents: true,
+        summary: true,
+        ..toy1_args("fixture_toy1_summary")
+    };
+    let output_dir = args.output_dir.clone();
+    run(&mut args)?;
+
+    // D is the only recombinant of A and B
+    let parent_pairs = Table::read(&output_dir.join("parent_pairs.tsv"))?;
+    let row = &parent_pairs.filter("parents", "A+B")?.rows[0];
+    assert_eq!(row[parent_pairs.header_position("count")?], "1");
+    assert_eq!(
+        row[parent_pairs.header_position("breakpoints_range")?],
+        "12-12"
+    );
+    assert_eq!(
+        row[parent_pairs.header_position("strains")?],
+        "population_D"
+    );
+
+    // every population is a sample, and classified as itself
+    let summary = std::fs::read_to_string(output_dir.join("summary.json"))?;
+    let summary: serde_json::Value = serde_json::from_str(&summary)?;
+    assert_eq!(summary["num_samples"], 5);
+    assert_eq!(summary["num_classified"], 5);
+    assert_eq!(summary["parents"]["A+B"], 1);
+    for population in ["A", "B", "C", "D", "E"] {
+        assert_eq!(summary["populations"][population], 1);
+    }
+    let summary = std::fs::read_to_string(output_dir.join("summary.txt"))?;
+    assert!(summary.contains("samples: 5\n"));
+    assert!(summary.contains("  A+B: 1\n"));
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_limit_sample() -> Result<(), Report> {
+    // strains in the linelist of a run
+    let strains = |args: &mut cli::run::Args| -> Result<Vec<String>, Report> {
+        run(args)?;
+        let linelist = Table::read(&args.output_dir.join("linelist.tsv"))?;
+        let strain_i = linelist.header_position("strain")?;
+        Ok(linelist.rows.iter().map(|row| row[strain_i].clone()).collect())
+    };
+
+    // only the first queries
+    let mut args = cli::run::Args {
+        limit: Some(2),
+        ..toy1_args("fixture_toy1_limit_sample/limit")
+    };
+    assert_eq!(strains(&mut args)?.len(), 2);
+
+    // a seeded sample is reproducible
+    let mut args = cli::run::Args {
+        sample: Some(0.6),
+        seed: Some(1),
+        ..toy1_args("fixture_toy1_limit_sample/sample")
+    };
+    let sample = strains(&mut args)?;
+    assert_eq!(sample.len(), 3);
+    assert_eq!(strains(&mut args)?, sample);
+
+    // and applied after the limit
+    args.limit = Some(4);
+    args.sample = Some(0.5);
+    assert_eq!(strains(&mut args)?.len(), 2);
+
+    // the sample is a fraction
+    args.sample = Some(1.5);
+    assert!(run(&mut args).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_no_phylogeny() -> Result<(), Report> {
+    // Run
+    let mut args = cli::run::Args {
+        no_phylogeny: true,
+        ..toy1_args("fixture_toy1_no_phylogeny")
+    };
+    let output_dir = args.output_dir.clone();
+    run(&mut args)?;
+
+    // Linelist
+    let linelist = Table::read(&output_dir.join("linelist.tsv"))?;
+    let population_i = linelist.header_position("population")?;
+    let validate_i = linelist.header_position("validate")?;
+
+    // best match is the top population, validation requires a phylogeny
+    let row = &linelist.filter("strain", "population_D")?.rows[0];
+    assert_eq!(row[population_i], "D");
+    assert_eq!(row[validate_i], "");
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_subthreshold() -> Result<(), Report> {
+    // Run, with a min_subs filter that no parent can satisfy
+    let mut args = cli::run::Args {
+        min_subs: 100,
+        report_subthreshold: true,
+        ..toy1_args("fixture_toy1_subthreshold")
+    };
+    let output_dir = args.output_dir.clone();
+    run(&mut args)?;
+
+    // D is no longer a recombinant, but its rejected parents are reported
+    let subthreshold = Table::read(&output_dir.join("subthreshold.tsv"))?;
+    let parents_i = subthreshold.header_position("parents")?;
+    let reason_i = subthreshold.header_position("reason")?;
+    let row = &subthreshold.filter("strain", "population_D")?.rows[0];
+    let mut parents = row[parents_i].split(',').collect::<Vec<_>>();
+    parents.sort();
+    assert_eq!(parents, ["A", "B"]);
+    assert!(row[reason_i].contains("min_subs"));
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_outputs() -> Result<(), Report> {
+    // Run, writing only the linelist
+    let mut args = cli::run::Args {
+        outputs: vec![cli::run::Output::Linelist],
+        ..toy1_args("fixture_toy1_outputs")
+    };
+    let output_dir = args.output_dir.clone();
+    // start clean, so artifacts from previous runs don't linger
+    if output_dir.exists() {
+        std::fs::remove_dir_all(&output_dir)?;
+    }
+    run(&mut args)?;
+
+    assert!(output_dir.join("linelist.tsv").exists());
+    assert!(!output_dir.join("run_args.json").exists());
+    assert!(!output_dir.join("dataset_provenance.json").exists());
+    assert!(!output_dir.join("barcodes").exists());
+    assert!(!output_dir.join("recombinations.json").exists());
+    assert!(!output_dir.join("summary.json").exists());
+    assert!(!output_dir.join("summary.txt").exists());
+
+    // the report is only written when selected
+    args.outputs = vec![cli::run::Output::Report];
+    std::fs::remove_dir_all(&output_dir)?;
+    run(&mut args)?;
+
+    assert!(output_dir.join("summary.json").exists());
+    assert!(output_dir.join("summary.txt").exists());
+    assert!(!output_dir.join("linelist.tsv").exists());
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_recombinations() -> Result<(), Report> {
+    let mut args = cli::run::Args {
+        input: cli::run::Input {
+            populations: Some(vec!["A".to_string(), "D".to_string()]),
+            ..Default::default()
+        },
+        ..toy1_args("fixture_toy1_recombinations")
+    };
+    let output_dir = args.output_dir.clone();
+    run(&mut args)?;
+
+    // full results are keyed by sequence id, without the table or bases
+    let recombinations = std::fs::read_to_string(output_dir.join("recombinations.json"))?;
+    let recombinations: serde_json::Value = serde_json::from_str(&recombinations)?;
+    let recombinations = recombinations.as_object().expect("Expected a JSON object.");
+    assert_eq!(recombinations.len(), 2);
+
+    let d = &recombinations["population_D"];
+    assert_eq!(d["parents"], serde_json::json!(["A", "B"]));
+    assert_eq!(d["breakpoints"].as_array().map(|b| b.len()), Some(1));
+    assert!(d.get("table").is_none());
+    assert!(d["sequence"].get("seq").is_none());
+    assert!(d["sequence"].get("substitutions").is_some());
+    assert!(recombinations["population_A"]["recombinant"].is_null());
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_parents_auto() -> Result<(), Report> {
+    // Run, trying the designated parents first
+    let mut args = cli::run::Args {
+        input: cli::run::Input {
+            populations: Some(vec!["D".to_string()]),
+            ..Default::default()
+        },
+        parents_auto: true,
+        trace_search: true,
+        ..toy1_args("fixture_toy1_parents_auto")
+    };
+    let output_dir = args.output_dir.clone();
+    run(&mut args)?;
+
+    // D is still a recombinant of its designated parents
+    let linelist = Table::read(&output_dir.join("linelist.tsv"))?;
+    let row = &linelist.filter("strain", "population_D")?.rows[0];
+    assert_eq!(row[linelist.header_position("parents")?], "A,B");
+
+    // and the exhaustive hypotheses were never searched
+    let trace = Table::read(&output_dir.join("trace.tsv"))?;
+    let hypothesis_i = trace.header_position("hypothesis")?;
+    assert!(trace.rows.iter().all(|row| row[hypothesis_i] == "DesignatedRecombinant"));
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_parents_order() -> Result<(), Report> {
+    // the order of the candidate parents does not change the recombinant
+    for parents in [["B", "A"], ["A", "B"]] {
+        let mut args = cli::run::Args {
+            input: cli::run::Input {
+                populations: Some(vec!["D".to_string()]),
+                ..Default::default()
+            },
+            parents: Some(parents.iter().map(|p| p.to_string()).collect()),
+            ..toy1_args(&format!("fixture_toy1_parents_order/{}", parents.join("_")))
+        };
+        let run_dir = args.output_dir.clone();
+        run(&mut args)?;
+
+        let linelist = Table::read(&run_dir.join("linelist.tsv"))?;
+        let row = &linelist.filter("strain", "population_D")?.rows[0];
+        assert_eq!(row[linelist.header_position("parents")?], "A,B");
+        assert_eq!(row[linelist.header_position("unique_key")?], "D_A_B_12-12");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_max_parents() -> Result<(), Report> {
+    // A (1-11) and B (12-17), with a 3' tail that only C partially explains:
+    // C matches 18 and 20 but conflicts at 19, so it adds no score
+    let mut args = cli::run::Args {
+        input: cli::run::Input {
+            sequence: Some("CCCCCCAACCCTTTTTTGTG".to_string()),
+            ..Default::default()
+        },
+        max_parents: 3,
+        min_consecutive: 2,
+        trace_search: true,
+        ..toy1_args("fixture_toy1_max_parents")
+    };
+    let output_dir = args.output_dir.clone();
+    run(&mut args)?;
+
+    // the search stops early, at the third parent
+    let trace = Table::read(&output_dir.join("trace.tsv"))?;
+    let candidate_i = trace.header_position("candidate")?;
+    let outcome_i = trace.header_position("outcome")?;
+    assert!(trace
+        .rows
+        .iter()
+        .any(|row| row[candidate_i] == "C" && row[outcome_i] == "no_improvement"));
+
+    // and keeps the two parents that were found
+    let linelist = Table::read(&output_dir.join("linelist.tsv"))?;
+    let row = &linelist.filter("strain", "sequence")?.rows[0];
+    assert_eq!(row[linelist.header_position("parents")?], "A,B");
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_max_breakpoints() -> Result<(), Report> {
+    // D has 1 breakpoint, so it is only a recombinant if that is allowed
+    for max_breakpoints in [1, 0] {
+        let mut args = cli::run::Args {
+            input: cli::run::Input {
+                populations: Some(vec!["D".to_string()]),
+                ..Default::default()
+            },
+            max_breakpoints: Some(max_breakpoints),
+            ..toy1_args(&format!("fixture_toy1_max_breakpoints/{max_breakpoints}"))
+        };
+        let run_dir = args.output_dir.clone();
+        run(&mut args)?;
+
+        let linelist = Table::read(&run_dir.join("linelist.tsv"))?;
+        let row = &linelist.filter("strain", "population_D")?.rows[0];
+        let breakpoints = &row[linelist.header_position("breakpoints")?];
+        assert_eq!(breakpoints.is_empty(), max_breakpoints == 0);
+
+        // the threshold is recorded with the run
+        let run_args = std::fs::read_to_string(run_dir.join("run_args.json"))?;
+        let run_args: cli::run::Args = serde_json::from_str(&run_args)?;
+        assert_eq!(run_args.max_breakpoints, Some(max_breakpoints));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_coordinate_base() -> Result<(), Report> {
+    let mut barcodes_coords = Vec::new();
+    let mut plots = Vec::new();
+    for coordinate_base in [
+        cli::run::CoordinateBase::One,
+        cli::run::CoordinateBase::Zero,
+    ] {
+        let mut args = cli::run::Args {
+            input: cli::run::Input {
+                populations: Some(vec!["D".to_string()]),
+                ..Default::default()
+            },
+            coordinate_base,
+            group_by_parents: true,
+            ..toy1_args(&format!("fixture_toy1_coordinate_base/{coordinate_base}"))
+        };
+        let run_dir = args.output_dir.clone();
+        run(&mut args)?;
+
+        // the unique_key (and barcodes file name) is unchanged
+        let barcodes = Table::read(&run_dir.join("barcodes").join("D_A_B_12-12.tsv"))?;
+        let coord_i = barcodes.header_position("coord")?;
+        let coords = barcodes
+            .rows
+            .iter()
+            .map(|row| row[coord_i].parse::<usize>())
+            .collect::<Result<Vec<_>, _>>()?;
+        barcodes_coords.push(coords);
+
+        let linelist = Table::read(&run_dir.join("linelist.tsv"))?;
+        let row = &linelist.rows[0];
+        let expected = match coordinate_base {
+            cli::run::CoordinateBase::One => "12-12",
+            cli::run::CoordinateBase::Zero => "11-11",
+        };
+        assert_eq!(row[linelist.header_position("breakpoints")?], expected);
+        assert_eq!(
+            row[linelist.header_position("coordinate_base")?],
+            coordinate_base.to_string()
+        );
+
+        let parent_pairs = Table::read(&run_dir.join("parent_pairs.tsv"))?;
+        let row = &parent_pairs.rows[0];
+        assert_eq!(
+            row[parent_pairs.header_position("breakpoints_range")?],
+            expected
+        );
+
+        // plots are always drawn in 1-based coordinates
+        let args = cli::plot::Args {
+            run_dir: Some(run_dir.clone()),
+            ..Default::default()
+        };
+        plot(&args)?;
+        plots.push(std::fs::read(
+            run_dir.join("plots").join("D_A_B_12-12.png"),
+        )?);
+    }
+
+    // all barcodes coordinates are shifted by one
+    let shifted = barcodes_coords[0].iter().map(|coord| coord - 1).collect::<Vec<_>>();
+    assert_eq!(barcodes_coords[1], shifted);
+    assert_eq!(plots[0], plots[1]);
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_priors() -> Result<(), Report> {
+    // D and E only differ in the first 3 bases, which are missing
+    let mut args = cli::run::Args {
+        input: cli::run::Input {
+            sequence: Some("NNNCCCAACCCTTTTTTTAA".to_string()),
+            ..Default::default()
+        },
+        ..toy1_args("fixture_toy1_priors")
+    };
+    let output_dir = args.output_dir.clone();
+    run(&mut args)?;
+    let linelist = Table::read(&output_dir.join("linelist.tsv"))?;
+    let row = &linelist.filter("strain", "sequence")?.rows[0];
+    assert_eq!(row[linelist.header_position("population")?], "D");
+
+    // a prior on E breaks the tie, and changes the consensus
+    std::fs::create_dir_all(&output_dir)?;
+    let priors = output_dir.join("priors.tsv");
+    std::fs::write(&priors, "population\tprior_weight\nE\t2\n")?;
+    args.priors = Some(priors);
+    run(&mut args)?;
+    let linelist = Table::read(&output_dir.join("linelist.tsv"))?;
+    let row = &linelist.filter("strain", "sequence")?.rows[0];
+    assert_eq!(row[linelist.header_position("population")?], "E");
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_sequence() -> Result<(), Report> {
+    // Run, on the raw sequence of D (with line breaks)
+    let mut args = cli::run::Args {
+        input: cli::run::Input {
+            sequence: Some("CCCCCCAACC\nCTTTTTTTAA\n".to_string()),
+            ..Default::default()
+        },
+        ..toy1_args("fixture_toy1_sequence")
+    };
+    let output_dir = args.output_dir.clone();
+    run(&mut args)?;
+
+    let linelist = Table::read(&output_dir.join("linelist.tsv"))?;
+    let row = &linelist.filter("strain", "sequence")?.rows[0];
+    assert_eq!(row[linelist.header_position("recombinant")?], "D");
+
+    // sequences must be aligned to the reference
+    args.input.sequence = Some("CCCCCCAACC".to_string());
+    assert!(run(&mut args).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_barcodes_missing() -> Result<(), Report> {
+    // D, with missing data at a site that discriminates the parents
+    let mut args = cli::run::Args {
+        input: cli::run::Input {
+            sequence: Some("CCNCCCAACCCTTTTTTTAA".to_string()),
+            ..Default::default()
+        },
+        ..toy1_args("fixture_toy1_barcodes_missing")
+    };
+    let output_dir = args.output_dir.clone();
+    run(&mut args)?;
+
+    // the missing site has no origin, and is not mistaken for a parent
+    let barcodes = Table::read(&output_dir.join("barcodes").join("D_A_B_12-12.tsv"))?;
+    let row = &barcodes.filter("coord", "3")?.rows[0];
+    assert_eq!(row[barcodes.header_position("origin")?], "?");
+    assert_eq!(row[barcodes.header_position("sequence")?], "N");
+    assert_eq!(rebar::plot::get_parents(&barcodes)?, vec!["A", "B"]);
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_compress_output() -> Result<(), Report> {
+    let mut args = cli::run::Args {
+        compress_output: true,
+        ..toy1_args("fixture_toy1_compress_output")
+    };
+    let output_dir = args.output_dir.clone();
+    run(&mut args)?;
+
+    // the gzip stream is complete, and reads back as the uncompressed table
+    let linelist = Table::read(&output_dir.join("linelist.tsv.gz"))?;
+    let row = &linelist.filter("strain", "population_D")?.rows[0];
+    assert_eq!(row[linelist.header_position("recombinant")?], "D");
+    let mut decoder = flate2::read::GzDecoder::new(std::fs::File::open(
+        output_dir.join("barcodes").join("D_A_B_12-12.tsv.gz"),
+    )?);
+    let mut barcodes = String::new();
+    std::io::Read::read_to_string(&mut decoder, &mut barcodes)?;
+    assert!(barcodes.starts_with("coord\torigin\tReference"));
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_min_consensus_depth() -> Result<(), Report> {
+    let mut args = cli::run::Args {
+        min_consensus_depth: 2,
+        ..toy1_args("fixture_toy1_min_consensus_depth")
+    };
+    let output_dir = args.output_dir.clone();
+    run(&mut args)?;
+
+    // A (depth 1) is rejected as too broad but still reported, D (depth 2) is kept
+    let linelist = Table::read(&output_dir.join("linelist.tsv"))?;
+    let population_i = linelist.header_position("population")?;
+    let ambiguous_i = linelist.header_position("consensus_ambiguous")?;
+    for (strain, population, ambiguous) in
+        [("population_A", "", "A"), ("population_D", "D", "")]
+    {
+        let row = &linelist.filter("strain", strain)?.rows[0];
+        assert_eq!(row[population_i], population);
+        assert_eq!(row[ambiguous_i], ambiguous);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_identical_to_reference() -> Result<(), Report> {
+    let mut args = cli::run::Args {
+        input: cli::run::Input {
+            sequence: Some("AAAAAAAAAAAAAAAAAAAA".to_string()),
+            ..Default::default()
+        },
+        ..toy1_args("fixture_toy1_identical_to_reference")
+    };
+    let output_dir = args.output_dir.clone();
+    run(&mut args)?;
+
+    // flagged in its own column, and not as a qc failure
+    let linelist = Table::read(&output_dir.join("linelist.tsv"))?;
+    let row = &linelist.filter("strain", "sequence")?.rows[0];
+    assert_eq!(
+        row[linelist.header_position("identical_to_reference")?],
+        "true"
+    );
+    assert!(
+        !row[linelist.header_position("qc_notes")?].contains("identical_to_reference")
+    );
+
+    // a query with mutations is not
+    args.input.sequence = Some("CCCCCCAACCCTTTTTTTAA".to_string());
+    run(&mut args)?;
+    let linelist = Table::read(&output_dir.join("linelist.tsv"))?;
+    let row = &linelist.filter("strain", "sequence")?.rows[0];
+    assert_eq!(
+        row[linelist.header_position("identical_to_reference")?],
+        "false"
+    );
+
+    // nor is a query of missing data, which has no mutations either
+    args.input.sequence = Some("N".repeat(20));
+    run(&mut args)?;
+    let linelist = Table::read(&output_dir.join("linelist.tsv"))?;
+    let row = &linelist.filter("strain", "sequence")?.rows[0];
+    assert_eq!(
+        row[linelist.header_position("identical_to_reference")?],
+        "false"
+    );
+    assert_eq!(row[linelist.header_position("population")?], "");
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_plot_tree() -> Result<(), Report> {
+    let dataset_dir = PathBuf::from("tests").join("data").join("toy1");
+    let output_dir = PathBuf::from("output").join("tests").join("fixture_toy1_plot_tree");
+    std::fs::create_dir_all(&output_dir)?;
+
+    let args = cli::dataset::plot_tree::Args {
+        dataset_dir,
+        output: output_dir.join("tree.png"),
+    };
+    plot_tree(&args)?;
+    assert!(args.output.exists());
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_plot_no_summary() -> Result<(), Report> {
+    // the fixture dataset has no summary.json
+    let mut args = cli::run::Args {
+        input: cli::run::Input {
+            populations: Some(vec!["D".to_string()]),
+            ..Default::default()
+        },
+        ..toy1_args("fixture_toy1_plot_no_summary")
+    };
+    let output_dir = args.output_dir.clone();
+    run(&mut args)?;
+    assert!(!args.dataset_dir.join("summary.json").exists());
+
+    let args = cli::plot::Args {
+        run_dir: Some(output_dir.clone()),
+        dataset_dir: Some(args.dataset_dir.clone()),
+        ..Default::default()
+    };
+    plot(&args)?;
+    assert!(output_dir.join("plots").join("D_A_B_12-12.png").exists());
+
+    Ok(())
+}
+
+#[test]
+fn order_samples() -> Result<(), Report> {
+    // barcodes of 4 sites, where parent A has C and parent B has T
+    let samples = [
+        ("s3", "CCCT"),
+        ("s1", "CCTT"),
+        ("s5", "CCCC"),
+        ("s2", "CTTT"),
+        ("s4", "CCTT"),
+    ];
+    let mut barcodes = Table::new();
+    barcodes.headers = ["coord", "origin", "Reference", "A", "B"]
+        .into_iter()
+        .chain(samples.iter().map(|(id, _bases)| *id))
+        .map(String::from)
+        .collect();
+    barcodes.rows = (0..4)
+        .map(|i| {
+            [
+                (i + 1).to_string(),
+                "?".into(),
+                "A".into(),
+                "C".into(),
+                "T".into(),
+            ]
+            .into_iter()
+            .chain(samples.iter().map(|(_id, bases)| bases[i..=i].to_string()))
+            .collect()
+        })
+        .collect();
+
+    // ordered by the first breakpoint, ties broken by profile then id,
+    // and samples without a breakpoint last
+    let parents = ["A".to_string(), "B".to_string()];
+    let sequence_ids =
+        samples.iter().map(|(id, _bases)| id.to_string()).collect::<Vec<_>>();
+    let order = rebar::plot::order_samples(&barcodes, &parents, &sequence_ids)?;
+    assert_eq!(order, ["s2", "s1", "s4", "s3", "s5"]);
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_plot_parts() -> Result<(), Report> {
+    // D has a single breakpoint, so most parts don't contain it
+    let mut args = cli::run::Args {
+        input: cli::run::Input {
+            populations: Some(vec!["D".to_string()]),
+            ..Default::default()
+        },
+        ..toy1_args("fixture_toy1_plot_parts")
+    };
+    let output_dir = args.output_dir.clone();
+    if output_dir.exists() {
+        std::fs::remove_dir_all(&output_dir)?;
+    }
+    run(&mut args)?;
+
+    let max_sites = 3;
+    let args = cli::plot::Args {
+        run_dir: Some(output_dir.clone()),
+        max_sites_per_plot: Some(max_sites),
         ..Default::default()
     };
     plot(&args)?;
 
+    let barcodes_path = output_dir.join("barcodes").join("D_A_B_12-12.tsv");
+    let num_sites = rebar::plot::num_sites(&barcodes_path, false, false, false)?;
+    let num_parts = num_sites.div_ceil(max_sites);
+    assert!(num_parts > 2);
+    for part in 1..=num_parts {
+        let path = output_dir.join("plots").join(format!("D_A_B_12-12.part{part}.png"));
+        assert!(path.exists(), "Missing plot part: {path:?}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_benchmark() -> Result<(), Report> {
+    let dataset_dir = PathBuf::from("tests").join("data").join("toy1");
+    let args = cli::benchmark::Args {
+        dataset_dir,
+        limit: 10,
+    };
+    benchmark(&args)?;
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_similar_parents() -> Result<(), Report> {
+    let dataset_dir = PathBuf::from("tests").join("data").join("toy1");
+    let mask = vec![0, 0];
+    let dataset = load::dataset(&dataset_dir, &mask, None, false)?;
+    let parents = ["A", "B"]
+        .into_iter()
+        .map(|pop| {
+            dataset.search(&dataset.populations[pop], None, None, 10, false, false)
+        })
+        .collect::<Result<Vec<_>, Report>>()?;
+
+    // A and B differ at all 20 sites, well above the 2 sites (+ margin) needed
+    let args = toy1_args("fixture_toy1_similar_parents");
+    assert!(recombination::search::similar_parents(&parents, &args).is_empty());
+
+    // a larger margin flags them as nearly identical
+    let args = cli::run::Args {
+        similar_parents_margin: 19,
+        ..args
+    };
+    let similar = recombination::search::similar_parents(&parents, &args);
+    assert_eq!(similar, vec![("A".to_string(), "B".to_string(), 20)]);
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_search_index() -> Result<(), Report> {
+    // the mutations index must not change search results
+    let dataset_dir = PathBuf::from("tests").join("data").join("toy1");
+    let mask = vec![0, 0];
+    let mut dataset = load::dataset(&dataset_dir, &mask, None, false)?;
+    assert!(dataset.indexed());
+
+    let search = |dataset: &rebar::dataset::Dataset| {
+        dataset
+            .populations
+            .values()
+            .map(|sequence| {
+                let result = dataset.search(sequence, None, None, 10, false, false)?;
+                Ok((sequence.id.clone(), result.top_populations))
+            })
+            .collect::<Result<Vec<_>, Report>>()
+    };
+    let indexed = search(&dataset)?;
+
+    dataset.mutations.clear();
+    assert!(!dataset.indexed());
+    let scanned = search(&dataset)?;
+    assert_eq!(indexed, scanned);
+
+    // populations removed without updating the index are skipped
+    let mut dataset = load::dataset(&dataset_dir, &mask, None, false)?;
+    dataset.populations.remove("D");
+    let indexed = search(&dataset)?;
+    dataset.mutations.clear();
+    let scanned = search(&dataset)?;
+    assert_eq!(indexed, scanned);
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_mutations_cache() -> Result<(), Report> {
+    let dataset_dir = PathBuf::from("tests").join("data").join("toy1");
+    let output_dir =
+        PathBuf::from("output").join("tests").join("fixture_toy1_mutations_cache");
+    std::fs::create_dir_all(&output_dir)?;
+    for file in ["reference.fasta", "populations.fasta"] {
+        std::fs::copy(dataset_dir.join(file), output_dir.join(file))?;
+    }
+    // a population with missing data, an ambiguity code, and a deletion
+    let reference_path = output_dir.join("reference.fasta");
+    let populations_path = output_dir.join("populations.fasta");
+    let mut file = std::fs::OpenOptions::new().append(true).open(&populations_path)?;
+    writeln!(file, ">F\nNNCCCCAACCCRCCCC-CCA")?;
+
+    // mutations are exported without masking, like a downloaded dataset
+    let mask = vec![0, 0];
+    let reference = read_reference(&reference_path, &mask, None)?;
+    let (populations, mutations) =
+        load::parse_populations(&populations_path, &reference, &mask, false)?;
+    let mutations_path = output_dir.join("mutations.json");
+    let sha256 = rebar::utils::sha256(&populations_path)?;
+    rebar::dataset::write_mutations(&populations, &mutations, &sha256, &mutations_path)?;
+
+    // populations read from their mutations are the same as from the fasta
+    for mask in [vec![0, 0], vec![3, 2]] {
+        let reference = read_reference(&reference_path, &mask, None)?;
+        let expected =
+            load::read_populations(&populations_path, &reference, &mask, false)?;
+        let cached = load::read_cached_populations(
+            &mutations_path,
+            &populations_path,
+            &reference,
+            &mask,
+        )?;
+        assert_eq!(cached, Some(expected));
+    }
+
+    // the dataset uses them if the fasta is unchanged, B is missing A18T
+    let mut cached = rebar::dataset::read_mutations(&mutations_path)?;
+    cached.mutations.retain(|(mutation, _pops)| mutation != "A18T");
+    std::fs::write(&mutations_path, serde_json::to_string(&cached)?)?;
+    let dataset = load::dataset(&output_dir, &mask, None, false)?;
+    assert_eq!(dataset.populations["B"].substitutions.len(), 17);
+    assert_eq!(dataset.mutations.len(), mutations.len() - 1);
+    // unless strict, which checks the fasta itself
+    let dataset = load::dataset(&output_dir, &mask, None, true)?;
+    assert_eq!(dataset.populations["B"].substitutions.len(), 18);
+
+    // a stale mutations.json is ignored, B now has 20 substitutions
+    let fasta = std::fs::read_to_string(&populations_path)?
+        .replace("TTTTTTTTTTTTTTTTTTAA", "TTTTTTTTTTTTTTTTTTTT");
+    std::fs::write(&populations_path, fasta)?;
+    let cached = load::read_cached_populations(
+        &mutations_path,
+        &populations_path,
+        &reference,
+        &mask,
+    )?;
+    assert!(cached.is_none());
+    let dataset = load::dataset(&output_dir, &mask, None, false)?;
+    assert_eq!(dataset.populations["B"].substitutions.len(), 20);
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_match_populations() -> Result<(), Report> {
+    let dataset_dir = PathBuf::from("tests").join("data").join("toy1");
+    let mask = vec![0, 0];
+    let dataset = load::dataset(&dataset_dir, &mask, None, false)?;
+    let expand = |populations: &[&str]| {
+        let populations = populations.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        dataset.expand_populations(&populations)
+    };
+
+    // the '*' suffix expands descendants, a glob matches by name alone
+    assert_eq!(expand(&["A*"])?, ["A", "D", "E"]);
+    assert_eq!(expand(&["glob:A*"])?, ["A"]);
+    assert_eq!(expand(&["glob:?"])?, ["A", "B", "C", "D", "E"]);
+    assert_eq!(expand(&["regex:^[B-D]$"])?, ["B", "C", "D"]);
+    // and can be combined with plain names, without duplicates
+    assert_eq!(expand(&["E", "regex:[DE]", "glob:A"])?, ["E", "D", "A"]);
+
+    // glob characters other than '*' and '?' are literal
+    assert_eq!(rebar::dataset::glob_to_regex("XBB.1*"), r"^XBB\.1.*$");
+    assert!(expand(&["glob:."]).is_err());
+    // patterns with no matches, or invalid patterns, are errors
+    assert!(expand(&["regex:^F$"]).is_err());
+    assert!(expand(&["regex:["]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_stats() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("fixture_toy1_stats");
+    std::fs::create_dir_all(&output_dir)?;
+
+    let args = cli::dataset::stats::Args {
+        dataset_dir: PathBuf::from("tests").join("data").join("toy1"),
+        top: 10,
+        output: Some(output_dir.join("sites.tsv")),
+    };
+    rebar::dataset::stats::stats(&args)?;
+
+    // C, G, and T substitutions at 18 sites each
+    let sites = Table::read(&output_dir.join("sites.tsv"))?;
+    assert_eq!(sites.rows.len(), 54);
+    let populations_i = sites.header_position("populations")?;
+    let singletons = sites.rows.iter().filter(|row| row[populations_i] == "1").count();
+    assert_eq!(singletons, 37);
+    // the most homoplasic site is shared by A, D, and E
+    assert_eq!(sites.rows[0], ["A4C", "3"]);
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_build() -> Result<(), Report> {
+    let dataset_dir = PathBuf::from("tests").join("data").join("toy1");
+    let output_dir = PathBuf::from("output").join("tests").join("fixture_toy1_build");
+    std::fs::create_dir_all(&output_dir)?;
+
+    let edges = "parent\tchild\nA\tD\nB\tD\nC\tE\nD\tE\n";
+    let edges_path = output_dir.join("edges.tsv");
+    std::fs::write(&edges_path, edges)?;
+
+    let mut args = cli::dataset::build::Args {
+        reference: dataset_dir.join("reference.fasta"),
+        populations: dataset_dir.join("populations.fasta"),
+        phylogeny: edges_path.clone(),
+        output_dir: output_dir.join("dataset"),
+    };
+    rebar::dataset::io::build(&args)?;
+
+    // recombinants are detected from multiple parents
+    let mask = vec![0, 0];
+    let dataset = load::dataset(&args.output_dir, &mask, None, false)?;
+    assert_eq!(dataset.name, Name::Custom);
+    assert_eq!(dataset.tag, Tag::Custom);
+    assert_eq!(dataset.populations.len(), 5);
+    assert_eq!(dataset.phylogeny.recombinants, vec!["D", "E"]);
+    let mut parents = dataset.phylogeny.get_parents("E")?;
+    parents.sort();
+    assert_eq!(parents, vec!["C", "D"]);
+
+    // cycles are an error
+    let edges = "parent\tchild\nA\tD\nB\tD\nD\tB\n";
+    std::fs::write(&edges_path, edges)?;
+    args.output_dir = output_dir.join("cycle");
+    let result = rebar::dataset::io::build(&args);
+    assert!(result.is_err_and(|e| e.to_string().contains("Failed to build phylogeny")));
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_timeout() -> Result<(), Report> {
+    // without any time, every parent search is abandoned, keeping the best match
+    let mut args = cli::run::Args {
+        timeout: Some(0),
+        ..toy1_args("fixture_toy1_timeout")
+    };
+    let run_dir = args.output_dir.clone();
+    run(&mut args)?;
+
+    let linelist = Table::read(&run_dir.join("linelist.tsv"))?;
+    assert_eq!(linelist.rows.len(), 5);
+    let strain_i = linelist.header_position("strain")?;
+    let population_i = linelist.header_position("population")?;
+    let parents_i = linelist.header_position("parents")?;
+    let timed_out_i = linelist.header_position("timed_out")?;
+    for row in &linelist.rows {
+        assert_eq!(row[timed_out_i], "true");
+        assert_eq!(row[strain_i], format!("population_{}", row[population_i]));
+        assert!(row[parents_i].is_empty());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_threads() -> Result<(), Report> {
+    // results must not depend on the number of threads
+    let mut linelists = Vec::new();
+    for threads in [1, 8] {
+        let mut args = cli::run::Args {
+            threads,
+            ..toy1_args(&format!("fixture_toy1_threads/threads_{threads}"))
+        };
+        let run_dir = args.output_dir.clone();
+        run(&mut args)?;
+        linelists.push(std::fs::read(run_dir.join("linelist.tsv"))?);
+    }
+    assert_eq!(linelists[0], linelists[1]);
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_compressed_alignment() -> Result<(), Report> {
+    // results must not depend on the compression of the alignment
+    let dataset_dir = PathBuf::from("tests").join("data").join("toy1");
+    let output_dir =
+        PathBuf::from("output").join("tests").join("fixture_toy1_compressed_alignment");
+    std::fs::create_dir_all(&output_dir)?;
+    let fasta = std::fs::read(dataset_dir.join("populations.fasta"))?;
+
+    let mut linelists = Vec::new();
+    for compression in ["", ".gz", ".zst"] {
+        let alignment = output_dir.join(format!("alignment.fasta{compression}"));
+        let bytes = match compression {
+            ".gz" => {
+                let mut encoder = flate2::write::GzEncoder::new(
+                    Vec::new(),
+                    flate2::Compression::default(),
+                );
+                encoder.write_all(&fasta)?;
+                encoder.finish()?
+            }
+            ".zst" => zstd::stream::encode_all(&fasta[..], 0)?,
+            _ => fasta.clone(),
+        };
+        std::fs::write(&alignment, bytes)?;
+
+        let name = format!(
+            "fixture_toy1_compressed_alignment/run{}",
+            compression.replace('.', "_")
+        );
+        let mut args = cli::run::Args {
+            input: cli::run::Input {
+                alignment: Some(alignment),
+                ..Default::default()
+            },
+            ..toy1_args(&name)
+        };
+        let run_dir = args.output_dir.clone();
+        run(&mut args)?;
+        linelists.push(std::fs::read(run_dir.join("linelist.tsv"))?);
+    }
+    assert_eq!(linelists[0], linelists[1]);
+    assert_eq!(linelists[0], linelists[2]);
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_insertions() -> Result<(), Report> {
+    // an alignment including the reference, with an insertion after coord 10
+    let mut args = cli::run::Args {
+        record_insertions: true,
+        ..toy1_args("fixture_toy1_insertions")
+    };
+    let output_dir = args.output_dir.clone();
+    std::fs::create_dir_all(&output_dir)?;
+    let alignment = output_dir.join("alignment.fasta");
+    let fasta = ">Reference\nAAAAAAAAAA--AAAAAAAAAA\n>query\nCCCCCCAACCGGCTTTTTTTAA\n";
+    std::fs::write(&alignment, fasta)?;
+    args.input = cli::run::Input {
+        alignment: Some(alignment),
+        ..Default::default()
+    };
+    run(&mut args)?;
+
+    // the query is D, in reference coordinates
+    let linelist = Table::read(&output_dir.join("linelist.tsv"))?;
+    assert_eq!(linelist.rows.len(), 1);
+    let row = &linelist.filter("strain", "query")?.rows[0];
+    assert_eq!(row[linelist.header_position("recombinant")?], "D");
+    assert_eq!(row[linelist.header_position("breakpoints")?], "12-12");
+
+    let recombinations = std::fs::read_to_string(output_dir.join("recombinations.json"))?;
+    let recombinations: serde_json::Value = serde_json::from_str(&recombinations)?;
+    assert_eq!(
+        recombinations["query"]["sequence"]["insertions"],
+        serde_json::json!([{"coord": 10, "bases": "GG"}])
+    );
+
+    // otherwise, the alignment is not the reference length
+    args.record_insertions = false;
+    assert!(run(&mut args).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_private_policy() -> Result<(), Report> {
+    // the private policy changes the origin, but never which subs are reported
+    let mut tables = Vec::new();
+    for policy in [PrivatePolicy::BestMatch, PrivatePolicy::Private] {
+        let mut args = cli::run::Args {
+            output_substitutions: true,
+            private_policy: policy,
+            ..toy1_args(&format!("fixture_toy1_private_policy/{policy}"))
+        };
+        let run_dir = args.output_dir.clone();
+        run(&mut args)?;
+        tables.push(Table::read(&run_dir.join("substitutions.tsv"))?);
+    }
+
+    let subs = tables
+        .iter()
+        .map(|table| {
+            table.rows.iter().map(|row| row[..4].to_vec()).collect::<BTreeSet<_>>()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(subs[0], subs[1]);
+
+    // E (best match), recombined from A and B. G3 is private to the parents,
+    // but shared with the best match.
+    let sequence = "CAGCCCAACCCTTTTTTTAA".to_string();
+    let mut origins = Vec::new();
+    for policy in [PrivatePolicy::BestMatch, PrivatePolicy::Private] {
+        let mut args = cli::run::Args {
+            input: cli::run::Input {
+                sequence: Some(sequence.clone()),
+                ..Default::default()
+            },
+            output_substitutions: true,
+            private_policy: policy,
+            ..toy1_args(&format!("fixture_toy1_private_policy/sequence_{policy}"))
+        };
+        let run_dir = args.output_dir.clone();
+        run(&mut args)?;
+
+        let linelist = Table::read(&run_dir.join("linelist.tsv"))?;
+        let row = &linelist.filter("strain", "sequence")?.rows[0];
+        assert_eq!(row[linelist.header_position("parents")?], "A,B");
+        let substitutions = row[linelist.header_position("substitutions")?].clone();
+
+        let table = Table::read(&run_dir.join("substitutions.tsv"))?;
+        let row = &table.filter("coord", "3")?.filter("alt", "G")?.rows[0];
+        origins.push((row[table.header_position("origin")?].clone(), substitutions));
+    }
+    assert_eq!(origins[0].0, "E");
+    assert!(origins[0].1.contains("A3G|E"));
+    assert_eq!(origins[1].0, "private");
+    assert!(!origins[1].1.contains("|E"));
+
+    // reversions, the parental subs that are reference in the sequence
+    let mut args = cli::run::Args {
+        input: cli::run::Input {
+            sequence: Some(sequence),
+            ..Default::default()
+        },
+        output_substitutions: true,
+        report_reversions: true,
+        ..toy1_args("fixture_toy1_private_policy/reversions")
+    };
+    let run_dir = args.output_dir.clone();
+    run(&mut args)?;
+    let table = Table::read(&run_dir.join("substitutions.tsv"))?;
+    let reversions = table.filter("origin", "reversion")?;
+    let coord_i = reversions.header_position("coord")?;
+    let coords =
+        reversions.rows.iter().map(|row| row[coord_i].as_str()).collect::<Vec<_>>();
+    assert_eq!(coords, ["2", "3"]);
+    let linelist = Table::read(&run_dir.join("linelist.tsv"))?;
+    let row = &linelist.filter("strain", "sequence")?.rows[0];
+    assert!(
+        row[linelist.header_position("substitutions")?].ends_with("A2C,A3C|reversion")
+    );
+
     Ok(())
 }
 
@@ -69,10 +1282,616 @@ async fn sarscov2_populations() -> Result<(), Report> {
     // Plot
     let args = cli::plot::Args {
         annotations: Some(output_dir.join("dataset").join("annotations.tsv")),
-        run_dir: output_dir.join("run"),
+        run_dir: Some(output_dir.join("run")),
         ..Default::default()
     };
     plot(&args)?;
 
     Ok(())
 }
+
+#[test]
+fn breakpoint_round_trip() -> Result<(), Report> {
+    // Display -> FromStr -> Display, including single-coordinate intervals
+    for text in ["100-200", "100-100"] {
+        let breakpoint = Breakpoint::from_str(text)?;
+        assert_eq!(breakpoint.to_string(), text);
+        assert_eq!(Breakpoint::from_str(&breakpoint.to_string())?, breakpoint);
+    }
+
+    // single coordinate form
+    let breakpoint = Breakpoint::from_str("100")?;
+    assert_eq!(
+        breakpoint,
+        Breakpoint {
+            start: 100,
+            end: 100
+        }
+    );
+
+    // ordered by start, then end, for set operations
+    let breakpoints = ["300-400", "100-200", "100-150", "100-200"]
+        .iter()
+        .map(|b| Breakpoint::from_str(b))
+        .collect::<Result<BTreeSet<_>, Report>>()?;
+    let breakpoints = breakpoints.iter().map(|b| b.to_string()).collect::<Vec<_>>();
+    assert_eq!(breakpoints, vec!["100-150", "100-200", "300-400"]);
+
+    // malformed
+    assert!(Breakpoint::from_str("").is_err());
+    assert!(Breakpoint::from_str("200-100").is_err());
+    assert!(Breakpoint::from_str("100-200-300").is_err());
+    assert!(Breakpoint::from_str("A-200").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn bridge_regions() -> Result<(), Report> {
+    // regions of (start, end, origin, number of substitutions)
+    let regions = [
+        (1, 10, "A", 3),
+        (11, 11, "B", 1),
+        (12, 20, "A", 2),
+        (21, 30, "B", 5),
+    ]
+    .into_iter()
+    .map(|(start, end, origin, n)| {
+        let substitutions = (start..start + n)
+            .map(|coord| Substitution {
+                coord,
+                reference: 'A',
+                alt: 'C',
+            })
+            .collect();
+        let region = Region {
+            start,
+            end,
+            origin: origin.to_string(),
+            substitutions,
+        };
+        (start, region)
+    })
+    .collect::<BTreeMap<_, _>>();
+
+    // no tolerance, regions are unchanged
+    let regions_bridge = recombination::bridge_regions(&regions, 0)?;
+    assert_eq!(regions_bridge.len(), 4);
+
+    // a single interrupting site is bridged, and not counted as support
+    let regions_bridge = recombination::bridge_regions(&regions, 1)?;
+    let observed = regions_bridge.values().map(|r| r.to_string()).collect::<Vec<_>>();
+    assert_eq!(observed, vec!["1-20|A", "21-30|B"]);
+    assert_eq!(regions_bridge[&1].substitutions.len(), 5);
+
+    Ok(())
+}
+
+#[test]
+fn regions_properties() -> Result<(), Report> {
+    // regions from a pattern of origins, one substitution per coordinate
+    let identify = |origins: &[&str]| {
+        let mut table = Table::new();
+        table.headers = ["coord", "origin", "Reference", "query"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        table.rows = origins
+            .iter()
+            .enumerate()
+            .map(|(i, origin)| {
+                vec![
+                    (i + 1).to_string(),
+                    origin.to_string(),
+                    "A".into(),
+                    "C".into(),
+                ]
+            })
+            .collect();
+        recombination::identify_regions(&table)
+    };
+    // origin and coordinates of each region, optionally mirrored (5' <-> 3')
+    let summarize = |regions: &BTreeMap<usize, Region>, mirror: Option<usize>| {
+        regions
+            .values()
+            .map(|r| {
+                let coords = r
+                    .substitutions
+                    .iter()
+                    .map(|s| mirror.map_or(s.coord, |n| n + 1 - s.coord))
+                    .collect::<BTreeSet<_>>();
+                (r.origin.clone(), coords)
+            })
+            .collect::<BTreeSet<_>>()
+    };
+
+    // every pattern of up to 7 sites from two origins
+    for n in 1..=7 {
+        for bits in 0..(1 << n) {
+            let origins = (0..n)
+                .map(|i| if (bits >> i) & 1 == 1 { "B" } else { "A" })
+                .collect::<Vec<_>>();
+            let mirrored = origins.iter().rev().copied().collect::<Vec<_>>();
+            let regions = identify(&origins)?;
+            let regions_mirror = identify(&mirrored)?;
+
+            for min_consecutive in 0..=3 {
+                for min_length in [0, 2, 4] {
+                    let filter = |regions: &BTreeMap<usize, Region>,
+                                  direction: Direction| {
+                        recombination::filter_regions(
+                            regions,
+                            direction,
+                            min_consecutive,
+                            min_length,
+                        )
+                    };
+                    let forward = filter(&regions, Direction::Forward)?;
+                    let reverse = filter(&regions, Direction::Reverse)?;
+
+                    // filtering is idempotent
+                    assert_eq!(filter(&forward, Direction::Forward)?, forward);
+                    assert_eq!(filter(&reverse, Direction::Reverse)?, reverse);
+
+                    // reverse is the mirror image of forward
+                    let reverse_mirror = filter(&regions_mirror, Direction::Reverse)?;
+                    assert_eq!(
+                        summarize(&forward, None),
+                        summarize(&reverse_mirror, Some(n)),
+                        "{origins:?}"
+                    );
+
+                    // intersection is symmetric
+                    assert_eq!(
+                        recombination::intersect_regions(&forward, &reverse)?,
+                        recombination::intersect_regions(&reverse, &forward)?,
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn intersect_regions_overlap() -> Result<(), Report> {
+    let region = |start: usize, end: usize, origin: &str| {
+        let substitutions = (start..=end)
+            .map(|coord| Substitution {
+                coord,
+                reference: 'A',
+                alt: 'C',
+            })
+            .collect();
+        let region = Region {
+            start,
+            end,
+            origin: origin.to_string(),
+            substitutions,
+        };
+        (start, region)
+    };
+
+    // overlapping regions of the same origin are combined
+    let regions_1 = BTreeMap::from([region(1, 5, "A"), region(3, 8, "A")]);
+    let regions_2 = BTreeMap::from([region(3, 4, "A"), region(5, 10, "A")]);
+    let intersect = recombination::intersect_regions(&regions_1, &regions_2)?;
+    let observed = intersect.values().map(|r| r.to_string()).collect::<Vec<_>>();
+    assert_eq!(observed, vec!["3-4|A", "5-8|A"]);
+    assert_eq!(intersect[&5].substitutions.len(), 4);
+
+    // and conflicting origins are an error
+    let regions_1 = BTreeMap::from([region(1, 5, "A"), region(3, 8, "B")]);
+    let regions_2 = BTreeMap::from([region(2, 9, "B"), region(3, 4, "A")]);
+    assert!(recombination::intersect_regions(&regions_1, &regions_2).is_err());
+
+    // a region split by the intersect is merged again by the filter, without
+    // a phantom breakpoint between the two halves
+    let regions_1 = BTreeMap::from([region(1, 10, "A")]);
+    let regions_2 = BTreeMap::from([region(1, 4, "A"), region(6, 10, "A")]);
+    let intersect = recombination::intersect_regions(&regions_1, &regions_2)?;
+    assert_eq!(intersect.len(), 2);
+    let filter = recombination::filter_regions(&intersect, Direction::Forward, 0, 0)?;
+    let observed = filter.values().map(|r| r.to_string()).collect::<Vec<_>>();
+    assert_eq!(observed, vec!["1-10|A"]);
+    assert_eq!(filter[&1].substitutions.len(), 9);
+    assert!(recombination::identify_breakpoints(&filter)?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn assign_hypothesis() -> Result<(), Report> {
+    // D is a designated recombinant of A and B, E of C and D
+    let phylogeny_path =
+        PathBuf::from("tests").join("data").join("toy1").join("phylogeny.json");
+    let phylogeny = Phylogeny::read(&phylogeny_path)?;
+    let sequence = Sequence::new();
+    let hypothesis = |recombinant: &str, parents: &[&str]| {
+        let mut recombination = recombination::Recombination::new(&sequence);
+        recombination.recombinant = Some(recombinant.to_string());
+        recombination.parents = parents.iter().map(|p| p.to_string()).collect();
+        recombination.assign_hypothesis(&phylogeny)
+    };
+
+    // the designated parents, in any order
+    assert_eq!(
+        hypothesis("D", &["B", "A"]),
+        Hypothesis::DesignatedRecombinant
+    );
+    // parents that disagree with the phylogeny are not designated
+    assert_eq!(
+        hypothesis("D", &["A", "C"]),
+        Hypothesis::NonRecursiveRecombinant
+    );
+    assert_eq!(
+        hypothesis("E", &["B", "D"]),
+        Hypothesis::RecursiveRecombinant
+    );
+    assert_eq!(
+        hypothesis("novel", &["A", "B"]),
+        Hypothesis::NonRecursiveRecombinant
+    );
+
+    Ok(())
+}
+
+#[test]
+fn fixture_toy1_unresolved_subs() -> Result<(), Report> {
+    let dataset_dir = PathBuf::from("tests").join("data").join("toy1");
+    let mask = vec![0, 0];
+    let dataset = load::dataset(&dataset_dir, &mask, None, false)?;
+
+    // D (A + B), with a private substitution (A5G) and a reversion (C3A)
+    let record =
+        bio::io::fasta::Record::with_attrs("sequence", None, b"CCACGCAACCCTTTTTTTAA");
+    let sequence = Sequence::from_record(record, Some(&dataset.reference), &mask)?;
+    let search = |pop: &str| {
+        let populations = vec![&dataset.populations[pop].id];
+        dataset.search(&sequence, Some(&populations), None, 10, false, false)
+    };
+    let parents = vec![search("A")?];
+    let args = toy1_args("fixture_toy1_unresolved_subs");
+    let recombination = recombination::detect_recombination(
+        &sequence,
+        &parents,
+        Some(&search("B")?),
+        &dataset.reference,
+        &args,
+    )?;
+    assert_eq!(recombination.parents, ["A", "B"]);
+
+    // the private substitution is not unresolved, it is already private
+    let private =
+        recombination.private["A"].iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    assert!(private.contains(&"A5G".to_string()));
+    let unresolved =
+        recombination.unresolved.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    assert_eq!(unresolved, ["C3A"]);
+
+    Ok(())
+}
+
+#[test]
+fn gapped_reference() -> Result<(), Report> {
+    // reference gaps (-) and missing data (N) are not in the reference frame
+    let reference = bio::io::fasta::Record::with_attrs("Reference", None, b"AC-TN");
+    let reference = Sequence::from_record(reference, None, &Vec::new())?;
+    let record = bio::io::fasta::Record::with_attrs("query", None, b"AGTTA");
+    let sequence = Sequence::from_record(record, Some(&reference), &Vec::new())?;
+
+    let substitutions =
+        sequence.substitutions.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    assert_eq!(substitutions, vec!["C2G"]);
+    assert!(sequence.deletions.is_empty());
+    assert!(sequence.missing.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn insertions() -> Result<(), Report> {
+    // reference gaps (-) are insertion columns in the query
+    let reference = bio::io::fasta::Record::with_attrs("Reference", None, b"AC--GTA-CA");
+    let reference = Sequence::from_record(reference, None, &Vec::new())?;
+    let record = bio::io::fasta::Record::with_attrs("query", None, b"ACTTGA-GCT");
+    let mode = InsertionMode::Record;
+    let sequence = Sequence::from_record_with_insertions(
+        record,
+        Some(&reference),
+        &Vec::new(),
+        mode,
+    )?;
+
+    // downstream mutations are in reference coordinates
+    assert_eq!(sequence.genome_length, 7);
+    let insertions =
+        sequence.insertions.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+    assert_eq!(insertions, vec!["2:TT", "5:G"]);
+    let substitutions =
+        sequence.substitutions.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    assert_eq!(substitutions, vec!["T4A", "A7T"]);
+    let deletions = sequence.deletions.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+    assert_eq!(deletions, vec!["A5-"]);
+    // and the insertion columns are removed from the sequence
+    assert_eq!(sequence.seq.iter().collect::<String>(), "ACGA-CT");
+
+    // insertions in the masked ends are not recorded
+    let record = bio::io::fasta::Record::with_attrs("query", None, b"ACTTGA-GCT");
+    let mask = vec![2, 0];
+    let sequence =
+        Sequence::from_record_with_insertions(record, Some(&reference), &mask, mode)?;
+    let insertions =
+        sequence.insertions.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+    assert_eq!(insertions, vec!["5:G"]);
+
+    Ok(())
+}
+
+#[test]
+fn parsimony_ambiguity() -> Result<(), Report> {
+    let reference = bio::io::fasta::Record::with_attrs("Reference", None, b"AAAAAA");
+    let reference = Sequence::from_record(reference, None, &Vec::new())?;
+    let population = bio::io::fasta::Record::with_attrs("population", None, b"TGAACA");
+    let population = Sequence::from_record(population, Some(&reference), &Vec::new())?;
+    let record = bio::io::fasta::Record::with_attrs("query", None, b"SRKWYN");
+    let sequence = Sequence::from_record(record, Some(&reference), &Vec::new())?;
+
+    // ambiguity codes are retained, N is only missing
+    let ambiguous = sequence.ambiguous.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    assert_eq!(ambiguous, vec!["A1S", "A2R", "A3K", "A4W", "A5Y"]);
+    assert_eq!(sequence.missing, vec![1, 2, 3, 4, 5, 6]);
+
+    // by default, ambiguous coordinates are missing
+    let summary =
+        parsimony::Summary::from_sequence(&sequence, &population, None, false, false)?;
+    assert!(summary.support.is_empty());
+    assert!(summary.conflict_ref.is_empty());
+    assert!(summary.conflict_alt.is_empty());
+
+    // compatible with the population alt is support, compatible with neither
+    // the population nor reference is conflict, compatible with the reference is ignored
+    let summary =
+        parsimony::Summary::from_sequence(&sequence, &population, None, false, true)?;
+    let to_strings =
+        |subs: &[Substitution]| subs.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    assert_eq!(to_strings(&summary.support), vec!["A2G", "A5C"]);
+    assert_eq!(to_strings(&summary.conflict_ref), vec!["A1T"]);
+    assert_eq!(to_strings(&summary.conflict_alt), vec!["A3K"]);
+    assert_eq!(summary.score, 0);
+
+    Ok(())
+}
+
+#[test]
+fn phylogeny_newick() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("phylogeny_newick");
+    std::fs::create_dir_all(&output_dir)?;
+
+    // recombinants are written once, under their first parent
+    let phylogeny_path =
+        PathBuf::from("tests").join("data").join("toy1").join("phylogeny.json");
+    let phylogeny = Phylogeny::read(&phylogeny_path)?;
+    assert_eq!(phylogeny.to_newick()?, "(((E)D)A,B,C)root;");
+
+    let newick_path = output_dir.join("phylogeny.nwk");
+    phylogeny.write(&newick_path)?;
+    let newick = Phylogeny::read(&newick_path)?;
+    assert!(newick.recombinants.is_empty());
+    assert_eq!(newick.get_parents("E")?, vec!["D"]);
+    assert_eq!(newick.get_parents("D")?, vec!["A"]);
+
+    // branch lengths and comments are ignored, internal nodes are named
+    let phylogeny = Phylogeny::from_newick("((a:0.1,'b c':0.2):0.3,d)[comment];")?;
+    assert_eq!(
+        phylogeny.get_names()?,
+        vec!["root", "node_1", "a", "b c", "d"]
+    );
+    assert_eq!(phylogeny.get_parents("b c")?, vec!["node_1"]);
+    assert_eq!(phylogeny.to_newick()?, "((a,'b c')node_1,d)root;");
+
+    // incomplete and ambiguous trees are errors
+    assert!(Phylogeny::from_newick("((a,b);").is_err());
+    assert!(Phylogeny::from_newick("(a,b)").is_err());
+    assert!(Phylogeny::from_newick("(a,a);").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn phylogeny_update_from() -> Result<(), Report> {
+    let mut phylogeny = rebar::dataset::toy1::phylogeny::build()?;
+    let names = ["root", "A", "B", "C", "D", "E"];
+    let nodes =
+        names.iter().map(|name| phylogeny.get_node(name)).collect::<Result<Vec<_>>>()?;
+
+    // populations already in the graph are skipped, only new ones are added
+    let populations = [("A", vec!["root"]), ("F", vec!["C"]), ("G", vec!["F", "D"])]
+        .into_iter()
+        .map(|(name, parents)| {
+            (
+                name.to_string(),
+                parents.into_iter().map(String::from).collect(),
+            )
+        })
+        .collect::<Vec<(String, Vec<String>)>>();
+    let added = phylogeny.update_from(&populations)?;
+    assert_eq!(added, ["F", "G"]);
+    assert_eq!(phylogeny.graph.node_count(), 8);
+    assert!(phylogeny.is_recombinant("G")?);
+
+    // existing node indices are preserved
+    for (name, node) in names.iter().zip(nodes) {
+        assert_eq!(phylogeny.get_node(name)?, node);
+    }
+
+    // a repeated update adds nothing
+    assert!(phylogeny.update_from(&populations)?.is_empty());
+    assert_eq!(phylogeny.graph.node_count(), 8);
+
+    Ok(())
+}
+
+#[test]
+fn phylogeny_auspice() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("phylogeny_auspice");
+    std::fs::create_dir_all(&output_dir)?;
+
+    // nodes are labelled by lineage or clade, falling back to their name
+    let clade = |name: &str, value: &str, children: Vec<serde_json::Value>| {
+        serde_json::json!({
+            "name": name,
+            "node_attrs": { "clade_membership": { "value": value } },
+            "children": children,
+        })
+    };
+    let lineage = serde_json::json!({
+        "name": "s3",
+        "node_attrs": {
+            "clade_membership": { "value": "C" },
+            "pango_lineage": { "value": "C.1" },
+        },
+    });
+    let tree = clade(
+        "NODE_1",
+        "A",
+        vec![
+            clade(
+                "NODE_2",
+                "A",
+                vec![
+                    clade("s1", "A", Vec::new()),
+                    clade("NODE_3", "B", vec![clade("s2", "B", Vec::new())]),
+                ],
+            ),
+            clade("NODE_4", "C", vec![lineage, clade("s4", "B", Vec::new())]),
+            serde_json::json!({ "name": "s5" }),
+        ],
+    );
+    let auspice_path = output_dir.join("auspice.json");
+    std::fs::write(
+        &auspice_path,
+        serde_json::json!({ "tree": tree }).to_string(),
+    )?;
+
+    // the auspice tree is detected when reading any phylogeny json
+    for phylogeny in [
+        Phylogeny::from_auspice_json(&auspice_path)?,
+        Phylogeny::read(&auspice_path)?,
+    ] {
+        let names = phylogeny.get_names()?.into_iter().collect::<BTreeSet<_>>();
+        let expected = ["root", "A", "B", "C", "C.1", "s5"];
+        assert_eq!(names, expected.into_iter().map(String::from).collect());
+
+        // consecutive nodes of the same clade are collapsed
+        assert_eq!(phylogeny.get_parents("A")?, vec!["root"]);
+        assert_eq!(phylogeny.get_parents("C")?, vec!["A"]);
+        assert_eq!(phylogeny.get_parents("C.1")?, vec!["C"]);
+        assert_eq!(phylogeny.get_parents("s5")?, vec!["A"]);
+        // and a clade in multiple places is not a recombinant
+        assert_eq!(phylogeny.get_parents("B")?.len(), 1);
+        assert!(phylogeny.recombinants.is_empty());
+    }
+
+    // files without a tree, or trees with unnamed nodes, are errors
+    std::fs::write(&auspice_path, r#"{"meta": {}}"#)?;
+    assert!(Phylogeny::from_auspice_json(&auspice_path).is_err());
+    std::fs::write(&auspice_path, r#"{"tree": {"children": [{"name": "a"}]}}"#)?;
+    assert!(Phylogeny::from_auspice_json(&auspice_path).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn open_fasta_extensions() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("open_fasta_extensions");
+    std::fs::create_dir_all(&output_dir)?;
+    let fasta = b">seq1\nACGT\n>seq2\nTTTT\n";
+
+    // every fasta extension, uncompressed and compressed
+    for ext in ["fasta", "fa", "fna", "fas"] {
+        for compression in ["", ".gz", ".zst"] {
+            let path = output_dir.join(format!("sequences.{ext}{compression}"));
+            let bytes = match compression {
+                ".gz" => {
+                    let mut encoder = flate2::write::GzEncoder::new(
+                        Vec::new(),
+                        flate2::Compression::default(),
+                    );
+                    encoder.write_all(fasta)?;
+                    encoder.finish()?
+                }
+                ".zst" => zstd::stream::encode_all(&fasta[..], 0)?,
+                _ => fasta.to_vec(),
+            };
+            std::fs::write(&path, bytes)?;
+
+            let ids = open_fasta(&path)?
+                .records()
+                .map(|record| record.map(|r| r.id().to_string()))
+                .collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(ids, vec!["seq1", "seq2"], "{path:?}");
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn read_populations_duplicates() -> Result<(), Report> {
+    let dataset_dir = PathBuf::from("tests").join("data").join("toy1");
+    let output_dir =
+        PathBuf::from("output").join("tests").join("read_populations_duplicates");
+    std::fs::create_dir_all(&output_dir)?;
+
+    let mask = vec![0, 0];
+    let reference = read_reference(&dataset_dir.join("reference.fasta"), &mask, None)?;
+    let path = output_dir.join("populations.fasta");
+    let fasta =
+        ">A\nCCCCCCAACCCCCCCCCCCC\n>B\nTTTTTTTTTTTTTTTTTTAA\n>A\nTTTTTTTTTTTTTTTTTTAA\n";
+    std::fs::write(&path, fasta)?;
+
+    // duplicates retain the first record
+    let populations = load::read_populations(&path, &reference, &mask, false)?;
+    assert_eq!(populations.len(), 2);
+    assert_ne!(
+        populations["A"].substitutions,
+        populations["B"].substitutions
+    );
+
+    // and are an error if strict
+    let strict = load::read_populations(&path, &reference, &mask, true);
+    assert!(strict.unwrap_err().to_string().ends_with(": A"));
+
+    Ok(())
+}
+
+#[test]
+fn warnings_tally() -> Result<(), Report> {
+    // warnings beyond the maximum are still counted
+    let mut warnings = Warnings::new(2);
+    (0..5).for_each(|i| warnings.warn("duplicate IDs dropped", &format!("s{i}")));
+    warnings.warn("invalid records skipped", "s5");
+
+    // counted warnings are only added if there are any
+    warnings.count("sequences with low coverage", 0);
+    assert!(!warnings.counts.contains_key("sequences with low coverage"));
+    warnings.count("sequences with low coverage", 3);
+    warnings.count("sequences with low coverage", 1);
+
+    let expected = BTreeMap::from([
+        ("duplicate IDs dropped".to_string(), 5),
+        ("invalid records skipped".to_string(), 1),
+        ("sequences with low coverage".to_string(), 4),
+    ]);
+    assert_eq!(warnings.counts, expected);
+    warnings.summary();
+
+    // no warnings are logged at all, but they are still counted
+    let mut warnings = Warnings::new(0);
+    warnings.warn("duplicate IDs dropped", "s1");
+    assert_eq!(warnings.counts["duplicate IDs dropped"], 1);
+
+    Ok(())
+}